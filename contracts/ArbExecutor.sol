@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: MIT
+pragma solidity 0.8.24;
+
+interface IERC20 {
+    function balanceOf(address account) external view returns (uint256);
+    function transfer(address to, uint256 amount) external returns (bool);
+}
+
+/// @title ArbExecutor
+/// @notice Runs a multi-leg swap route for its owner in one transaction, reverting unless the
+/// route hands back at least `minAmountOut` of the token it started with. The contract holds the
+/// working capital; each leg is a plain call (approval, transfer or pool swap) encoded off-chain
+/// by eth-rust's route builder.
+contract ArbExecutor {
+    struct Call {
+        address target;
+        bytes data;
+    }
+
+    address public immutable owner;
+
+    // Target of the call being made, the only address allowed into the swap callback
+    address private activeTarget;
+
+    error NotOwner();
+    error CallFailed(uint256 index, bytes reason);
+    error InsufficientOutput(uint256 amountOut, uint256 minAmountOut);
+    error UnexpectedCallback(address caller);
+    error TransferFailed();
+
+    event Executed(address indexed token, uint256 amountIn, uint256 amountOut);
+
+    modifier onlyOwner() {
+        if (msg.sender != owner) revert NotOwner();
+        _;
+    }
+
+    constructor() {
+        owner = msg.sender;
+    }
+
+    /// @notice Makes `calls` in order, spending `amountIn` of `token` held by the contract, and
+    /// reverts unless at least `minAmountOut` of `token` comes back
+    /// @return amountOut What the route returned for the `amountIn` it spent
+    function execute(address token, uint256 amountIn, uint256 minAmountOut, Call[] calldata calls)
+        external
+        onlyOwner
+        returns (uint256 amountOut)
+    {
+        uint256 balanceBefore = IERC20(token).balanceOf(address(this));
+        for (uint256 i = 0; i < calls.length; i++) {
+            activeTarget = calls[i].target;
+            (bool success, bytes memory reason) = calls[i].target.call(calls[i].data);
+            if (!success) revert CallFailed(i, reason);
+        }
+        activeTarget = address(0);
+
+        uint256 balanceAfter = IERC20(token).balanceOf(address(this));
+        amountOut = balanceAfter + amountIn > balanceBefore ? balanceAfter + amountIn - balanceBefore : 0;
+        if (amountOut < minAmountOut) revert InsufficientOutput(amountOut, minAmountOut);
+        emit Executed(token, amountIn, amountOut);
+    }
+
+    /// @notice Pays a Uniswap V3 pool the input it asks for mid-swap; `data` is the input token
+    function uniswapV3SwapCallback(int256 amount0Delta, int256 amount1Delta, bytes calldata data) external {
+        if (activeTarget == address(0) || msg.sender != activeTarget) revert UnexpectedCallback(msg.sender);
+        address tokenIn = abi.decode(data, (address));
+        uint256 amount = uint256(amount0Delta > 0 ? amount0Delta : amount1Delta);
+        _transfer(tokenIn, msg.sender, amount);
+    }
+
+    /// @notice Sends tokens held by the contract to `to`
+    function withdraw(address token, address to, uint256 amount) external onlyOwner {
+        _transfer(token, to, amount);
+    }
+
+    // Tolerates tokens such as USDT whose `transfer` returns nothing
+    function _transfer(address token, address to, uint256 amount) private {
+        (bool success, bytes memory result) = token.call(abi.encodeCall(IERC20.transfer, (to, amount)));
+        if (!success || (result.length > 0 && !abi.decode(result, (bool)))) revert TransferFailed();
+    }
+}
@@ -0,0 +1,28 @@
+#!/usr/bin/env sh
+# Compiles the executor and the test mocks into contracts/out with a pinned solc: a local solc of
+# exactly this version if there is one, otherwise the official Docker image. Only the committed
+# artifacts are copied over, byte for byte as solc writes them, so a rebuild shows up in git only
+# when the sources changed.
+set -eu
+
+SOLC_VERSION=0.8.24
+CONTRACTS="ArbExecutor MockERC20 MockPair"
+
+cd "$(dirname "$0")"
+build=$(mktemp -d build.XXXXXX)
+trap 'rm -rf "$build"' EXIT
+
+if command -v solc >/dev/null 2>&1 && solc --version | grep -q "Version: ${SOLC_VERSION}+"; then
+    set -- solc
+else
+    set -- docker run --rm -v "$PWD:/contracts" -w /contracts "ethereum/solc:${SOLC_VERSION}"
+fi
+
+"$@" --optimize --optimize-runs 200 --evm-version cancun --metadata-hash none \
+    --bin --abi -o "$build" \
+    ArbExecutor.sol test/MockERC20.sol test/MockPair.sol
+
+mkdir -p out
+for contract in $CONTRACTS; do
+    cp "$build/$contract.abi" "$build/$contract.bin" out/
+done
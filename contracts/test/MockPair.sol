@@ -0,0 +1,22 @@
+// SPDX-License-Identifier: MIT
+pragma solidity 0.8.24;
+
+import {MockERC20} from "./MockERC20.sol";
+
+/// @title MockPair
+/// @notice Stands in for a Uniswap V2 pair in the executor tests: `swap` pays out whatever it is
+/// asked for from the pair's balances, with no invariant check
+contract MockPair {
+    MockERC20 public immutable token0;
+    MockERC20 public immutable token1;
+
+    constructor(MockERC20 _token0, MockERC20 _token1) {
+        token0 = _token0;
+        token1 = _token1;
+    }
+
+    function swap(uint256 amount0Out, uint256 amount1Out, address to, bytes calldata) external {
+        if (amount0Out > 0) token0.transfer(to, amount0Out);
+        if (amount1Out > 0) token1.transfer(to, amount1Out);
+    }
+}
@@ -0,0 +1,334 @@
+use ethers::prelude::*;
+use ethers::utils::to_checksum;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::account::multicall_balances::BalanceMatrix;
+use crate::account::token_balances::get_token_balances;
+use crate::account::token_registry::TokenRegistry;
+use crate::account::wallet::{keystore_dir, read_secret, Wallet};
+use crate::contracts::{parse_checksummed, ChainAddresses};
+use crate::output::{Report, Table};
+
+// File name of the account set inside the keystore dir
+pub const ACCOUNTS_FILE: &str = "accounts.json";
+// BIP-44 account path; the address index is appended
+pub const DEFAULT_ACCOUNT_PATH_PREFIX: &str = "m/44'/60'/0'/0";
+// Consecutive unused addresses after which discovery stops, as in BIP-44
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+pub fn accounts_path() -> PathBuf {
+    keystore_dir().join(ACCOUNTS_FILE)
+}
+
+/// A derived account; only the address is stored, the key is re-derived from the mnemonic when signing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Account {
+    pub index: u32,
+    pub label: Option<String>,
+    pub address: Address,
+    pub derivation_path: String,
+}
+
+/// Accounts derived from one mnemonic, persisted in the keystore dir
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccountSet {
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    pub accounts: Vec<Account>,
+}
+
+impl AccountSet {
+    /// Opens the account set at `path`, empty if the file doesn't exist yet
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut set = if path.exists() {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read accounts {}", path.display()))?;
+            serde_json::from_str::<Self>(&content)
+                .with_context(|| format!("Invalid accounts file {}", path.display()))?
+        } else {
+            Self::default()
+        };
+        set.path = Some(path.to_path_buf());
+        Ok(set)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write accounts {}", path.display()))
+    }
+
+    /// Derives accounts `0..count`, keeping existing labels and rejecting a different mnemonic
+    pub fn derive(&mut self, phrase: &str, count: u32) -> Result<Vec<Account>> {
+        (0..count).map(|index| self.derive_index(phrase, index)).collect()
+    }
+
+    fn derive_index(&mut self, phrase: &str, index: u32) -> Result<Account> {
+        let derivation_path = format!("{}/{}", DEFAULT_ACCOUNT_PATH_PREFIX, index);
+        let address = Wallet::from_mnemonic(phrase, &derivation_path)?.address;
+
+        if let Some(existing) = self.accounts.iter().find(|account| account.index == index) {
+            if existing.address != address {
+                bail!(
+                    "Account {} is {} but the mnemonic derives {}; the account set was created from a different mnemonic",
+                    index,
+                    to_checksum(&existing.address, None),
+                    to_checksum(&address, None)
+                );
+            }
+            return Ok(existing.clone());
+        }
+
+        let account = Account {
+            index,
+            label: None,
+            address,
+            derivation_path,
+        };
+        self.accounts.push(account.clone());
+        self.accounts.sort_by_key(|account| account.index);
+        Ok(account)
+    }
+
+    /// Finds an account by label, index or address
+    pub fn get(&self, selector: &str) -> Result<&Account> {
+        let found = if let Ok(index) = selector.parse::<u32>() {
+            self.accounts.iter().find(|account| account.index == index)
+        } else if selector.starts_with("0x") {
+            let address = parse_checksummed(selector)?;
+            self.accounts.iter().find(|account| account.address == address)
+        } else {
+            self.accounts.iter().find(|account| account.label.as_deref() == Some(selector))
+        };
+        found.ok_or_else(|| anyhow!("No account '{}' (see `accounts list`)", selector))
+    }
+
+    /// An address, or the address of the account with that label or index
+    pub fn resolve(&self, address_or_selector: &str) -> Result<Address> {
+        match self.get(address_or_selector) {
+            Ok(account) => Ok(account.address),
+            Err(_) if address_or_selector.starts_with("0x") => parse_checksummed(address_or_selector),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn set_label(&mut self, selector: &str, label: &str) -> Result<Account> {
+        if label.parse::<u32>().is_ok() || label.starts_with("0x") {
+            bail!("Label '{}' would be ambiguous with an index or address", label);
+        }
+        if let Some(other) = self.accounts.iter().find(|account| account.label.as_deref() == Some(label)) {
+            bail!("Label '{}' is already used by account {}", label, other.index);
+        }
+        let index = self.get(selector)?.index;
+        let account = self
+            .accounts
+            .iter_mut()
+            .find(|account| account.index == index)
+            .expect("account was just found");
+        account.label = Some(label.to_string());
+        Ok(account.clone())
+    }
+
+    pub fn addresses(&self) -> Vec<Address> {
+        self.accounts.iter().map(|account| account.address).collect()
+    }
+
+    /// Signing wallet for an account, re-derived from `ETH_RUST_MNEMONIC` (or a prompt)
+    pub fn wallet(&self, selector: &str) -> Result<Wallet> {
+        let account = self.get(selector)?;
+        let phrase = read_secret("ETH_RUST_MNEMONIC", "Mnemonic: ")?;
+        let wallet = Wallet::from_mnemonic(&phrase, &account.derivation_path)?;
+        if wallet.address != account.address {
+            bail!("The mnemonic does not derive account {} ({})", account.index, to_checksum(&account.address, None));
+        }
+        Ok(wallet)
+    }
+
+    /// Derives accounts until `gap_limit` consecutive ones have no balance and no transactions
+    pub async fn discover<M: Middleware + 'static>(
+        &mut self,
+        provider: Arc<M>,
+        registry: &mut TokenRegistry,
+        addresses: &ChainAddresses,
+        phrase: &str,
+        symbols: &[&str],
+        gap_limit: u32,
+    ) -> Result<DiscoveryResult> {
+        let gap_limit = gap_limit.max(1);
+        let known: Vec<u32> = self.accounts.iter().map(|account| account.index).collect();
+        let mut matrices: Vec<BalanceMatrix> = Vec::new();
+        // Used accounts, with their row numbered across all fetched matrices
+        let mut used: Vec<(Account, usize)> = Vec::new();
+        let mut offset = 0;
+        let mut next = 0;
+        let mut gap = 0;
+
+        // Each batch covers exactly the addresses that could still end the scan
+        while gap < gap_limit {
+            let batch = (next..next + gap_limit - gap)
+                .map(|index| self.derive_index(phrase, index))
+                .collect::<Result<Vec<_>>>()?;
+            next += batch.len() as u32;
+
+            let wallets: Vec<Address> = batch.iter().map(|account| account.address).collect();
+            let matrix = get_token_balances(provider.clone(), registry, addresses, &wallets, symbols).await?;
+
+            for (row, account) in batch.into_iter().enumerate() {
+                // Only check the nonce when there are no balances, to save round trips
+                if holds_funds(&matrix, row) || !provider.get_transaction_count(account.address, None).await?.is_zero() {
+                    used.push((account, offset + row));
+                    gap = 0;
+                } else {
+                    gap += 1;
+                }
+            }
+            offset += matrix.wallets.len();
+            matrices.push(matrix);
+        }
+
+        // Persist accounts found in use; empty ones scanned past are dropped unless derived before
+        let used_indices: Vec<u32> = used.iter().map(|(account, _)| account.index).collect();
+        self.accounts
+            .retain(|account| known.contains(&account.index) || used_indices.contains(&account.index));
+
+        Ok(DiscoveryResult {
+            scanned: next,
+            balances: merge_rows(&matrices, used.iter().map(|(_, row)| *row)),
+            accounts: used.into_iter().map(|(account, _)| account).collect(),
+        })
+    }
+}
+
+// Whether a wallet row has a non-zero ETH or token balance
+fn holds_funds(matrix: &BalanceMatrix, row: usize) -> bool {
+    matrix.eth[row].is_some_and(|balance| !balance.is_zero())
+        || matrix.balances[row].iter().any(|balance| balance.is_some_and(|b| !b.is_zero()))
+}
+
+// Picks rows (numbered across all matrices) out of matrices that share the same token columns
+fn merge_rows(matrices: &[BalanceMatrix], rows: impl Iterator<Item = usize>) -> BalanceMatrix {
+    let mut merged = BalanceMatrix {
+        wallets: Vec::new(),
+        native_symbol: matrices.first().map(|m| m.native_symbol.clone()).unwrap_or_default(),
+        tokens: matrices.first().map(|m| m.tokens.clone()).unwrap_or_default(),
+        eth: Vec::new(),
+        balances: Vec::new(),
+    };
+    let all: Vec<_> = matrices
+        .iter()
+        .flat_map(|m| (0..m.wallets.len()).map(move |row| (m, row)))
+        .collect();
+    for row in rows {
+        let (matrix, row) = all[row];
+        merged.wallets.push(matrix.wallets[row]);
+        merged.eth.push(matrix.eth[row]);
+        merged.balances.push(matrix.balances[row].clone());
+    }
+    merged
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveryResult {
+    pub scanned: u32,
+    pub accounts: Vec<Account>,
+    pub balances: BalanceMatrix,
+}
+
+impl Report for DiscoveryResult {
+    fn text(&self) -> String {
+        let mut lines = vec![format!(
+            "🔎 Scanned {} addresses, {} in use",
+            self.scanned,
+            self.accounts.len()
+        )];
+        for account in &self.accounts {
+            lines.push(format!("#{} {}", account.index, display_account(account)));
+        }
+        if !self.accounts.is_empty() {
+            lines.push(String::new());
+            lines.push(self.balances.text());
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        self.balances.table()
+    }
+}
+
+impl Report for Account {
+    fn text(&self) -> String {
+        format!("#{} {}\nPath: {}", self.index, display_account(self), self.derivation_path)
+    }
+
+    fn table(&self) -> Table {
+        AccountSet { path: None, accounts: vec![self.clone()] }.table()
+    }
+}
+
+impl Report for AccountSet {
+    fn text(&self) -> String {
+        if self.accounts.is_empty() {
+            return "No accounts yet, derive some with `accounts derive`".to_string();
+        }
+        self.accounts
+            .iter()
+            .map(|account| format!("#{} {}", account.index, display_account(account)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(["index", "label", "address", "path"]);
+        for account in &self.accounts {
+            table.row([
+                account.index.to_string(),
+                account.label.clone().unwrap_or_default(),
+                to_checksum(&account.address, None),
+                account.derivation_path.clone(),
+            ]);
+        }
+        table
+    }
+}
+
+fn display_account(account: &Account) -> String {
+    match &account.label {
+        Some(label) => format!("{} ({})", to_checksum(&account.address, None), label),
+        None => to_checksum(&account.address, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHRASE: &str = "test test test test test test test test test test test junk";
+
+    #[test]
+    fn test_derive_label_and_select() {
+        let mut set = AccountSet::default();
+        let accounts = set.derive(PHRASE, 3).unwrap();
+        assert_eq!(accounts[1].address, "0x70997970C51812dc3A010C7d01b50e0d17dc79C8".parse::<Address>().unwrap());
+
+        set.set_label("1", "trading").unwrap();
+        assert_eq!(set.get("trading").unwrap().index, 1);
+        assert_eq!(set.resolve("2").unwrap(), accounts[2].address);
+        assert!(set.set_label("2", "trading").is_err());
+        assert!(set.set_label("2", "7").is_err());
+
+        // Re-deriving keeps labels; a different mnemonic is rejected
+        set.derive(PHRASE, 4).unwrap();
+        assert_eq!(set.get("1").unwrap().label.as_deref(), Some("trading"));
+        let other = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        assert!(set.derive(other, 1).is_err());
+    }
+}
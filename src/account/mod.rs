@@ -1,4 +1,7 @@
 pub mod wallet;
+pub mod accounts;
 pub mod token_balances;
+pub mod multicall_balances;
 pub mod token_transfer;
-pub mod token;
\ No newline at end of file
+pub mod token;
+pub mod token_registry;
\ No newline at end of file
@@ -0,0 +1,198 @@
+use ethers::abi::Token;
+use ethers::contract::{Multicall, MULTICALL_ADDRESS};
+use ethers::prelude::*;
+use std::sync::Arc;
+use anyhow::Result;
+use serde::Serialize;
+use crate::account::token::ERC20;
+use crate::account::token_registry::TokenInfo;
+use crate::amount::TokenAmount;
+use crate::output::{Report, Table};
+
+// Calls per `aggregate3`; large enough to amortise round trips, small enough for public RPC gas caps
+pub const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// Native and ERC-20 balances for every (wallet, token) pair; `None` marks a call that reverted
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceMatrix {
+    pub wallets: Vec<Address>,
+    // Symbol of the chain's native currency, whose balances are in `eth`
+    pub native_symbol: String,
+    pub tokens: Vec<TokenInfo>,
+    pub eth: Vec<Option<TokenAmount>>,
+    pub balances: Vec<Vec<Option<TokenAmount>>>,
+}
+
+impl BalanceMatrix {
+    pub fn eth_balance(&self, wallet: Address) -> Option<TokenAmount> {
+        let row = self.wallets.iter().position(|w| *w == wallet)?;
+        self.eth[row]
+    }
+
+    pub fn token_balance(&self, wallet: Address, token: Address) -> Option<TokenAmount> {
+        let row = self.wallets.iter().position(|w| *w == wallet)?;
+        let column = self.tokens.iter().position(|t| t.address == token)?;
+        self.balances[row][column]
+    }
+
+    /// Tokens whose `balanceOf` reverted for at least one wallet
+    pub fn failed_tokens(&self) -> Vec<&TokenInfo> {
+        self.tokens
+            .iter()
+            .enumerate()
+            .filter(|(column, _)| self.balances.iter().any(|row| row[*column].is_none()))
+            .map(|(_, token)| token)
+            .collect()
+    }
+}
+
+impl Report for BalanceMatrix {
+    fn text(&self) -> String {
+        let mut lines = Vec::new();
+        for (row, wallet) in self.wallets.iter().enumerate() {
+            lines.push(format!("Wallet Balance: {:?}", wallet));
+            lines.push(format!("{}: {}", self.native_symbol, display_balance(self.eth[row])));
+            for (column, token) in self.tokens.iter().enumerate() {
+                lines.push(format!("{}: {}", token.symbol, display_balance(self.balances[row][column])));
+            }
+            lines.push(String::new());
+        }
+        lines.join("\n").trim_end().to_string()
+    }
+
+    fn table(&self) -> Table {
+        let headers = ["wallet".to_string(), self.native_symbol.clone()]
+            .into_iter()
+            .chain(self.tokens.iter().map(|token| token.symbol.clone()));
+        let mut table = Table::new(headers);
+        for (row, wallet) in self.wallets.iter().enumerate() {
+            let cells = [format!("{:?}", wallet), display_balance(self.eth[row])]
+                .into_iter()
+                .chain(self.balances[row].iter().map(|balance| display_balance(*balance)));
+            table.row(cells);
+        }
+        table
+    }
+}
+
+fn display_balance(balance: Option<TokenAmount>) -> String {
+    balance.map(|b| b.to_string()).unwrap_or_else(|| "<reverted>".to_string())
+}
+
+// A single balance lookup: which wallet, and which token (`None` for ETH)
+#[derive(Clone, Copy)]
+struct Query {
+    wallet: usize,
+    token: Option<usize>,
+}
+
+/// Aggregates balance lookups into Multicall3 `aggregate3` calls
+pub struct BalanceEngine<M> {
+    provider: Arc<M>,
+    multicall_address: Address,
+    native_symbol: String,
+    batch_size: usize,
+}
+
+impl<M: Middleware + 'static> BalanceEngine<M> {
+    /// Uses the canonical Multicall3 deployment unless another address is given
+    pub fn new(provider: Arc<M>, multicall_address: Option<Address>, native_symbol: &str) -> Self {
+        Self {
+            provider,
+            multicall_address: multicall_address.unwrap_or(MULTICALL_ADDRESS),
+            native_symbol: native_symbol.to_string(),
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub async fn fetch(&self, wallets: &[Address], tokens: &[TokenInfo]) -> Result<BalanceMatrix> {
+        let mut matrix = BalanceMatrix {
+            wallets: wallets.to_vec(),
+            native_symbol: self.native_symbol.clone(),
+            tokens: tokens.to_vec(),
+            eth: vec![None; wallets.len()],
+            balances: vec![vec![None; tokens.len()]; wallets.len()],
+        };
+
+        let queries: Vec<Query> = (0..wallets.len())
+            .flat_map(|wallet| {
+                std::iter::once(Query { wallet, token: None })
+                    .chain((0..tokens.len()).map(move |token| Query { wallet, token: Some(token) }))
+            })
+            .collect();
+
+        let contracts: Vec<ERC20<M>> = tokens
+            .iter()
+            .map(|token| ERC20::new(token.address, self.provider.clone()))
+            .collect();
+
+        for batch in queries.chunks(self.batch_size) {
+            let mut multicall = Multicall::new(self.provider.clone(), Some(self.multicall_address)).await?;
+            for query in batch {
+                let wallet = wallets[query.wallet];
+                match query.token {
+                    None => multicall.add_get_eth_balance(wallet, true),
+                    Some(token) => multicall.add_call(contracts[token].balance_of(wallet), true),
+                };
+            }
+
+            // Each result is `Err(revert data)` for calls that failed, which we record as `None`
+            let results = multicall.call_raw().await?;
+            for (query, result) in batch.iter().zip(results) {
+                let raw = match result {
+                    Ok(Token::Uint(raw)) => Some(raw),
+                    _ => None,
+                };
+                match query.token {
+                    None => matrix.eth[query.wallet] = raw.map(|raw| TokenAmount::new(raw, 18)),
+                    Some(token) => {
+                        matrix.balances[query.wallet][token] =
+                            raw.map(|raw| TokenAmount::new(raw, tokens[token].decimals));
+                    }
+                }
+            }
+        }
+
+        Ok(matrix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi;
+
+    #[tokio::test]
+    async fn test_fetch_tolerates_reverting_token() {
+        let (provider, mock) = Provider::mocked();
+        let wallet = Address::from_low_u64_be(1);
+        let token = TokenInfo {
+            chain_id: 1,
+            address: Address::from_low_u64_be(2),
+            symbol: "BAD".to_string(),
+            name: "Reverting Token".to_string(),
+            decimals: 6,
+        };
+
+        // aggregate3 returns (bool success, bytes returnData)[]: ETH succeeds, balanceOf reverts
+        let eth_balance = abi::encode(&[Token::Uint(U256::exp10(18))]);
+        let response = abi::encode(&[Token::Array(vec![
+            Token::Tuple(vec![Token::Bool(true), Token::Bytes(eth_balance)]),
+            Token::Tuple(vec![Token::Bool(false), Token::Bytes(vec![])]),
+        ])]);
+        mock.push::<Bytes, _>(Bytes::from(response)).unwrap();
+
+        let engine = BalanceEngine::new(Arc::new(provider), None, "POL");
+        let matrix = engine.fetch(&[wallet], std::slice::from_ref(&token)).await.unwrap();
+
+        assert_eq!(matrix.eth_balance(wallet).unwrap().to_string(), "1");
+        assert!(matrix.text().contains("POL: 1"));
+        assert!(matrix.token_balance(wallet, token.address).is_none());
+        assert_eq!(matrix.failed_tokens(), vec![&token]);
+    }
+}
@@ -1,6 +1,8 @@
 use ethers::prelude::*;
 use std::sync::Arc;
 use anyhow::Result;
+use crate::account::token_registry::TokenRegistry;
+use crate::amount::TokenAmount;
 
 
 abigen!(
@@ -12,20 +14,19 @@ abigen!(
     ]"
 );
 
-pub async fn get_eth_balance(provider: Arc<Provider<Http>>, address: Address) -> Result<U256> {
+pub async fn get_eth_balance<M: Middleware + 'static>(provider: Arc<M>, address: Address) -> Result<U256> {
     let balance = provider.get_balance(address, None).await?;
     Ok(balance)
 }
 
-pub async fn get_erc20_balance(
-    provider: Arc<Provider<Http>>,
+pub async fn get_erc20_balance<M: Middleware + 'static>(
+    provider: Arc<M>,
+    registry: &mut TokenRegistry,
     token_address: Address,
     address: Address,
-) -> Result<(String, f64)> {
+) -> Result<(String, TokenAmount)> {
+    let metadata = registry.metadata(provider.clone(), token_address).await?;
     let token = ERC20::new(token_address, provider.clone());
     let balance = token.balance_of(address).call().await?;
-    let decimals = token.decimals().call().await?;
-    let symbol = token.symbol().call().await?;
-    let balance_float = balance.as_u128() as f64 / 10f64.powi(decimals as i32);
-    Ok((symbol, balance_float))
+    Ok((metadata.symbol, TokenAmount::new(balance, metadata.decimals)))
 } 
\ No newline at end of file
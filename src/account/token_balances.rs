@@ -1,55 +1,52 @@
 use ethers::prelude::*;
-use std::collections::HashMap;
 use std::sync::Arc;
 use anyhow::Result;
-use crate::account::token::ERC20;
-use crate::contracts::{USDT_CONTRACT, USDC_CONTRACT, DAI_CONTRACT, WBTC_CONTRACT, LINK_CONTRACT, CSUSDL_CONTRACT};
+use crate::account::multicall_balances::{BalanceEngine, BalanceMatrix};
+use crate::account::token_registry::{TokenInfo, TokenRegistry};
+use crate::contracts::{ChainAddresses, USDT, USDC, DAI, WBTC, LINK, CSUSDL, MULTICALL3};
 
-// Example mapping for popular tokens on Ethereum mainnet
-fn token_address_map() -> HashMap<&'static str, &'static str> {
-    let mut map = HashMap::new();
-    map.insert("USDT", USDT_CONTRACT);
-    map.insert("USDC", USDC_CONTRACT);
-    map.insert("DAI",  DAI_CONTRACT);
-    map.insert("WBTC", WBTC_CONTRACT);
-    map.insert("LINK", LINK_CONTRACT);
-    map.insert("CSUSDL", CSUSDL_CONTRACT);
-    map
-}
+// Popular tokens checked when no symbols are given
+const DEFAULT_TOKENS: [&str; 6] = [USDT, USDC, DAI, WBTC, LINK, CSUSDL];
 
-pub async fn get_token_balances(
-    provider: Arc<Provider<Http>>,
-    address: Address,
+/// Resolves symbols to token metadata; token lists take precedence, the address book covers the rest
+pub async fn resolve_tokens<M: Middleware + 'static>(
+    provider: Arc<M>,
+    registry: &mut TokenRegistry,
+    addresses: &ChainAddresses,
     symbols: &[&str],
-) -> Result<Vec<(String, f64)>> {
-    let map = token_address_map();
-    let mut balances = Vec::new();
+) -> Result<Vec<TokenInfo>> {
+    let mut tokens = Vec::new();
+    for symbol in symbols {
+        let token_address = match registry.resolve(symbol) {
+            Ok(token_address) => token_address,
+            Err(_) => addresses.get(symbol)?,
+        };
+        tokens.push(registry.metadata(provider.clone(), token_address).await?);
+    }
+    Ok(tokens)
+}
 
-    // If symbols is empty, use all keys from the map
+/// The native currency plus the given tokens for every wallet, fetched through Multicall3
+pub async fn get_token_balances<M: Middleware + 'static>(
+    provider: Arc<M>,
+    registry: &mut TokenRegistry,
+    addresses: &ChainAddresses,
+    wallets: &[Address],
+    symbols: &[&str],
+) -> Result<BalanceMatrix> {
+    // If symbols is empty, use the default tokens known on this chain
     let tokens_to_check: Vec<&str> = if symbols.is_empty() {
-        map.keys().copied().collect()
+        DEFAULT_TOKENS
+            .iter()
+            .copied()
+            .filter(|symbol| registry.by_symbol(symbol).is_some() || addresses.contains(symbol))
+            .collect()
     } else {
-        symbols.to_vec()
+        symbols.iter().copied().filter(|symbol| !symbol.eq_ignore_ascii_case(&addresses.native_symbol)).collect()
     };
 
-    
+    let tokens = resolve_tokens(provider.clone(), registry, addresses, &tokens_to_check).await?;
 
-    for symbol in tokens_to_check {
-        if symbol == "ETH" {
-            let balance = provider.get_balance(address, None).await?;
-            balances.push(("ETH".to_string(), balance.as_u128() as f64 / 10f64.powi(18 as i32)));
-            continue;
-        }
-        if let Some(&token_addr) = map.get(symbol) {
-            let token_address: Address = token_addr.parse()?;
-            let token = ERC20::new(token_address, provider.clone());
-            let balance = token.balance_of(address).call().await?;
-            let decimals = token.decimals().call().await?;
-            let balance_float = balance.as_u128() as f64 / 10f64.powi(decimals as i32);
-            balances.push((symbol.to_string(), balance_float));
-        } else {
-            balances.push((symbol.to_string(), 0.0)); // or return an error if preferred
-        }
-    }
-    Ok(balances)
-} 
\ No newline at end of file
+    let engine = BalanceEngine::new(provider.clone(), addresses.get(MULTICALL3).ok(), &addresses.native_symbol);
+    engine.fetch(wallets, &tokens).await
+}
@@ -0,0 +1,239 @@
+use ethers::abi::{self, ParamType, Token};
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{anyhow, Context, Result};
+use crate::account::token::ERC20;
+use crate::contracts::parse_checksummed;
+
+// Function selectors for the metadata getters, called raw so bytes32 returns can be handled
+const SYMBOL_SELECTOR: [u8; 4] = [0x95, 0xd8, 0x9b, 0x41];
+const NAME_SELECTOR: [u8; 4] = [0x06, 0xfd, 0xde, 0x03];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub chain_id: u64,
+    pub address: Address,
+    pub symbol: String,
+    pub name: String,
+    pub decimals: u8,
+}
+
+// Subset of the Uniswap token-list schema (https://tokenlists.org)
+#[derive(Debug, Deserialize)]
+struct TokenList {
+    tokens: Vec<TokenListEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenListEntry {
+    chain_id: u64,
+    address: String,
+    symbol: String,
+    name: String,
+    decimals: u8,
+}
+
+/// Token metadata for one chain, resolvable by symbol or address and persisted between runs
+pub struct TokenRegistry {
+    chain_id: u64,
+    cache_path: Option<PathBuf>,
+    by_address: BTreeMap<Address, TokenInfo>,
+    by_symbol: HashMap<String, Address>,
+}
+
+impl TokenRegistry {
+    pub fn new(chain_id: u64) -> Self {
+        Self {
+            chain_id,
+            cache_path: None,
+            by_address: BTreeMap::new(),
+            by_symbol: HashMap::new(),
+        }
+    }
+
+    /// Opens the registry for a chain, loading `tokens-<chain_id>.json` from the cache dir if present
+    pub fn open(chain_id: u64, cache_dir: impl AsRef<Path>) -> Result<Self> {
+        let cache_path = cache_dir.as_ref().join(format!("tokens-{}.json", chain_id));
+        let mut registry = Self::new(chain_id);
+
+        if cache_path.exists() {
+            let content = std::fs::read_to_string(&cache_path)
+                .with_context(|| format!("Failed to read token cache {}", cache_path.display()))?;
+            let tokens: Vec<TokenInfo> = serde_json::from_str(&content)
+                .with_context(|| format!("Invalid token cache {}", cache_path.display()))?;
+            for token in tokens {
+                registry.insert(token);
+            }
+        }
+
+        registry.cache_path = Some(cache_path);
+        Ok(registry)
+    }
+
+    /// Loads a Uniswap token-list JSON file, keeping only tokens for this chain
+    pub fn load_token_list(&mut self, path: impl AsRef<Path>) -> Result<usize> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read token list {}", path.display()))?;
+        let list: TokenList = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid token list {}", path.display()))?;
+
+        let chain_id = self.chain_id;
+        let mut count = 0;
+        for entry in list.tokens.into_iter().filter(|entry| entry.chain_id == chain_id) {
+            let address = parse_checksummed(&entry.address)
+                .with_context(|| format!("Token {} in {}", entry.symbol, path.display()))?;
+            self.insert(TokenInfo {
+                chain_id: entry.chain_id,
+                address,
+                symbol: entry.symbol,
+                name: entry.name,
+                decimals: entry.decimals,
+            });
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    pub fn insert(&mut self, token: TokenInfo) {
+        self.by_symbol.insert(token.symbol.to_uppercase(), token.address);
+        self.by_address.insert(token.address, token);
+    }
+
+    pub fn by_symbol(&self, symbol: &str) -> Option<&TokenInfo> {
+        self.by_symbol
+            .get(&symbol.to_uppercase())
+            .and_then(|address| self.by_address.get(address))
+    }
+
+    pub fn by_address(&self, address: Address) -> Option<&TokenInfo> {
+        self.by_address.get(&address)
+    }
+
+    /// Resolves a symbol (case-insensitive) or a hex address to a token address
+    pub fn resolve(&self, symbol_or_address: &str) -> Result<Address> {
+        if symbol_or_address.starts_with("0x") {
+            return parse_checksummed(symbol_or_address);
+        }
+        self.by_symbol(symbol_or_address)
+            .map(|token| token.address)
+            .ok_or_else(|| anyhow!("Unknown token '{}' on chain id {}", symbol_or_address, self.chain_id))
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = &TokenInfo> {
+        self.by_address.values()
+    }
+
+    /// Returns cached metadata for a token, fetching it on-chain on first use
+    pub async fn metadata<M: Middleware + 'static>(
+        &mut self,
+        provider: Arc<M>,
+        address: Address,
+    ) -> Result<TokenInfo> {
+        if let Some(token) = self.by_address.get(&address) {
+            return Ok(token.clone());
+        }
+        let token = fetch_token_info(provider, self.chain_id, address).await?;
+        self.insert(token.clone());
+        Ok(token)
+    }
+
+    /// Writes the registry to its cache file, if it was opened with one
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.cache_path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tokens: Vec<&TokenInfo> = self.by_address.values().collect();
+        std::fs::write(path, serde_json::to_string_pretty(&tokens)?)
+            .with_context(|| format!("Failed to write token cache {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Fetches symbol, name and decimals for a token, accepting `string` or `bytes32` metadata
+pub async fn fetch_token_info<M: Middleware + 'static>(
+    provider: Arc<M>,
+    chain_id: u64,
+    address: Address,
+) -> Result<TokenInfo> {
+    let decimals = ERC20::new(address, provider.clone()).decimals().call().await?;
+    let symbol = call_string_getter(provider.as_ref(), address, SYMBOL_SELECTOR).await?;
+    let name = call_string_getter(provider.as_ref(), address, NAME_SELECTOR)
+        .await
+        .unwrap_or_else(|_| symbol.clone());
+
+    Ok(TokenInfo {
+        chain_id,
+        address,
+        symbol,
+        name,
+        decimals,
+    })
+}
+
+async fn call_string_getter<M: Middleware>(
+    provider: &M,
+    address: Address,
+    selector: [u8; 4],
+) -> Result<String> {
+    let tx: TypedTransaction = TransactionRequest::new()
+        .to(address)
+        .data(Bytes::from(selector.to_vec()))
+        .into();
+    let output = provider
+        .call(&tx, None)
+        .await
+        .map_err(|e| anyhow!("Metadata call to {:?} failed: {}", address, e))?;
+    decode_string_or_bytes32(&output)
+}
+
+/// Decodes an ABI `string` return value, or a null-padded `bytes32` as used by e.g. MKR
+pub fn decode_string_or_bytes32(output: &[u8]) -> Result<String> {
+    if output.len() == 32 {
+        let end = output.iter().position(|b| *b == 0).unwrap_or(32);
+        return Ok(String::from_utf8_lossy(&output[..end]).into_owned());
+    }
+    match abi::decode(&[ParamType::String], output)?.pop() {
+        Some(Token::String(value)) => Ok(value),
+        _ => Err(anyhow!("Unexpected metadata return value")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_string_or_bytes32() {
+        let mut mkr = [0u8; 32];
+        mkr[..3].copy_from_slice(b"MKR");
+        assert_eq!(decode_string_or_bytes32(&mkr).unwrap(), "MKR");
+
+        let encoded = abi::encode(&[Token::String("USDC".to_string())]);
+        assert_eq!(decode_string_or_bytes32(&encoded).unwrap(), "USDC");
+    }
+
+    #[test]
+    fn test_resolve_both_ways() {
+        let mut registry = TokenRegistry::new(1);
+        let address: Address = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".parse().unwrap();
+        registry.insert(TokenInfo {
+            chain_id: 1,
+            address,
+            symbol: "USDC".to_string(),
+            name: "USD Coin".to_string(),
+            decimals: 6,
+        });
+        assert_eq!(registry.resolve("usdc").unwrap(), address);
+        assert_eq!(registry.by_address(address).unwrap().symbol, "USDC");
+        assert!(registry.resolve("DAI").is_err());
+    }
+}
@@ -1,8 +1,10 @@
 use ethers::prelude::*;
-use ethers::utils::parse_units;
 use ethers::types::transaction::eip2718::TypedTransaction;
+use serde::Serialize;
 use std::sync::Arc;
 use eyre::Result;
+use crate::amount::TokenAmount;
+use crate::output::{Report, Table};
 
 abigen!(
     ERC20,
@@ -11,30 +13,128 @@ abigen!(
         function approve(address spender, uint256 value) external returns (bool)
         function transferFrom(address from, address to, uint256 value) external returns (bool)
         function allowance(address owner, address spender) external view returns (uint256)
-        function decimals() external view returns (uint8)
     ]"#
 );
 
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferResult {
+    pub from: Address,
+    pub to: Address,
+    // None for native ETH transfers
+    pub token: Option<Address>,
+    pub amount: TokenAmount,
+    pub gas_estimate: U256,
+    pub gas_price: U256,
+    pub gas_fee: TokenAmount,
+    pub dry_run: bool,
+    pub tx_hash: Option<H256>,
+}
+
+impl Report for TransferResult {
+    fn text(&self) -> String {
+        let asset = match self.token {
+            Some(token) => format!("{:?}", token),
+            None => "ETH".to_string(),
+        };
+        let mut lines = vec![
+            format!("From: {:?}", self.from),
+            format!("To: {:?}", self.to),
+            format!("Amount: {} {}", self.amount, asset),
+            format!("⛽ Estimated gas: {}", self.gas_estimate),
+            format!("💰 Gas price: {} wei", self.gas_price),
+            format!("🔢 Estimated total gas fee: {} ETH", self.gas_fee),
+        ];
+        match self.tx_hash {
+            Some(tx_hash) => lines.push(format!("✅ Transfer complete: {:?}", tx_hash)),
+            None => lines.push("🚀 Ready to send transaction (dry run, not sent)".to_string()),
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        Table::key_value([
+            ("from", format!("{:?}", self.from)),
+            ("to", format!("{:?}", self.to)),
+            ("token", self.token.map(|t| format!("{:?}", t)).unwrap_or_else(|| "ETH".to_string())),
+            ("amount", self.amount.to_string()),
+            ("gas_estimate", self.gas_estimate.to_string()),
+            ("gas_price", self.gas_price.to_string()),
+            ("gas_fee", self.gas_fee.to_string()),
+            ("tx_hash", self.tx_hash.map(|h| format!("{:?}", h)).unwrap_or_else(|| "-".to_string())),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApprovalResult {
+    pub token: Address,
+    pub owner: Address,
+    pub spender: Address,
+    pub gas_estimate: U256,
+    pub dry_run: bool,
+    pub tx_hash: Option<H256>,
+    // The allowance after the approval, or the current one on a dry run
+    pub allowance: TokenAmount,
+}
+
+impl Report for ApprovalResult {
+    fn text(&self) -> String {
+        let status = match self.tx_hash {
+            Some(tx_hash) => format!("✅ Approval complete: {:?}", tx_hash),
+            None => "🚀 Ready to send approval (dry run, not sent)".to_string(),
+        };
+        [
+            status,
+            format!("⛽ Estimated gas: {}", self.gas_estimate),
+            format!("🔍 Allowance for spender: {}", self.allowance),
+        ]
+        .join("\n")
+    }
+
+    fn table(&self) -> Table {
+        Table::key_value([
+            ("token", format!("{:?}", self.token)),
+            ("owner", format!("{:?}", self.owner)),
+            ("spender", format!("{:?}", self.spender)),
+            ("gas_estimate", self.gas_estimate.to_string()),
+            ("tx_hash", self.tx_hash.map(|h| format!("{:?}", h)).unwrap_or_else(|| "-".to_string())),
+            ("allowance", self.allowance.to_string()),
+        ])
+    }
+}
+
 // Approve Spender
-async fn approve_spender<M: Middleware + 'static>(
+pub async fn approve_spender<M: Middleware + 'static>(
     client: Arc<M>,
     token_address: Address,
     owner: Address,
     spender: Address,
-) -> Result<()> {
+    amount: TokenAmount,
+    dry_run: bool,
+) -> Result<ApprovalResult> {
     let token = ERC20::new(token_address, client.clone());
-    let decimals = token.decimals().call().await?;
-    let amount: U256 = parse_units("10", decimals as usize)?.into();
 
-    println!("\n🛂 Approving spender...");
-    let contract_call = token.approve(spender, amount);
-    let pending_tx = contract_call.send().await?;
-    let receipt = pending_tx.await?.expect("Approval failed");
-    println!("✅ Approval complete: {:?}", receipt.transaction_hash);
+    let contract_call = token.approve(spender, amount.raw());
+    let gas_estimate = contract_call.estimate_gas().await?;
+    let tx_hash = match dry_run {
+        true => None,
+        false => {
+            let pending_tx = contract_call.send().await?;
+            let receipt = pending_tx.await?.ok_or_else(|| eyre::eyre!("Approval failed"))?;
+            Some(receipt.transaction_hash)
+        }
+    };
 
     let allowance = token.allowance(owner, spender).call().await?;
-    println!("🔍 Allowance for spender: {}", allowance);
-    Ok(())
+    Ok(ApprovalResult {
+        token: token_address,
+        owner,
+        spender,
+        gas_estimate,
+        dry_run,
+        tx_hash,
+        allowance: TokenAmount::new(allowance, amount.decimals()),
+    })
 }
 
 // Transfer Tokens Directly
@@ -42,17 +142,15 @@ pub async fn transfer_tokens<M: Middleware + 'static>(
     client: Arc<M>,
     token_address: Address,
     recipient: Address,
-) -> Result<()> {
+    amount: TokenAmount,
+    dry_run: bool,
+) -> Result<TransferResult> {
+    let from = client.default_sender().ok_or_else(|| eyre::eyre!("No signer to send the transfer from"))?;
     let token = ERC20::new(token_address, client.clone());
     let provider = client.provider();
 
-    let decimals = token.decimals().call().await?;
-    let amount: U256 = parse_units("10", decimals as usize)?.into();
-
-    println!("\n🔁 Estimating gas for token transfer...");
-
     // Create contract call and get transaction request
-    let contract_call = token.transfer(recipient, amount);
+    let contract_call = token.transfer(recipient, amount.raw());
     let tx_request = contract_call.tx.clone();
 
     // Estimate gas
@@ -60,40 +158,42 @@ pub async fn transfer_tokens<M: Middleware + 'static>(
     let gas_price = provider.get_gas_price().await?;
     let gas_fee = gas_estimate * gas_price;
 
-    // Show estimate
-    println!("⛽ Estimated gas: {}", gas_estimate);
-    println!("💰 Gas price: {} wei", gas_price);
-    println!("🔢 Estimated total gas fee: {} ETH", ethers::utils::format_units(gas_fee, 18)?);
+    let mut result = TransferResult {
+        from,
+        to: recipient,
+        token: Some(token_address),
+        amount,
+        gas_estimate,
+        gas_price,
+        gas_fee: TokenAmount::new(gas_fee, 18),
+        dry_run,
+        tx_hash: None,
+    };
+    if dry_run {
+        return Ok(result);
+    }
 
-    println!("✅ Token transfer estimation complete.");
-    println!("🚀 Ready to send transaction (commented out for safety)");
-    
-    // Uncomment to actually send the transaction
-    // let pending_tx = contract_call.send().await?;
-    // let receipt = pending_tx.await?.expect("Transaction failed");
-    // println!("✅ Transfer complete: {:?}", receipt.transaction_hash);
+    let pending_tx = contract_call.send().await?;
+    let receipt = pending_tx.await?.ok_or_else(|| eyre::eyre!("Transaction failed"))?;
+    result.tx_hash = Some(receipt.transaction_hash);
 
-    Ok(())
+    Ok(result)
 }
 
 // Transfer Tokens From Another User (After Approval)
-async fn transfer_tokens_from<M: Middleware + 'static>(
+pub async fn transfer_tokens_from<M: Middleware + 'static>(
     client: Arc<M>,
     token_address: Address,
     sender: Address,
     recipient: Address,
-) -> eyre::Result<()> {
+    amount: TokenAmount,
+    dry_run: bool,
+) -> eyre::Result<TransferResult> {
     let token = ERC20::new(token_address, client.clone());
     let provider = client.provider();
 
-    // Set amount
-    let decimals = token.decimals().call().await?;
-    let amount: U256 = parse_units("10", decimals as usize)?.into();
-
-    println!("\n🔍 Preparing transferFrom transaction...");
-
     // Step 1: Build ContractCall
-    let contract_call = token.transfer_from(sender, recipient, amount);
+    let contract_call = token.transfer_from(sender, recipient, amount.raw());
 
     // Step 2: Get raw transaction request
     let tx_request = contract_call.tx.clone();
@@ -103,19 +203,26 @@ async fn transfer_tokens_from<M: Middleware + 'static>(
     let gas_price = provider.get_gas_price().await?;
     let gas_fee = gas_estimate * gas_price;
 
-    println!("⛽ Estimated gas: {}", gas_estimate);
-    println!("💰 Gas price: {} wei", gas_price);
-    println!("🔢 Estimated fee: {} ETH", ethers::utils::format_units(gas_fee, 18)?);
-
-    println!("✅ transferFrom estimation complete.");
-    println!("🚀 Ready to send transaction (commented out for safety)");
+    let mut result = TransferResult {
+        from: sender,
+        to: recipient,
+        token: Some(token_address),
+        amount,
+        gas_estimate,
+        gas_price,
+        gas_fee: TokenAmount::new(gas_fee, 18),
+        dry_run,
+        tx_hash: None,
+    };
+    if dry_run {
+        return Ok(result);
+    }
 
-    // Uncomment to actually send the transaction
-    // let pending_tx = contract_call.send().await?;
-    // let receipt = pending_tx.await?.expect("Transfer failed");
-    // println!("✅ transferFrom successful: {:?}", receipt.transaction_hash);
+    let pending_tx = contract_call.send().await?;
+    let receipt = pending_tx.await?.ok_or_else(|| eyre::eyre!("Transfer failed"))?;
+    result.tx_hash = Some(receipt.transaction_hash);
 
-    Ok(())
+    Ok(result)
 }
 
 // Public wrapper for ETH transfer
@@ -123,54 +230,54 @@ pub async fn transfer_eth<M: Middleware + 'static>(
     client: Arc<M>,
     from: Address,
     to: Address,
-    amount: U256,
-) -> Result<()> {
+    amount: TokenAmount,
+    dry_run: bool,
+) -> Result<TransferResult> {
     let provider = client.provider();
-    
-    println!("\n💸 Preparing ETH transfer...");
-    println!("From: {:?}", from);
-    println!("To: {:?}", to);
-    println!("Amount: {} ETH", ethers::utils::format_units(amount, 18)?);
-    
+
     // Create transaction request using TypedTransaction
     let tx = Eip1559TransactionRequest::new()
         .to(to)
-        .value(amount)
+        .value(amount.raw())
         .from(from);
-    
+
     let typed_tx: TypedTransaction = tx.into();
-    
+
     // Estimate gas
     let gas_estimate = provider.estimate_gas(&typed_tx, None).await?;
     let gas_price = provider.get_gas_price().await?;
     let gas_fee = gas_estimate * gas_price;
-    
-    println!("⛽ Estimated gas: {}", gas_estimate);
-    println!("💰 Gas price: {} wei", gas_price);
-    println!("🔢 Estimated total gas fee: {} ETH", ethers::utils::format_units(gas_fee, 18)?);
-    
+
     // Check balance
     let balance = provider.get_balance(from, None).await?;
-    let total_needed = amount + gas_fee;
-    
+    let total_needed = amount.raw() + gas_fee;
+
     if balance < total_needed {
-        println!("❌ Insufficient ETH! Need {}, have {}",
-            ethers::utils::format_units(total_needed, 18)?,
-            ethers::utils::format_units(balance, 18)?
+        eyre::bail!(
+            "❌ Insufficient ETH! Need {}, have {}",
+            TokenAmount::new(total_needed, 18),
+            TokenAmount::new(balance, 18)
         );
-        return Ok(());
     }
-    
-    println!("✅ Sufficient ETH for transfer and gas.");
 
-    // Uncomment to actually not send the transaction
-    // println!("🚀 Ready to send transaction (commented out for safety)");
-    
-    // Uncomment to actually send the transaction
+    let mut result = TransferResult {
+        from,
+        to,
+        token: None,
+        amount,
+        gas_estimate,
+        gas_price,
+        gas_fee: TokenAmount::new(gas_fee, 18),
+        dry_run,
+        tx_hash: None,
+    };
+    if dry_run {
+        return Ok(result);
+    }
+
     let pending_tx = client.send_transaction(typed_tx, None).await?;
-    let receipt = pending_tx.await?.expect("Transaction failed");
-    println!("✅ Transfer complete: {:?}", receipt.transaction_hash);
-    
-    Ok(())
-}
+    let receipt = pending_tx.await?.ok_or_else(|| eyre::eyre!("Transaction failed"))?;
+    result.tx_hash = Some(receipt.transaction_hash);
 
+    Ok(result)
+}
@@ -1,7 +1,21 @@
-// TODO: TO BE REMOVED SINCE NO LONGER USED
-
 use ethers::prelude::*;
-use anyhow::Result;
+use ethers::signers::coins_bip39::English;
+use ethers::utils::to_checksum;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use crate::output::{Report, Table};
+
+// Default directory for encrypted keystores, overridable with ETH_RUST_KEYSTORE_DIR
+pub const DEFAULT_KEYSTORE_DIR: &str = "keystore";
+// BIP-44 path for the first Ethereum account
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/60'/0'/0/0";
+
+pub fn keystore_dir() -> PathBuf {
+    std::env::var("ETH_RUST_KEYSTORE_DIR")
+        .unwrap_or_else(|_| DEFAULT_KEYSTORE_DIR.to_string())
+        .into()
+}
 
 #[derive(Debug)]
 pub struct Wallet {
@@ -14,7 +28,7 @@ impl Clone for Wallet {
         // Clone by recreating from the private key bytes
         let private_key_bytes = self.private_key.signer().to_bytes();
         let private_key = LocalWallet::from_bytes(&private_key_bytes).unwrap();
-        
+
         Self {
             address: self.address,
             private_key,
@@ -23,22 +37,63 @@ impl Clone for Wallet {
 }
 
 impl Wallet {
+    fn from_signer(private_key: LocalWallet) -> Self {
+        Self {
+            address: private_key.address(),
+            private_key,
+        }
+    }
+
     /// Creates a new wallet from a private key
     pub fn from_private_key(private_key: &str) -> Result<Self> {
-        let private_key = private_key.trim_start_matches("0x");
+        let private_key = private_key.trim().trim_start_matches("0x");
         let private_key = hex::decode(private_key)?;
         let private_key = LocalWallet::from_bytes(&private_key)?;
-        let address = private_key.address();
+        Ok(Self::from_signer(private_key))
+    }
 
-        Ok(Self {
-            address,
-            private_key,
-        })
+    /// Decrypts a Web3 Secret Storage (V3) JSON keystore
+    pub fn from_keystore(path: impl AsRef<Path>, password: &str) -> Result<Self> {
+        let path = path.as_ref();
+        let private_key = LocalWallet::decrypt_keystore(path, password)
+            .map_err(|e| anyhow!("Failed to decrypt keystore {}: {}", path.display(), e))?;
+        Ok(Self::from_signer(private_key))
+    }
+
+    /// Derives a wallet from a BIP-39 mnemonic at a BIP-44 derivation path
+    pub fn from_mnemonic(phrase: &str, derivation_path: &str) -> Result<Self> {
+        let private_key = MnemonicBuilder::<English>::default()
+            .phrase(phrase.trim())
+            .derivation_path(derivation_path)?
+            .build()
+            .map_err(|e| anyhow!("Invalid mnemonic or derivation path: {}", e))?;
+        Ok(Self::from_signer(private_key))
+    }
+
+    /// Loads the signing wallet: keystore if given, else `ETH_RUST_MNEMONIC`, else `ACCOUNT_PRIVATE_KEY`
+    pub fn load(keystore: Option<&str>, derivation_path: &str) -> Result<Self> {
+        if let Some(keystore) = keystore {
+            let path = resolve_keystore(keystore)?;
+            let password = read_secret("ETH_RUST_KEYSTORE_PASSWORD", "Keystore password: ")?;
+            return Self::from_keystore(path, &password);
+        }
+        if let Ok(phrase) = std::env::var("ETH_RUST_MNEMONIC") {
+            return Self::from_mnemonic(&phrase, derivation_path);
+        }
+        if let Ok(private_key) = std::env::var("ACCOUNT_PRIVATE_KEY") {
+            return Self::from_private_key(&private_key);
+        }
+        bail!("No wallet configured: pass --keystore, or set ETH_RUST_MNEMONIC or ACCOUNT_PRIVATE_KEY (see .env.example)")
+    }
+
+    /// Returns the signer bound to a chain id, ready for `SignerMiddleware`
+    pub fn signer(&self, chain_id: u64) -> LocalWallet {
+        self.private_key.clone().with_chain_id(chain_id)
     }
 
     /// Returns the wallet's address as a string
     pub fn address_str(&self) -> String {
-        format!("{:?}", self.address)
+        to_checksum(&self.address, None)
     }
 
     /// Returns the wallet's private key as a hex string
@@ -47,6 +102,197 @@ impl Wallet {
     }
 }
 
+/// Reads a secret from an env var, falling back to a hidden terminal prompt
+pub fn read_secret(env_var: &str, prompt: &str) -> Result<String> {
+    if let Ok(secret) = std::env::var(env_var) {
+        return Ok(secret);
+    }
+    rpassword::prompt_password(prompt).context("Failed to read from terminal")
+}
+
+// Prompts twice for a new password unless one is provided through the environment
+fn read_new_password() -> Result<String> {
+    if let Ok(password) = std::env::var("ETH_RUST_KEYSTORE_PASSWORD") {
+        return Ok(password);
+    }
+    let password = rpassword::prompt_password("New keystore password: ")?;
+    if password != rpassword::prompt_password("Repeat password: ")? {
+        bail!("Passwords do not match");
+    }
+    Ok(password)
+}
+
+/// Accepts a keystore name inside the keystore dir, or a path to a keystore file
+pub fn resolve_keystore(name_or_path: &str) -> Result<PathBuf> {
+    let in_dir = keystore_dir().join(name_or_path);
+    if in_dir.is_file() {
+        return Ok(in_dir);
+    }
+    let path = PathBuf::from(name_or_path);
+    if path.is_file() {
+        return Ok(path);
+    }
+    bail!("Keystore '{}' not found in {} or as a file", name_or_path, keystore_dir().display())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeystoreEntry {
+    pub name: String,
+    pub address: Option<Address>,
+    pub path: PathBuf,
+}
+
+impl Report for KeystoreEntry {
+    fn text(&self) -> String {
+        format!(
+            "🔐 Keystore: {}\nAddress: {}\nPath: {}",
+            self.name,
+            self.address.map(|a| to_checksum(&a, None)).unwrap_or_else(|| "?".to_string()),
+            self.path.display()
+        )
+    }
+
+    fn table(&self) -> Table {
+        KeystoreList { keystores: vec![self.clone()] }.table()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeystoreList {
+    pub keystores: Vec<KeystoreEntry>,
+}
+
+impl Report for KeystoreList {
+    fn text(&self) -> String {
+        if self.keystores.is_empty() {
+            return format!("No keystores in {}", keystore_dir().display());
+        }
+        self.keystores.iter().map(Report::text).collect::<Vec<_>>().join("\n\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(["name", "address", "path"]);
+        for entry in &self.keystores {
+            table.row([
+                entry.name.clone(),
+                entry.address.map(|a| to_checksum(&a, None)).unwrap_or_else(|| "?".to_string()),
+                entry.path.display().to_string(),
+            ]);
+        }
+        table
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedKey {
+    pub address: Address,
+    pub private_key: String,
+}
+
+impl Report for ExportedKey {
+    fn text(&self) -> String {
+        format!(
+            "⚠️  Anyone with this key controls the account!\nAddress: {}\nPrivate key: {}",
+            to_checksum(&self.address, None),
+            self.private_key
+        )
+    }
+
+    fn table(&self) -> Table {
+        Table::key_value([
+            ("address", to_checksum(&self.address, None)),
+            ("private_key", self.private_key.clone()),
+        ])
+    }
+}
+
+// Encrypts a wallet into the keystore dir, named after its address unless a name is given
+fn write_keystore(wallet: &Wallet, name: Option<&str>, password: &str) -> Result<KeystoreEntry> {
+    let dir = keystore_dir();
+    std::fs::create_dir_all(&dir)?;
+    let name = name.map(str::to_string).unwrap_or_else(|| wallet.address_str());
+    let path = dir.join(&name);
+    if path.exists() {
+        bail!("Keystore {} already exists", path.display());
+    }
+
+    let mut rng = rand::thread_rng();
+    LocalWallet::encrypt_keystore(&dir, &mut rng, wallet.private_key.signer().to_bytes(), password, Some(&name))
+        .map_err(|e| anyhow!("Failed to write keystore: {}", e))?;
+
+    // Record the address like geth does, so `list` doesn't need the password
+    let mut json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    json["address"] = serde_json::Value::String(hex::encode(wallet.address));
+    std::fs::write(&path, serde_json::to_string(&json)?)?;
+
+    Ok(KeystoreEntry {
+        name,
+        address: Some(wallet.address),
+        path,
+    })
+}
+
+/// Generates a new random key and stores it encrypted
+pub fn create_keystore(name: Option<&str>) -> Result<KeystoreEntry> {
+    let wallet = Wallet::from_signer(LocalWallet::new(&mut rand::thread_rng()));
+    let password = read_new_password()?;
+    write_keystore(&wallet, name, &password)
+}
+
+/// Encrypts an existing private key, or a key derived from a mnemonic, into a keystore
+pub fn import_keystore(name: Option<&str>, mnemonic: bool, derivation_path: &str) -> Result<KeystoreEntry> {
+    let wallet = if mnemonic {
+        let phrase = read_secret("ETH_RUST_MNEMONIC", "Mnemonic: ")?;
+        Wallet::from_mnemonic(&phrase, derivation_path)?
+    } else {
+        let private_key = read_secret("ACCOUNT_PRIVATE_KEY", "Private key: ")?;
+        Wallet::from_private_key(&private_key)?
+    };
+    let password = read_new_password()?;
+    write_keystore(&wallet, name, &password)
+}
+
+/// Lists keystores in the keystore dir; the address is read from the file or the file name
+pub fn list_keystores() -> Result<KeystoreList> {
+    let dir = keystore_dir();
+    let mut keystores = Vec::new();
+    if dir.is_dir() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            // The HD account set shares the directory but is not a keystore
+            if name == crate::account::accounts::ACCOUNTS_FILE {
+                continue;
+            }
+            let from_file = std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+                .and_then(|json| json.get("address")?.as_str()?.parse::<Address>().ok());
+            keystores.push(KeystoreEntry {
+                address: from_file.or_else(|| name.parse().ok()),
+                name,
+                path,
+            });
+        }
+    }
+    keystores.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(KeystoreList { keystores })
+}
+
+/// Decrypts a keystore and returns its private key
+pub fn export_keystore(name_or_path: &str) -> Result<ExportedKey> {
+    let path = resolve_keystore(name_or_path)?;
+    let password = read_secret("ETH_RUST_KEYSTORE_PASSWORD", "Keystore password: ")?;
+    let wallet = Wallet::from_keystore(path, &password)?;
+    Ok(ExportedKey {
+        address: wallet.address,
+        private_key: wallet.private_key_str(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -58,4 +304,28 @@ mod tests {
         let wallet = Wallet::from_private_key(private_key).unwrap();
         assert_eq!(wallet.address_str(), "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf");
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_from_mnemonic() {
+        // Well-known test mnemonic used by Hardhat and Anvil
+        let phrase = "test test test test test test test test test test test junk";
+        let wallet = Wallet::from_mnemonic(phrase, DEFAULT_DERIVATION_PATH).unwrap();
+        assert_eq!(wallet.address_str(), "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266");
+    }
+
+    #[test]
+    fn test_keystore_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("eth-rust-keystore-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let wallet = Wallet::from_private_key(&"01".repeat(32)).unwrap();
+
+        let mut rng = rand::thread_rng();
+        LocalWallet::encrypt_keystore(&dir, &mut rng, wallet.private_key.signer().to_bytes(), "secret", Some("test"))
+            .unwrap();
+        let decrypted = Wallet::from_keystore(dir.join("test"), "secret").unwrap();
+        assert_eq!(decrypted.address, wallet.address);
+        assert!(Wallet::from_keystore(dir.join("test"), "wrong").is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
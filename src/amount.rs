@@ -0,0 +1,227 @@
+use anyhow::{anyhow, bail, Result};
+use ethers::types::{I256, U256};
+use serde::{Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Sub};
+
+/// An exact token amount: the raw on-chain integer plus the token's decimals. It carries no
+/// token identity, so amounts of different tokens compare and add as plain numbers; callers keep
+/// track of which token an amount is in.
+#[derive(Clone, Copy, Debug)]
+pub struct TokenAmount {
+    raw: U256,
+    decimals: u8,
+}
+
+impl TokenAmount {
+    pub fn new(raw: U256, decimals: u8) -> Self {
+        Self { raw, decimals }
+    }
+
+    pub fn zero(decimals: u8) -> Self {
+        Self::new(U256::zero(), decimals)
+    }
+
+    /// Converts a signed on-chain value (e.g. a Chainlink answer), rejecting negatives
+    pub fn from_signed(raw: I256, decimals: u8) -> Result<Self> {
+        if raw.is_negative() {
+            bail!("Negative amount {} cannot be represented as a token amount", raw);
+        }
+        Ok(Self::new(raw.into_raw(), decimals))
+    }
+
+    /// Parses a decimal string such as "1.5" exactly, rejecting excess fractional digits
+    pub fn parse(value: &str, decimals: u8) -> Result<Self> {
+        let value = value.trim();
+        let (int_part, frac_part) = value.split_once('.').unwrap_or((value, ""));
+        if int_part.is_empty() && frac_part.is_empty() {
+            bail!("Empty amount");
+        }
+        if !int_part.chars().chain(frac_part.chars()).all(|c| c.is_ascii_digit()) {
+            bail!("Invalid amount '{}'", value);
+        }
+        if frac_part.len() > decimals as usize {
+            bail!("Amount '{}' has more than {} decimal places", value, decimals);
+        }
+
+        let digits = format!("{}{:0<width$}", int_part, frac_part, width = decimals as usize);
+        let digits = digits.trim_start_matches('0');
+        let raw = if digits.is_empty() {
+            U256::zero()
+        } else {
+            U256::from_dec_str(digits).map_err(|_| anyhow!("Amount '{}' overflows U256", value))?
+        };
+        Ok(Self::new(raw, decimals))
+    }
+
+    pub fn raw(&self) -> U256 {
+        self.raw
+    }
+
+    pub fn decimals(&self) -> u8 {
+        self.decimals
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.raw.is_zero()
+    }
+
+    /// Lossy conversion for display and floating-point heuristics
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::MAX)
+    }
+
+    /// Re-expresses the amount with different decimals, truncating when scaling down
+    pub fn rescale(&self, decimals: u8) -> Option<Self> {
+        let raw = match decimals.cmp(&self.decimals) {
+            Ordering::Equal => self.raw,
+            Ordering::Greater => self.raw.checked_mul(pow10(decimals - self.decimals)?)?,
+            Ordering::Less => self.raw / pow10(self.decimals - decimals)?,
+        };
+        Some(Self::new(raw, decimals))
+    }
+
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        let decimals = self.decimals.max(other.decimals);
+        let raw = self.rescale(decimals)?.raw.checked_add(other.rescale(decimals)?.raw)?;
+        Some(Self::new(raw, decimals))
+    }
+
+    pub fn checked_sub(&self, other: &Self) -> Option<Self> {
+        let decimals = self.decimals.max(other.decimals);
+        let raw = self.rescale(decimals)?.raw.checked_sub(other.rescale(decimals)?.raw)?;
+        Some(Self::new(raw, decimals))
+    }
+}
+
+fn pow10(exp: u8) -> Option<U256> {
+    U256::from(10u8).checked_pow(U256::from(exp))
+}
+
+// Compares `coarse` with `fine`, which has more decimals, by splitting `fine` into whole units of
+// `coarse` and a remainder; scaling `coarse` up instead overflows for far-apart decimals
+fn cmp_across_decimals(coarse: &TokenAmount, fine: &TokenAmount) -> Ordering {
+    let (whole, rest) = match pow10(fine.decimals - coarse.decimals) {
+        Some(unit) => (fine.raw / unit, fine.raw % unit),
+        // One unit of `coarse` is beyond U256, so `fine` is less than it
+        None => (U256::zero(), fine.raw),
+    };
+    coarse.raw.cmp(&whole).then(if rest.is_zero() { Ordering::Equal } else { Ordering::Less })
+}
+
+impl PartialEq for TokenAmount {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TokenAmount {}
+
+impl PartialOrd for TokenAmount {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TokenAmount {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.decimals.cmp(&other.decimals) {
+            Ordering::Equal => self.raw.cmp(&other.raw),
+            Ordering::Less => cmp_across_decimals(self, other),
+            Ordering::Greater => cmp_across_decimals(other, self).reverse(),
+        }
+    }
+}
+
+/// Panics on overflow; use `checked_add` for amounts from user input or the chain
+impl Add for TokenAmount {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.checked_add(&other).expect("TokenAmount addition overflow")
+    }
+}
+
+/// Panics on underflow; use `checked_sub` for amounts from user input or the chain
+impl Sub for TokenAmount {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.checked_sub(&other).expect("TokenAmount subtraction underflow")
+    }
+}
+
+/// Serialized as the exact decimal string, so JSON consumers never see a rounded float
+impl Serialize for TokenAmount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Formats the exact decimal value; a precision (`{:.6}`) truncates the fractional digits
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = format!("{:0>width$}", self.raw.to_string(), width = self.decimals as usize + 1);
+        let (int_part, frac_part) = digits.split_at(digits.len() - self.decimals as usize);
+
+        let frac_part = match f.precision() {
+            Some(precision) => format!("{:0<precision$}", &frac_part[..frac_part.len().min(precision)]),
+            None => frac_part.trim_end_matches('0').to_string(),
+        };
+
+        if frac_part.is_empty() {
+            write!(f, "{}", int_part)
+        } else {
+            write!(f, "{}.{}", int_part, frac_part)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format_roundtrip() {
+        let amount = TokenAmount::parse("1234.000001", 6).unwrap();
+        assert_eq!(amount.raw(), U256::from(1_234_000_001u64));
+        assert_eq!(amount.to_string(), "1234.000001");
+        assert_eq!(format!("{:.2}", amount), "1234.00");
+        assert_eq!(TokenAmount::parse(".5", 18).unwrap().to_string(), "0.5");
+        assert_eq!(TokenAmount::zero(18).to_string(), "0");
+        assert!(TokenAmount::parse("0.0000001", 6).is_err());
+        assert!(TokenAmount::parse("1e18", 18).is_err());
+    }
+
+    #[test]
+    fn test_values_above_u128() {
+        let amount = TokenAmount::new(U256::MAX, 18);
+        assert_eq!(TokenAmount::parse(&amount.to_string(), 18).unwrap(), amount);
+        assert!(amount.to_f64() > 1e58);
+    }
+
+    #[test]
+    fn test_arithmetic_and_ordering_across_decimals() {
+        // One token's amounts at two precisions, e.g. raw 6-decimal USDC and an 18-decimal rescale
+        let one = TokenAmount::parse("1", 6).unwrap();
+        let half = TokenAmount::parse("0.5", 18).unwrap();
+        assert_eq!((one - half).to_string(), "0.5");
+        assert_eq!((one - half).decimals(), 18);
+        assert!(half < one);
+        assert_eq!(one, TokenAmount::parse("1", 18).unwrap());
+        assert!(half.checked_sub(&one).is_none());
+        assert!(TokenAmount::from_signed(I256::from(-1), 8).is_err());
+    }
+
+    #[test]
+    fn test_ordering_with_far_apart_decimals() {
+        let tiny = TokenAmount::new(U256::MAX, 255);
+        let one = TokenAmount::new(U256::one(), 0);
+        assert!(tiny < one);
+        assert!(TokenAmount::zero(255) == TokenAmount::zero(0));
+        assert!(TokenAmount::new(U256::one(), 100) > TokenAmount::zero(0));
+        assert_eq!(TokenAmount::new(U256::from(1_500u64), 3).cmp(&TokenAmount::new(U256::one(), 0)), Ordering::Greater);
+        assert_eq!(TokenAmount::new(U256::from(1_000u64), 3), TokenAmount::new(U256::one(), 0));
+    }
+}
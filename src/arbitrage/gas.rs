@@ -0,0 +1,181 @@
+use ethers::prelude::*;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use crate::amount::TokenAmount;
+use crate::arbitrage::index::amount_from_f64;
+
+// Transaction intrinsic gas plus the executor's own overhead around the swaps
+pub const ARBITRAGE_BASE_GAS: u64 = 50_000;
+
+// Tip used when the node can't suggest one (1 gwei)
+const DEFAULT_PRIORITY_FEE: u64 = 1_000_000_000;
+
+/// Typical gas for one swap on a venue, as reported by `Pool::venue`; the fallback when a route
+/// can't be estimated through the executor
+pub fn swap_gas(venue: &str) -> u64 {
+    match venue {
+        "uniswap-v2" => 90_000,
+        "uniswap-v3" => 130_000,
+        "balancer" | "balancer-v2" => 120_000,
+        "curve" => 130_000,
+        "curve-crypto" => 180_000,
+        _ => 150_000,
+    }
+}
+
+/// What gas costs in the next block: the latest base fee plus the tip we'd pay
+#[derive(Debug, Clone, Copy)]
+pub struct GasPrice {
+    pub base_fee: U256,
+    pub priority_fee: U256,
+}
+
+impl GasPrice {
+    /// Reads the latest base fee and the node's suggested tip, unless `priority_fee` is given
+    pub async fn fetch<M: Middleware + 'static>(provider: &M, priority_fee: Option<U256>) -> Result<Self> {
+        let block = provider
+            .get_block(BlockNumber::Latest)
+            .await?
+            .ok_or_else(|| anyhow!("Latest block not found"))?;
+        let base_fee = block
+            .base_fee_per_gas
+            .ok_or_else(|| anyhow!("Latest block has no base fee; the chain isn't on EIP-1559"))?;
+        let priority_fee = match priority_fee {
+            Some(priority_fee) => priority_fee,
+            None => provider
+                .provider()
+                .request::<_, U256>("eth_maxPriorityFeePerGas", ())
+                .await
+                .unwrap_or_else(|_| U256::from(DEFAULT_PRIORITY_FEE)),
+        };
+        Ok(Self { base_fee, priority_fee })
+    }
+
+    pub fn per_gas(&self) -> U256 {
+        self.base_fee + self.priority_fee
+    }
+}
+
+/// How an opportunity's gas is costed: the gas price and the wrapped native token it's priced through
+#[derive(Debug, Clone)]
+pub struct GasModel {
+    pub gas_price: GasPrice,
+    // e.g. WETH, whose price in the profit token converts the gas cost
+    pub native_token: Address,
+    // The chain's native currency that gas is paid in, e.g. ETH
+    pub native_symbol: String,
+}
+
+impl GasModel {
+    pub async fn fetch<M: Middleware + 'static>(
+        provider: &M,
+        native_token: Address,
+        native_symbol: &str,
+        priority_fee: Option<U256>,
+    ) -> Result<Self> {
+        Ok(Self {
+            gas_price: GasPrice::fetch(provider, priority_fee).await?,
+            native_token,
+            native_symbol: native_symbol.to_string(),
+        })
+    }
+
+    /// Gas for one transaction running swaps on these venues in turn
+    pub fn gas_units<'a>(&self, venues: impl IntoIterator<Item = &'a str>) -> u64 {
+        ARBITRAGE_BASE_GAS + venues.into_iter().map(swap_gas).sum::<u64>()
+    }
+
+    /// Gas cost of the route from the per-venue table, converted into the profit token with
+    /// `native_price` (profit token per native token)
+    pub fn breakdown<'a>(
+        &self,
+        venues: impl IntoIterator<Item = &'a str>,
+        native_price: Option<f64>,
+        profit_decimals: u8,
+    ) -> GasBreakdown {
+        self.priced(self.gas_units(venues), native_price, profit_decimals)
+    }
+
+    /// Cost of `gas_units`, e.g. from `estimate_gas`, converted as in `breakdown`
+    pub fn priced(&self, gas_units: u64, native_price: Option<f64>, profit_decimals: u8) -> GasBreakdown {
+        let cost_native = TokenAmount::new(self.gas_price.per_gas() * gas_units, 18);
+        let cost_in_token = native_price.and_then(|price| {
+            let cost = cost_native.to_f64() * price;
+            TokenAmount::parse(&format!("{:.*}", profit_decimals as usize, cost), profit_decimals).ok()
+        });
+        GasBreakdown {
+            gas_units,
+            base_fee: TokenAmount::new(self.gas_price.base_fee, 9),
+            priority_fee: TokenAmount::new(self.gas_price.priority_fee, 9),
+            native_symbol: self.native_symbol.clone(),
+            cost_native,
+            native_price,
+            cost_in_token,
+        }
+    }
+}
+
+/// Gas line items for one opportunity
+#[derive(Debug, Clone, Serialize)]
+pub struct GasBreakdown {
+    pub gas_units: u64,
+    // Both in gwei
+    pub base_fee: TokenAmount,
+    pub priority_fee: TokenAmount,
+    pub native_symbol: String,
+    pub cost_native: TokenAmount,
+    // Profit token per native token, kept so the cost can be re-priced for an estimated gas figure
+    pub native_price: Option<f64>,
+    // `None` when no pool prices the native token in the profit token
+    pub cost_in_token: Option<TokenAmount>,
+}
+
+impl GasBreakdown {
+    /// Profit less the gas cost, negative when gas eats it all; `None` if the gas couldn't be priced
+    pub fn net_profit(&self, profit: TokenAmount) -> Option<f64> {
+        self.cost_in_token.map(|cost| profit.to_f64() - cost.to_f64())
+    }
+
+    /// What a trade must make over its input to clear the gas cost and `min_profit`, for the
+    /// executor's on-chain floor; `None` if the gas couldn't be priced
+    pub fn profit_floor(&self, min_profit: f64) -> Option<TokenAmount> {
+        let cost = self.cost_in_token?;
+        cost.checked_add(&amount_from_f64(min_profit.max(0.0), cost.decimals())?)
+    }
+
+    pub fn describe(&self) -> String {
+        let cost = self.cost_in_token.map(|cost| format!(" ≈ {}", cost)).unwrap_or_default();
+        format!(
+            "{} gas at {:.3} + {:.3} gwei = {} {}{}",
+            self.gas_units, self.base_fee, self.priority_fee, self.cost_native, self.native_symbol, cost,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gas_breakdown() {
+        let model = GasModel {
+            gas_price: GasPrice { base_fee: U256::from(20_000_000_000u64), priority_fee: U256::from(2_000_000_000u64) },
+            native_token: Address::zero(),
+            native_symbol: "ETH".to_string(),
+        };
+        // Base 50k plus a v3 and a v2 swap at 22 gwei, with ETH at 2,000 USDC
+        let gas = model.breakdown(["uniswap-v3", "uniswap-v2"], Some(2_000.0), 6);
+        assert_eq!(gas.gas_units, 270_000);
+        assert_eq!(gas.cost_native, TokenAmount::parse("0.00594", 18).unwrap());
+        assert_eq!(gas.cost_in_token, Some(TokenAmount::parse("11.88", 6).unwrap()));
+        let net = gas.net_profit(TokenAmount::parse("10", 6).unwrap()).unwrap();
+        assert!((net + 1.88).abs() < 1e-9);
+        assert_eq!(gas.profit_floor(1.5), Some(TokenAmount::parse("13.38", 6).unwrap()));
+        assert!(model.breakdown(["curve"], None, 6).net_profit(TokenAmount::zero(6)).is_none());
+
+        // An estimate replaces the table's figure, priced the same way
+        let estimated = model.priced(135_000, gas.native_price, 6);
+        assert_eq!(estimated.cost_in_token, Some(TokenAmount::parse("5.94", 6).unwrap()));
+        assert_eq!(estimated.describe(), "135000 gas at 20.000 + 2.000 gwei = 0.00297 ETH ≈ 5.94");
+    }
+}
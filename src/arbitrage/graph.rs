@@ -0,0 +1,375 @@
+use std::collections::BTreeMap;
+use anyhow::{bail, Result};
+use ethers::types::Address;
+use serde::Serialize;
+use crate::amount::TokenAmount;
+use crate::arbitrage::gas::{GasBreakdown, GasModel};
+use crate::arbitrage::index::{amount_from_f64, maximise_profit};
+use crate::output::{Report, Table};
+use crate::pool::{Pool, PoolToken};
+
+// Largest first-hop input tried, as a share of that pool's balance of the start token
+const MAX_INPUT_SHARE: f64 = 0.5;
+
+/// One swap in a cycle; `pool` indexes the graph's pool set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Hop {
+    pub pool: usize,
+    pub token_in: Address,
+    pub token_out: Address,
+}
+
+/// Tokens as nodes and every ordered token pair of every pool as an edge
+pub struct TokenGraph {
+    pools: Vec<Box<dyn Pool>>,
+    tokens: BTreeMap<Address, PoolToken>,
+    // Swaps leaving each token
+    edges: BTreeMap<Address, Vec<Hop>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolRef {
+    pub venue: &'static str,
+    pub address: Address,
+}
+
+/// A profitable cycle at its best input size
+#[derive(Debug, Clone, Serialize)]
+pub struct CycleOpportunity {
+    // Token symbols along the cycle, the start token first and last
+    pub path: Vec<String>,
+    pub pools: Vec<PoolRef>,
+    #[serde(skip)]
+    pub hops: Vec<Hop>,
+    pub amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+    // Output minus input, in the start token
+    pub profit: TokenAmount,
+    // Gas for the route, costed in the start token
+    pub gas: Option<GasBreakdown>,
+    // Profit less gas in the start token, and that in the search's numeraire
+    pub net_profit: Option<f64>,
+    pub net_profit_value: Option<f64>,
+}
+
+impl CycleOpportunity {
+    fn route(&self) -> String {
+        self.pools
+            .iter()
+            .map(|pool| format!("{} {:?}", pool.venue, pool.address))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArbitrageSearch {
+    pub numeraire: String,
+    pub max_hops: usize,
+    pub cycles_checked: usize,
+    // Smallest net profit kept, in the numeraire
+    pub min_profit: f64,
+    // Cycles profitable before gas that fell short of `min_profit` or whose gas couldn't be priced
+    pub below_threshold: usize,
+    // Best net profit first
+    pub opportunities: Vec<CycleOpportunity>,
+}
+
+impl Report for ArbitrageSearch {
+    fn text(&self) -> String {
+        let mut lines = vec![
+            "🔺 Arbitrage Search:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Checked {} cycles of up to {} hops", self.cycles_checked, self.max_hops),
+        ];
+        if self.below_threshold > 0 {
+            lines.push(format!(
+                "{} cycles profitable before gas fell short of {} {} net",
+                self.below_threshold, self.min_profit, self.numeraire,
+            ));
+        }
+        if self.opportunities.is_empty() {
+            lines.push("No arbitrage opportunity found".to_string());
+        }
+        for (rank, opportunity) in self.opportunities.iter().enumerate() {
+            let symbol = &opportunity.path[0];
+            let mut line = format!(
+                "{}. {}: {} {} in, {} out, profit {} {}",
+                rank + 1,
+                opportunity.path.join(" → "),
+                opportunity.amount_in,
+                symbol,
+                opportunity.amount_out,
+                opportunity.profit,
+                symbol,
+            );
+            if let Some(net) = opportunity.net_profit {
+                line.push_str(&format!(", net of gas {:.6} {}", net, symbol));
+            }
+            if let Some(value) = opportunity.net_profit_value {
+                line.push_str(&format!(" (≈ {:.4} {})", value, self.numeraire));
+            }
+            lines.push(line);
+            lines.push(format!("   via {}", opportunity.route()));
+            if let Some(gas) = &opportunity.gas {
+                lines.push(format!("   gas {}", gas.describe()));
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new([
+            "rank", "path", "pools", "amount_in", "amount_out", "profit", "gas_units", "gas_cost", "net_profit", "net_profit_value",
+        ]);
+        for (rank, opportunity) in self.opportunities.iter().enumerate() {
+            table.row([
+                (rank + 1).to_string(),
+                opportunity.path.join(" → "),
+                opportunity.route(),
+                opportunity.amount_in.to_string(),
+                opportunity.amount_out.to_string(),
+                opportunity.profit.to_string(),
+                opportunity.gas.as_ref().map(|gas| gas.gas_units.to_string()).unwrap_or_else(|| "-".to_string()),
+                opportunity.gas.as_ref().and_then(|gas| gas.cost_in_token).map(|cost| cost.to_string()).unwrap_or_else(|| "-".to_string()),
+                opportunity.net_profit.map(|net| net.to_string()).unwrap_or_else(|| "-".to_string()),
+                opportunity.net_profit_value.map(|value| value.to_string()).unwrap_or_else(|| "-".to_string()),
+            ]);
+        }
+        table
+    }
+}
+
+impl TokenGraph {
+    /// Builds the graph from refreshed pools
+    pub fn new(pools: Vec<Box<dyn Pool>>) -> Result<Self> {
+        let mut tokens = BTreeMap::new();
+        let mut edges: BTreeMap<Address, Vec<Hop>> = BTreeMap::new();
+        for (index, pool) in pools.iter().enumerate() {
+            let pool_tokens = pool.tokens()?;
+            for token_in in &pool_tokens {
+                tokens.entry(token_in.address).or_insert_with(|| token_in.clone());
+                for token_out in pool_tokens.iter().filter(|token| token.address != token_in.address) {
+                    edges.entry(token_in.address).or_default().push(Hop {
+                        pool: index,
+                        token_in: token_in.address,
+                        token_out: token_out.address,
+                    });
+                }
+            }
+        }
+        Ok(Self { pools, tokens, edges })
+    }
+
+    pub fn pools(&self) -> &[Box<dyn Pool>] {
+        &self.pools
+    }
+
+    /// The pools, to update their state in place; their tokens don't change, so the edges stay as they are
+    pub fn pools_mut(&mut self) -> &mut [Box<dyn Pool>] {
+        &mut self.pools
+    }
+
+    pub fn token(&self, address: Address) -> Option<&PoolToken> {
+        self.tokens.get(&address)
+    }
+
+    /// Cycles from `start` back to it in 2 to `max_hops` swaps, each through a different pool
+    /// and passing through no other token twice
+    pub fn cycles(&self, start: Address, max_hops: usize) -> Vec<Vec<Hop>> {
+        let mut cycles = Vec::new();
+        self.extend_cycles(start, start, max_hops, &mut Vec::new(), &mut cycles);
+        cycles
+    }
+
+    fn extend_cycles(&self, start: Address, token: Address, max_hops: usize, path: &mut Vec<Hop>, cycles: &mut Vec<Vec<Hop>>) {
+        for hop in self.edges.get(&token).into_iter().flatten() {
+            if path.iter().any(|taken| taken.pool == hop.pool) {
+                continue;
+            }
+            if hop.token_out == start {
+                if !path.is_empty() {
+                    let mut cycle = path.clone();
+                    cycle.push(*hop);
+                    cycles.push(cycle);
+                }
+                continue;
+            }
+            let revisits = path.iter().any(|taken| taken.token_in == hop.token_out);
+            if revisits || path.len() + 2 > max_hops {
+                continue;
+            }
+            path.push(*hop);
+            self.extend_cycles(start, hop.token_out, max_hops, path, cycles);
+            path.pop();
+        }
+    }
+
+    /// Output of sending `amount_in` through each hop's pool in turn
+    pub async fn cycle_output(&self, cycle: &[Hop], amount_in: TokenAmount) -> Result<TokenAmount> {
+        let mut amount = amount_in;
+        for hop in cycle {
+            amount = self.pools[hop.pool].quote_exact_in(hop.token_in, hop.token_out, amount).await?;
+        }
+        Ok(amount)
+    }
+
+    /// Sizes the input that maximises profit on `cycle`; `None` when no input size is profitable
+    pub async fn optimise(&self, cycle: &[Hop]) -> Result<Option<CycleOpportunity>> {
+        let Some(first) = cycle.first() else {
+            bail!("Empty cycle");
+        };
+        let Some(start) = self.token(first.token_in).cloned() else {
+            bail!("Token {:?} is not in the graph", first.token_in);
+        };
+        let first_pool = &self.pools[first.pool];
+        let reserve = first_pool.reserves()?[first_pool.token_index(start.address)?].to_f64();
+
+        let (best, _) = maximise_profit(reserve * MAX_INPUT_SHARE, async |amount| {
+            let Some(amount_in) = amount_from_f64(amount, start.decimals) else {
+                return f64::NEG_INFINITY;
+            };
+            match self.cycle_output(cycle, amount_in).await {
+                Ok(amount_out) => amount_out.to_f64() - amount_in.to_f64(),
+                Err(_) => f64::NEG_INFINITY,
+            }
+        }).await;
+
+        // Settle on an exact amount and re-quote it, so the reported profit isn't a float estimate
+        let Some(amount_in) = amount_from_f64(best, start.decimals) else {
+            return Ok(None);
+        };
+        let Ok(amount_out) = self.cycle_output(cycle, amount_in).await else {
+            return Ok(None);
+        };
+        let profit = match amount_out.checked_sub(&amount_in) {
+            Some(profit) if !profit.is_zero() => profit,
+            _ => return Ok(None),
+        };
+
+        let mut path = vec![start.symbol.clone()];
+        path.extend(cycle.iter().map(|hop| self.tokens[&hop.token_out].symbol.clone()));
+        Ok(Some(CycleOpportunity {
+            path,
+            pools: cycle
+                .iter()
+                .map(|hop| PoolRef { venue: self.pools[hop.pool].venue(), address: self.pools[hop.pool].address() })
+                .collect(),
+            hops: cycle.to_vec(),
+            amount_in,
+            amount_out,
+            profit,
+            gas: None,
+            net_profit: None,
+            net_profit_value: None,
+        }))
+    }
+
+    /// Spot price of `token` in `numeraire` from the first pool that holds both
+    pub async fn price(&self, token: Address, numeraire: Address) -> Option<f64> {
+        if token == numeraire {
+            return Some(1.0);
+        }
+        for hop in self.edges.get(&token)?.iter().filter(|hop| hop.token_out == numeraire) {
+            if let Ok(price) = self.pools[hop.pool].spot_price(token, numeraire).await {
+                return Some(price);
+            }
+        }
+        None
+    }
+
+    /// Every cycle starting at one of `start_tokens` whose profit after gas reaches `min_profit`,
+    /// ranked by that net profit valued in the first start token
+    pub async fn search(&self, start_tokens: &[Address], max_hops: usize, gas: &GasModel, min_profit: f64) -> Result<ArbitrageSearch> {
+        let Some(&numeraire) = start_tokens.first() else {
+            bail!("No start tokens to search from");
+        };
+        let mut cycles_checked = 0;
+        let mut below_threshold = 0;
+        let mut opportunities = Vec::new();
+        for &start in start_tokens {
+            let decimals = self.token(start).map(|token| token.decimals).unwrap_or(18);
+            let price = self.price(start, numeraire).await;
+            let native_price = self.price(gas.native_token, start).await;
+            for cycle in self.cycles(start, max_hops) {
+                cycles_checked += 1;
+                let Some(mut opportunity) = self.optimise(&cycle).await? else {
+                    continue;
+                };
+                let breakdown = gas.breakdown(opportunity.pools.iter().map(|pool| pool.venue), native_price, decimals);
+                opportunity.net_profit = breakdown.net_profit(opportunity.profit);
+                opportunity.net_profit_value = opportunity.net_profit.zip(price).map(|(net, price)| net * price);
+                opportunity.gas = Some(breakdown);
+                match opportunity.net_profit_value {
+                    Some(value) if value >= min_profit => opportunities.push(opportunity),
+                    _ => below_threshold += 1,
+                }
+            }
+        }
+        opportunities.sort_by(|a, b| {
+            let value = |opportunity: &CycleOpportunity| opportunity.net_profit_value.unwrap_or(f64::NEG_INFINITY);
+            value(b).total_cmp(&value(a))
+        });
+
+        Ok(ArbitrageSearch {
+            numeraire: self.token(numeraire).map(|token| token.symbol.clone()).unwrap_or_else(|| format!("{:?}", numeraire)),
+            max_hops,
+            cycles_checked,
+            min_profit,
+            below_threshold,
+            opportunities,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbitrage::gas::GasPrice;
+    use crate::pool::testing::{token, ConstantProductPool};
+    use ethers::types::U256;
+
+    fn pool(id: u64, a: (&PoolToken, f64), b: (&PoolToken, f64)) -> Box<dyn Pool> {
+        Box::new(ConstantProductPool::new(id, a, b, 30))
+    }
+
+    #[tokio::test]
+    async fn test_triangular_cycle_search() {
+        let (a, b, c) = (token(1, "A"), token(2, "B"), token(3, "C"));
+        // A -> B -> C -> A turns 1 A into about 1.1 A before fees
+        let graph = TokenGraph::new(vec![
+            pool(0, (&a, 1_000.0), (&b, 2_000.0)),
+            pool(1, (&b, 2_000.0), (&c, 4_000.0)),
+            pool(2, (&c, 4_000.0), (&a, 1_100.0)),
+        ]).unwrap();
+
+        // The triangle both ways round; a 2-hop cycle would need two pools for the same pair
+        assert_eq!(graph.cycles(a.address, 3).len(), 2);
+        assert!(graph.cycles(a.address, 2).is_empty());
+
+        // Gas priced in A itself: 50k plus three 150k swaps at 1 gwei costs 0.0005 A
+        let gas = GasModel {
+            gas_price: GasPrice { base_fee: U256::from(1_000_000_000u64), priority_fee: U256::zero() },
+            native_token: a.address,
+            native_symbol: "ETH".to_string(),
+        };
+        let search = graph.search(&[a.address], 3, &gas, 0.0).await.unwrap();
+        assert_eq!(search.cycles_checked, 2);
+        assert_eq!(search.opportunities.len(), 1);
+        let best = &search.opportunities[0];
+        assert_eq!(best.path, ["A", "B", "C", "A"]);
+        let net = best.net_profit.unwrap();
+        assert!((best.profit.to_f64() - net - 0.0005).abs() < 1e-12);
+
+        // A threshold above the net profit filters the cycle out
+        let search = graph.search(&[a.address], 3, &gas, net + 1.0).await.unwrap();
+        assert!(search.opportunities.is_empty());
+        assert_eq!(search.below_threshold, 1);
+
+        // The chosen size beats slightly smaller and larger trades
+        let profit_at = async |amount: TokenAmount| graph.cycle_output(&best.hops, amount).await.unwrap().to_f64() - amount.to_f64();
+        let scaled = |factor: f64| amount_from_f64(best.amount_in.to_f64() * factor, 18).unwrap();
+        assert!(best.profit.to_f64() >= profit_at(scaled(0.9)).await);
+        assert!(best.profit.to_f64() >= profit_at(scaled(1.1)).await);
+    }
+}
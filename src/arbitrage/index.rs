@@ -1,106 +1,400 @@
 use crate::uniswap::pool::PoolUniswap;
 use crate::balancer::pool::PoolBalancer;
-use crate::contracts::{UNISWAP_ETHEREUM_WETH_USDC, BALANCER_ETHEREUM_BCoW_50WETH_50USDC};
 use ethers::prelude::*;
+use serde::Serialize;
 use std::sync::Arc;
 use anyhow::Result;
+use crate::amount::TokenAmount;
+use crate::output::{Report, Table};
+use crate::arbitrage::gas::{GasBreakdown, GasModel};
+use crate::pool::{not_loaded, Pool};
 
-fn compute_optimal_arbitrage(
-    x1: f64, // X in Pool 1
-    y1: f64, // Y in Pool 1
-    x2: f64, // X in Pool 2
-    y2: f64, // Y in Pool 2
-    exchange_rate_pool_1: f64, // Exchange rate of Pool 1
-    exchange_rate_pool_2: f64, // Exchange rate of Pool 2
-    gamma1: f64, // fee multiplier in Pool A (e.g. 0.997)
-    gamma2: f64, // fee multiplier in Pool B (e.g. 0.9975)
-) -> Option<f64> {
-    
-
-    // x * y = k
-    // γ = 1 - fee
-    // exchange rate of pool 1: x1 / y1
-    // exchange rate of pool 2: x2 / y2
-
-    // optimize equation based on exchange rate of pool 1 and pool 2
-    if exchange_rate_pool_1 < exchange_rate_pool_2 {
-        println!("Obtain x out from pool_A");
-    
-        let numerator = x2 * y1 * gamma2 * gamma1;
-        let denominator = y2 * x1;
-        if denominator == 0.0 {
-            return None;
-        }
+// Tick bitmap words loaded around the current Uniswap price when sizing a trade
+const ARBITRAGE_WORD_RADIUS: i16 = 4;
+
+// The closed-form size is exact for constant-product pools; real curves are searched up to this multiple of it
+const SEARCH_MULTIPLE: f64 = 4.0;
+
+/// One pool seen as a constant-product curve in the direction of a swap: reserves of the token
+/// paid in and the token received, in whole tokens, and `gamma = 1 - fee`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Leg {
+    pub reserve_in: f64,
+    pub reserve_out: f64,
+    pub gamma: f64,
+}
+
+impl Leg {
+    pub fn amount_out(&self, amount_in: f64) -> f64 {
+        let amount_in_with_fee = self.gamma * amount_in;
+        amount_in_with_fee * self.reserve_out / (self.reserve_in + amount_in_with_fee)
+    }
+}
 
-        let sqrt_term = (numerator / denominator).sqrt();
-        let delta = sqrt_term * x1 - (x1 / gamma2);
-        if delta > 0.0 {
-            return Some(delta);
+/// Input that maximises profit when buying in `buy` and selling the proceeds back in `sell`,
+/// or `None` if no input is profitable.
+///
+/// The two legs compose into `out = aΔ / (b + cΔ)`, so profit `out - Δ` peaks where
+/// `ab / (b + cΔ)² = 1`, i.e. `Δ = (√(ab) - b) / c`; that is positive only when `a > b`,
+/// meaning the buy pool prices the middle token below the sell pool by more than both fees.
+pub fn compute_optimal_arbitrage(buy: Leg, sell: Leg) -> Option<f64> {
+    let a = buy.gamma * sell.gamma * buy.reserve_out * sell.reserve_out;
+    let b = buy.reserve_in * sell.reserve_in;
+    let c = buy.gamma * (sell.reserve_in + sell.gamma * buy.reserve_out);
+    if a <= b || c <= 0.0 {
+        return None;
+    }
+    let delta = ((a * b).sqrt() - b) / c;
+    (delta.is_finite() && delta > 0.0).then_some(delta)
+}
+
+/// A two-pool trade: buy `token_mid` with `token_in` on one venue, sell it back on the other
+#[derive(Debug, Clone, Serialize)]
+pub struct ArbitragePlan {
+    pub token_in: Address,
+    pub token_mid: Address,
+    pub symbol_in: String,
+    pub symbol_mid: String,
+    pub buy_venue: &'static str,
+    pub buy_pool: Address,
+    pub sell_venue: &'static str,
+    pub sell_pool: Address,
+    pub amount_in: TokenAmount,
+    // `token_mid` bought on the buy venue
+    pub amount_mid: TokenAmount,
+    pub expected_out: TokenAmount,
+    // Expected output minus input, in `token_in`
+    pub profit: TokenAmount,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArbitrageResult {
+    pub uniswap_pool: Address,
+    pub balancer_pool: Address,
+    // Price of the plan's middle token in its input token on each venue, before fees
+    pub uniswap_price: f64,
+    pub balancer_price: f64,
+    // `None` when neither direction is profitable
+    pub plan: Option<ArbitragePlan>,
+    // Gas for both swaps, costed in the input token
+    pub gas: Option<GasBreakdown>,
+    // Profit less gas, in the input token
+    pub net_profit: Option<f64>,
+    pub min_profit: f64,
+}
+
+impl Report for ArbitrageResult {
+    fn text(&self) -> String {
+        let mut lines = vec![
+            "🔁 Arbitrage:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Uniswap price: {}", self.uniswap_price),
+            format!("Balancer price: {}", self.balancer_price),
+        ];
+        let Some(plan) = &self.plan else {
+            lines.push("No arbitrage opportunity found".to_string());
+            return lines.join("\n");
+        };
+        lines.push(format!("Buy {} on {} {:?}", plan.symbol_mid, plan.buy_venue, plan.buy_pool));
+        lines.push(format!("Sell it on {} {:?}", plan.sell_venue, plan.sell_pool));
+        lines.push(format!("Amount in: {} {}", plan.amount_in, plan.symbol_in));
+        lines.push(format!("Bought: {} {}", plan.amount_mid, plan.symbol_mid));
+        lines.push(format!("Expected out: {} {}", plan.expected_out, plan.symbol_in));
+        lines.push(format!("Expected profit: {} {}", plan.profit, plan.symbol_in));
+        if let Some(gas) = &self.gas {
+            lines.push(format!("Gas: {}", gas.describe()));
+        }
+        match self.net_profit {
+            Some(net) if net >= self.min_profit => lines.push(format!("Net profit: {:?}", net)),
+            Some(net) => lines.push(format!("Net profit: {:?}, below the minimum of {}", net, self.min_profit)),
+            None => lines.push("Net profit: gas cost could not be priced".to_string()),
         }
-    } else {
-         // Δ = x out from pool_B - x in to pool_A
-        // Pool A: (x1 + γ1 * Δx1) * (y1 - Δy1) = x1 * y1
-        // Δy1 = (Δx1 * γ1 * y1) / (x1 + Δx1 * γ1)
-        // Pool B: (x2 - Δx2) * (y2 + γ2 * Δy2) = x2 * y2
-        // Δy1 = Δy2 | because that’s what you're actually depositing into Pool B
-        // Δx2 = (Δy1 * γ2 * x2) / (y2 + Δy1 * γ2)
-
-        println!("Obtain x out from pool_B");
-
-        let numerator = x1 * y2 * gamma1 * gamma2;
-        let denominator = y1 * x2;
-        if denominator == 0.0 {
-            return None;
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let plan = self.plan.as_ref();
+        let dash = || "-".to_string();
+        Table::key_value([
+            ("uniswap_pool", format!("{:?}", self.uniswap_pool)),
+            ("balancer_pool", format!("{:?}", self.balancer_pool)),
+            ("uniswap_price", self.uniswap_price.to_string()),
+            ("balancer_price", self.balancer_price.to_string()),
+            ("buy_venue", plan.map(|plan| plan.buy_venue.to_string()).unwrap_or_else(dash)),
+            ("sell_venue", plan.map(|plan| plan.sell_venue.to_string()).unwrap_or_else(dash)),
+            ("amount_in", plan.map(|plan| plan.amount_in.to_string()).unwrap_or_else(dash)),
+            ("expected_out", plan.map(|plan| plan.expected_out.to_string()).unwrap_or_else(dash)),
+            ("expected_profit", plan.map(|plan| plan.profit.to_string()).unwrap_or_else(dash)),
+            ("gas_units", self.gas.as_ref().map(|gas| gas.gas_units.to_string()).unwrap_or_else(dash)),
+            ("gas_cost", self.gas.as_ref().and_then(|gas| gas.cost_in_token).map(|cost| cost.to_string()).unwrap_or_else(dash)),
+            ("net_profit", self.net_profit.map(|net| net.to_string()).unwrap_or_else(dash)),
+            ("min_profit", self.min_profit.to_string()),
+        ])
+    }
+}
+
+// Golden-section search for the input maximising profit, which is concave in the input size
+pub(crate) async fn maximise_profit(upper: f64, profit: impl AsyncFn(f64) -> f64) -> (f64, f64) {
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut low, mut high) = (0.0, upper);
+    for _ in 0..100 {
+        let a = high - ratio * (high - low);
+        let b = low + ratio * (high - low);
+        if profit(a).await < profit(b).await {
+            low = a;
+        } else {
+            high = b;
         }
-        let sqrt_term = (numerator / denominator).sqrt();
-        let delta = sqrt_term * x2 - (x2/ gamma1);
-        if delta > 0.0 {
-            return Some(delta);
+    }
+    let best = (low + high) / 2.0;
+    (best, profit(best).await)
+}
+
+// Nearest token amount to a float, for search inputs
+pub(crate) fn amount_from_f64(amount: f64, decimals: u8) -> Option<TokenAmount> {
+    if !amount.is_finite() || amount < 0.0 {
+        return None;
+    }
+    TokenAmount::parse(&format!("{:.*}", decimals as usize, amount), decimals).ok()
+}
+
+/// One pool of a two-pool arbitrage, with its constant-product reserves of
+/// `(token_in, token_mid)` in whole tokens for the closed-form estimate
+pub struct ArbitrageVenue<'a> {
+    pub pool: &'a dyn Pool,
+    pub reserves: (f64, f64),
+}
+
+impl ArbitrageVenue<'_> {
+    fn buy_leg(&self) -> Result<Leg> {
+        Ok(Leg { reserve_in: self.reserves.0, reserve_out: self.reserves.1, gamma: 1.0 - self.pool.fee()? })
+    }
+
+    fn sell_leg(&self) -> Result<Leg> {
+        Ok(Leg { reserve_in: self.reserves.1, reserve_out: self.reserves.0, gamma: 1.0 - self.pool.fee()? })
+    }
+}
+
+/// The most profitable way to start from `token_in`, buy `token_mid` in one pool and sell it back in the other.
+/// Tokens are matched by address, each pool quotes with its own fee, and the closed-form size from the
+/// constant-product view is refined against the pools' exact quotes.
+pub async fn plan_arbitrage(
+    venues: [&ArbitrageVenue<'_>; 2],
+    token_in: Address,
+    token_mid: Address,
+) -> Result<Option<ArbitragePlan>> {
+    let tokens = venues[0].pool.tokens()?;
+    let token = |address: Address| tokens.iter().find(|token| token.address == address).cloned();
+    let (Some(start), Some(mid)) = (token(token_in), token(token_mid)) else {
+        anyhow::bail!("Tokens {:?} and {:?} must both be in pool {:?}", token_in, token_mid, venues[0].pool.address());
+    };
+    // Fails early if the other pool doesn't hold both tokens
+    venues[1].pool.token_index(token_in)?;
+    venues[1].pool.token_index(token_mid)?;
+
+    let mut best: Option<ArbitragePlan> = None;
+    for (buy, sell) in [(venues[0], venues[1]), (venues[1], venues[0])] {
+        let Some(estimate) = compute_optimal_arbitrage(buy.buy_leg()?, sell.sell_leg()?) else {
+            continue;
+        };
+        let round_trip = async |amount_in: TokenAmount| -> Result<(TokenAmount, TokenAmount)> {
+            let amount_mid = buy.pool.quote_exact_in(token_in, token_mid, amount_in).await?;
+            let amount_out = sell.pool.quote_exact_in(token_mid, token_in, amount_mid).await?;
+            Ok((amount_mid, amount_out))
+        };
+        let (size, _) = maximise_profit(estimate * SEARCH_MULTIPLE, async |amount| {
+            let Some(amount_in) = amount_from_f64(amount, start.decimals) else {
+                return f64::NEG_INFINITY;
+            };
+            match round_trip(amount_in).await {
+                Ok((_, amount_out)) => amount_out.to_f64() - amount_in.to_f64(),
+                Err(_) => f64::NEG_INFINITY,
+            }
+        }).await;
+
+        // Settle on an exact amount and re-quote it, so the plan isn't a float estimate
+        let Some(amount_in) = amount_from_f64(size, start.decimals) else {
+            continue;
+        };
+        let Ok((amount_mid, expected_out)) = round_trip(amount_in).await else {
+            continue;
+        };
+        let profit = match expected_out.checked_sub(&amount_in) {
+            Some(profit) if !profit.is_zero() => profit,
+            _ => continue,
+        };
+        if best.as_ref().is_some_and(|best| best.profit >= profit) {
+            continue;
         }
-                 
+        best = Some(ArbitragePlan {
+            token_in,
+            token_mid,
+            symbol_in: start.symbol.clone(),
+            symbol_mid: mid.symbol.clone(),
+            buy_venue: buy.pool.venue(),
+            buy_pool: buy.pool.address(),
+            sell_venue: sell.pool.venue(),
+            sell_pool: sell.pool.address(),
+            amount_in,
+            amount_mid,
+            expected_out,
+            profit,
+        });
     }
+    Ok(best)
+}
+
+pub async fn call_arbitrage<M: Middleware + 'static>(
+    provider: Arc<M>,
+    uniswap_pool: Address,
+    balancer_pool: Address,
+    gas: &GasModel,
+    min_profit: f64,
+) -> Result<ArbitrageResult> {
+    let (pool_1, pool_2) = load_arbitrage_pools(provider, uniswap_pool, balancer_pool).await?;
+    evaluate_arbitrage(&pool_1, &pool_2, gas, min_profit).await
+}
 
-    return None;
+/// Loads the Uniswap pool with the ticks around its current price, and the Balancer pool
+pub async fn load_arbitrage_pools<M: Middleware + 'static>(
+    provider: Arc<M>,
+    uniswap_pool: Address,
+    balancer_pool: Address,
+) -> Result<(PoolUniswap<M>, PoolBalancer<M>)> {
+    let mut pool_1 = PoolUniswap::new(provider.clone(), uniswap_pool).word_radius(Some(ARBITRAGE_WORD_RADIUS));
+    pool_1.refresh().await?;
+    let mut pool_2 = PoolBalancer::new(provider, balancer_pool).await?;
+    pool_2.refresh().await?;
+    Ok((pool_1, pool_2))
 }
 
-pub async fn call_arbitrage(provider: Arc<Provider<Http>>) -> Result<()> {
-    println!("Arbitraging...");
-
-    // Get Uniswap pool info
-    let mut pool_1 = PoolUniswap::new(provider.clone(), UNISWAP_ETHEREUM_WETH_USDC.parse().expect("invalid address"));
-    pool_1.get_pool_info().await?;
-    println!("\n");
-
-    // Get Balancer pool info
-    let mut pool_2 = PoolBalancer::new(provider.clone(), BALANCER_ETHEREUM_BCoW_50WETH_50USDC.parse().expect("invalid address")).await?;
-    pool_2.get_pool_info().await?;
-    println!("\n");
-
-    let exchage_rate_pool_1 = pool_1.get_info().unwrap().token_0_balance / pool_1.get_info().unwrap().token_1_balance;
-    let exchage_rate_pool_2 = pool_2.get_info().unwrap().token_0_balance / pool_2.get_info().unwrap().token_1_balance;
-
-    println!("Exchage rate pool 1: {}", exchage_rate_pool_1);
-    println!("Exchage rate pool 2: {}", exchage_rate_pool_2);
-
-    let pool_1_token_0_balance = pool_1.get_info().unwrap().token_0_balance;
-    let pool_1_token_1_balance = pool_1.get_info().unwrap().token_1_balance;
-    let pool_2_token_0_balance = pool_2.get_info().unwrap().token_0_balance;
-    let pool_2_token_1_balance = pool_2.get_info().unwrap().token_1_balance;
-
-    if let Some(arbitrage) = compute_optimal_arbitrage(
-        pool_1_token_0_balance,
-        pool_1_token_1_balance,
-        pool_2_token_0_balance,
-        pool_2_token_1_balance,
-        exchage_rate_pool_1,
-        exchage_rate_pool_2,
-        0.997, 
-        0.9975
-    ) {
-        println!("Arbitrage: {:?}", arbitrage);
-    } else {
-        println!("No arbitrage opportunity found");
+/// Compares two loaded pools, as `call_arbitrage` does
+pub async fn evaluate_arbitrage<M: Middleware + 'static>(
+    pool_1: &PoolUniswap<M>,
+    pool_2: &PoolBalancer<M>,
+    gas: &GasModel,
+    min_profit: f64,
+) -> Result<ArbitrageResult> {
+    // Trade out of and back into Uniswap's token0, found in the Balancer pool by address
+    let uniswap_info = pool_1.get_info().ok_or_else(|| not_loaded(pool_1.address()))?;
+    let (token_in, token_mid) = (uniswap_info.token_0_addr, uniswap_info.token_1_addr);
+    let balancer_info = pool_2.get_info().ok_or_else(|| not_loaded(pool_2.address()))?;
+    let (balancer_in, balancer_mid) = (balancer_info.token(token_in)?, balancer_info.token(token_mid)?);
+
+    // Uniswap V3 trades like a constant-product pool on the virtual reserves of the active range;
+    // a weighted pool's balances over weights give a constant product with the same spot price
+    let uniswap = ArbitrageVenue { pool: pool_1, reserves: uniswap_info.virtual_reserves() };
+    let balancer = ArbitrageVenue {
+        pool: pool_2,
+        reserves: (
+            balancer_in.balance.to_f64() / balancer_in.normalized_weight.to_f64(),
+            balancer_mid.balance.to_f64() / balancer_mid.normalized_weight.to_f64(),
+        ),
+    };
+    let plan = plan_arbitrage([&uniswap, &balancer], token_in, token_mid).await?;
+
+    // Gas is priced through the Uniswap pool when it holds the native token
+    let decimals = uniswap_info.token_0_decimals;
+    let native_price = match gas.native_token == token_in {
+        true => Some(1.0),
+        false => pool_1.spot_price(gas.native_token, token_in).await.ok(),
+    };
+    let gas_breakdown = plan.as_ref().map(|plan| gas.breakdown([plan.buy_venue, plan.sell_venue], native_price, decimals));
+    let net_profit = plan
+        .as_ref()
+        .zip(gas_breakdown.as_ref())
+        .and_then(|(plan, gas)| gas.net_profit(plan.profit));
+
+    Ok(ArbitrageResult {
+        uniswap_pool: pool_1.address(),
+        balancer_pool: pool_2.address(),
+        uniswap_price: pool_1.spot_price(token_mid, token_in).await?,
+        balancer_price: Pool::spot_price(pool_2, token_mid, token_in).await?,
+        plan,
+        gas: gas_breakdown,
+        net_profit,
+        min_profit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::testing::{token, ConstantProductPool};
+    use proptest::prelude::*;
+
+    // Best profit on a fine grid of inputs. A profitable input is below the output, which is below
+    // the sell pool's reserve, so the grid stops there rather than spreading over a far larger pool.
+    fn brute_force(buy: Leg, sell: Leg) -> (f64, f64) {
+        let steps = 20_000;
+        let max_in = buy.reserve_in.min(sell.reserve_out);
+        (0..=steps)
+            .map(|step| max_in * step as f64 / steps as f64)
+            .map(|amount| (amount, sell.amount_out(buy.amount_out(amount)) - amount))
+            .fold((0.0, 0.0), |best, candidate| if candidate.1 > best.1 { candidate } else { best })
     }
 
-    Ok(())
-}
\ No newline at end of file
+    proptest! {
+        #[test]
+        fn prop_closed_form_matches_brute_force(
+            x_a in 1e3f64..1e7, y_a in 1e3f64..1e7, x_b in 1e3f64..1e7, spread in -0.05f64..0.05,
+            fee_a in 0.0f64..0.01, fee_b in 0.0f64..0.01,
+        ) {
+            // Pool B prices the middle token `spread` away from pool A
+            let y_b = x_b * y_a / x_a * (1.0 + spread);
+            let buy = Leg { reserve_in: x_a, reserve_out: y_a, gamma: 1.0 - fee_a };
+            let sell = Leg { reserve_in: y_b, reserve_out: x_b, gamma: 1.0 - fee_b };
+            let (_, grid_profit) = brute_force(buy, sell);
+            let tolerance = 1e-9 * x_a.max(x_b);
+            match compute_optimal_arbitrage(buy, sell) {
+                Some(delta) => {
+                    let profit = sell.amount_out(buy.amount_out(delta)) - delta;
+                    prop_assert!(profit > 0.0);
+                    prop_assert!(profit >= grid_profit - tolerance, "closed form {} < grid {}", profit, grid_profit);
+                }
+                None => prop_assert!(grid_profit <= tolerance, "missed a profit of {}", grid_profit),
+            }
+        }
+
+        #[test]
+        fn prop_plan_picks_direction_by_address(
+            x_a in 1e3f64..1e6, y_a in 1e3f64..1e6, x_b in 1e3f64..1e6, spread in -0.05f64..0.05,
+            fee_a in 0u32..100, fee_b in 0u32..100, reversed in any::<bool>(),
+        ) {
+            let (a, m) = (token(1, "IN"), token(2, "MID"));
+            let y_b = x_b * y_a / x_a * (1.0 + spread);
+            let pool_a = ConstantProductPool::new(0, (&a, x_a), (&m, y_a), fee_a);
+            // Pool B may list its tokens the other way round
+            let pool_b = match reversed {
+                true => ConstantProductPool::new(1, (&m, y_b), (&a, x_b), fee_b),
+                false => ConstantProductPool::new(1, (&a, x_b), (&m, y_b), fee_b),
+            };
+            let venue_a = ArbitrageVenue { pool: &pool_a, reserves: (x_a, y_a) };
+            let venue_b = ArbitrageVenue { pool: &pool_b, reserves: (x_b, y_b) };
+            let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+            let plan = runtime.block_on(plan_arbitrage([&venue_a, &venue_b], a.address, m.address)).unwrap();
+
+            let gamma = |fee_bps: u32| 1.0 - fee_bps as f64 / 10_000.0;
+            let leg = |(reserve_in, reserve_out): (f64, f64), fee_bps| Leg { reserve_in, reserve_out, gamma: gamma(fee_bps) };
+            let a_then_b = brute_force(leg((x_a, y_a), fee_a), leg((y_b, x_b), fee_b));
+            let b_then_a = brute_force(leg((x_b, y_b), fee_b), leg((y_a, x_a), fee_a));
+            let grid_best = a_then_b.1.max(b_then_a.1);
+            // Only one direction can pay, and the closed form says which even when the profit is
+            // too thin for the grid to see
+            let pays = |buy: Leg, sell: Leg| compute_optimal_arbitrage(buy, sell).is_some();
+            let a_pays = pays(leg((x_a, y_a), fee_a), leg((y_b, x_b), fee_b));
+            let b_pays = pays(leg((x_b, y_b), fee_b), leg((y_a, x_a), fee_a));
+            // A 1e-9 edge is below what whole-wei quotes resolve
+            let tolerance = 1e-9 * x_a.max(x_b);
+            match plan {
+                Some(plan) => {
+                    let buys_in_a = plan.buy_pool == pool_a.address;
+                    prop_assert_eq!((buys_in_a, !buys_in_a), (a_pays, b_pays));
+                    prop_assert!(plan.profit.to_f64() >= grid_best - tolerance);
+                    prop_assert_eq!(plan.expected_out.checked_sub(&plan.amount_in), Some(plan.profit));
+                }
+                None => prop_assert!(grid_best <= tolerance, "missed a profit of {}", grid_best),
+            }
+        }
+    }
+}
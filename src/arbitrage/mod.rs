@@ -1 +1,4 @@
-pub mod index;
\ No newline at end of file
+pub mod index;
+pub mod graph;
+pub mod gas;
+pub mod monitor;
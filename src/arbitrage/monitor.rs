@@ -0,0 +1,222 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Result};
+use ethers::prelude::*;
+use serde::Serialize;
+use crate::arbitrage::gas::GasModel;
+use crate::arbitrage::graph::{CycleOpportunity, TokenGraph};
+use crate::mirror::{MirrorUpdate, PoolMirror};
+use crate::output::{render_line, OutputFormat, Report, Table};
+
+/// Running processing time per block, from the new head arriving to opportunities being ready
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LatencyStats {
+    pub blocks: usize,
+    pub last_ms: f64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, elapsed: Duration) {
+        let ms = elapsed.as_secs_f64() * 1000.0;
+        self.blocks += 1;
+        self.last_ms = ms;
+        self.mean_ms += (ms - self.mean_ms) / self.blocks as f64;
+        self.max_ms = self.max_ms.max(ms);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockReport {
+    pub number: U64,
+    pub hash: H256,
+    // How the mirrored pools were brought up to the block
+    pub sync: MirrorUpdate,
+    // Whether the cycles were searched again; they are only when a pool changed
+    pub evaluated: bool,
+    pub sync_ms: f64,
+    pub evaluate_ms: f64,
+    pub latency: LatencyStats,
+    pub opportunities: Vec<CycleOpportunity>,
+}
+
+impl Report for BlockReport {
+    fn text(&self) -> String {
+        let mut lines = vec![format!(
+            "⛓️  Block {} {:?}: {} pool events ({} applied, {} pools reloaded) in {:.1}ms, evaluated in {:.1}ms",
+            self.number,
+            self.hash,
+            self.sync.logs,
+            self.sync.applied,
+            self.sync.refreshed.len(),
+            self.sync_ms,
+            self.evaluate_ms,
+        )];
+        if self.sync.rolled_back > 0 {
+            lines.push(format!("   Reorg: rolled back {} blocks", self.sync.rolled_back));
+        }
+        if self.sync.checked {
+            lines.push(format!("   Consistency check: {} pools had drifted from the chain", self.sync.mismatched.len()));
+        }
+        for (rank, opportunity) in self.opportunities.iter().enumerate() {
+            let net = opportunity.net_profit.map(|net| format!(", net {:.6}", net)).unwrap_or_default();
+            lines.push(format!(
+                "   {}. {}: {} in, profit {}{}",
+                rank + 1,
+                opportunity.path.join(" → "),
+                opportunity.amount_in,
+                opportunity.profit,
+                net,
+            ));
+        }
+        lines.push(format!(
+            "   Latency: {:.1}ms (mean {:.1}ms, max {:.1}ms over {} blocks)",
+            self.latency.last_ms, self.latency.mean_ms, self.latency.max_ms, self.latency.blocks,
+        ));
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(["block", "path", "amount_in", "profit", "net_profit", "latency_ms"]);
+        for opportunity in &self.opportunities {
+            table.row([
+                self.number.to_string(),
+                opportunity.path.join(" → "),
+                opportunity.amount_in.to_string(),
+                opportunity.profit.to_string(),
+                opportunity.net_profit.map(|net| net.to_string()).unwrap_or_else(|| "-".to_string()),
+                format!("{:.1}", self.latency.last_ms),
+            ]);
+        }
+        table
+    }
+}
+
+/// Where block reports go: stdout in the CLI's output format (JSON output gives JSON lines), or appended to a file as JSON lines
+pub enum Sink {
+    Stdout(OutputFormat),
+    File(File),
+}
+
+impl Sink {
+    pub fn file(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| anyhow!("Failed to open {}: {}", path.display(), e))?;
+        Ok(Self::File(file))
+    }
+
+    pub fn emit(&mut self, report: &BlockReport) -> Result<()> {
+        match self {
+            Self::Stdout(format) => println!("{}", render_line(report, *format)?),
+            Self::File(file) => writeln!(file, "{}", serde_json::to_string(report)?)?,
+        }
+        Ok(())
+    }
+}
+
+pub struct MonitorConfig {
+    pub start_tokens: Vec<Address>,
+    pub max_hops: usize,
+    pub min_profit: f64,
+    pub gas: GasModel,
+    // Balancer V2 swaps are logged by the Vault, so it's watched too
+    pub vault: Option<Address>,
+    // Blocks between consistency checks of the mirrored pools; 0 turns them off
+    pub check_interval: u64,
+    // Stop after this many blocks
+    pub blocks: Option<usize>,
+}
+
+/// Follows new heads, keeps the graph's pools in step through a `PoolMirror` and re-evaluates the
+/// cycles whenever a pool changed. Failures within a block are reported and the monitor moves on.
+pub async fn run_monitor<P: PubsubClient + 'static>(
+    provider: Arc<Provider<P>>,
+    graph: &mut TokenGraph,
+    mut config: MonitorConfig,
+    sinks: &mut [Sink],
+) -> Result<()> {
+    let mut mirror = PoolMirror::new(graph.pools(), config.vault).check_interval(config.check_interval);
+    let mut stream = provider.subscribe_blocks().await?;
+    let mut latency = LatencyStats::default();
+    // Set when a search fails, so the next block searches even if no pool changed
+    let mut search_failed = false;
+    while let Some(block) = stream.next().await {
+        let started = Instant::now();
+        let (Some(number), Some(hash)) = (block.number, block.hash) else {
+            continue;
+        };
+        if let Some(base_fee) = block.base_fee_per_gas {
+            config.gas.gas_price.base_fee = base_fee;
+        }
+
+        let sync = match mirror.sync_block(&*provider, graph.pools_mut(), &block).await {
+            Ok(sync) => sync,
+            Err(e) => {
+                eprintln!("Block {}: failed to sync pools: {}", number, e);
+                continue;
+            }
+        };
+        for error in &sync.errors {
+            eprintln!("Block {}: {}", number, error);
+        }
+        let sync_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        // The first block sets the baseline; after that only changed pools can change the answer
+        let evaluated = latency.blocks == 0 || search_failed || sync.changed();
+        let opportunities = match evaluated {
+            true => match graph.search(&config.start_tokens, config.max_hops, &config.gas, config.min_profit).await {
+                Ok(search) => search.opportunities,
+                Err(e) => {
+                    eprintln!("Block {}: failed to search for opportunities: {}", number, e);
+                    search_failed = true;
+                    continue;
+                }
+            },
+            false => Vec::new(),
+        };
+        search_failed = false;
+        latency.record(started.elapsed());
+
+        let report = BlockReport {
+            number,
+            hash,
+            sync,
+            evaluated,
+            sync_ms,
+            evaluate_ms: latency.last_ms - sync_ms,
+            latency: latency.clone(),
+            opportunities,
+        };
+        for sink in sinks.iter_mut() {
+            sink.emit(&report)?;
+        }
+        if config.blocks.is_some_and(|blocks| latency.blocks >= blocks) {
+            return Ok(());
+        }
+    }
+    Err(anyhow!("Block subscription ended"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_stats() {
+        let mut latency = LatencyStats::default();
+        for ms in [10, 30, 20] {
+            latency.record(Duration::from_millis(ms));
+        }
+        assert_eq!(latency.blocks, 3);
+        assert!((latency.mean_ms - 20.0).abs() < 1e-9);
+        assert!((latency.max_ms - 30.0).abs() < 1e-9);
+        assert!((latency.last_ms - 20.0).abs() < 1e-9);
+    }
+}
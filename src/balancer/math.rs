@@ -0,0 +1,210 @@
+// Port of the Balancer V1 pool math libraries (BNum, BMath); errors carry the contract's error string
+use ethers::types::U256;
+use anyhow::{anyhow, bail, Result};
+
+// 18-decimal fixed point one
+pub fn bone() -> U256 {
+    U256::exp10(18)
+}
+
+fn min_bpow_base() -> U256 {
+    U256::one()
+}
+
+fn max_bpow_base() -> U256 {
+    bone() * 2 - 1
+}
+
+// Precision at which the series in `bpow_approx` stops
+fn bpow_precision() -> U256 {
+    bone() / U256::exp10(10)
+}
+
+// Largest trade BPool accepts: half the input balance in, a third of the output balance out
+pub fn max_in_ratio() -> U256 {
+    bone() / 2
+}
+
+pub fn max_out_ratio() -> U256 {
+    bone() / 3 + 1
+}
+
+fn btoi(a: U256) -> U256 {
+    a / bone()
+}
+
+fn bfloor(a: U256) -> U256 {
+    btoi(a) * bone()
+}
+
+fn badd(a: U256, b: U256) -> Result<U256> {
+    a.checked_add(b).ok_or_else(|| anyhow!("ERR_ADD_OVERFLOW"))
+}
+
+fn bsub(a: U256, b: U256) -> Result<U256> {
+    a.checked_sub(b).ok_or_else(|| anyhow!("ERR_SUB_UNDERFLOW"))
+}
+
+// |a - b| and whether it's negative
+fn bsub_sign(a: U256, b: U256) -> (U256, bool) {
+    if a >= b { (a - b, false) } else { (b - a, true) }
+}
+
+pub fn bmul(a: U256, b: U256) -> Result<U256> {
+    let c0 = a.checked_mul(b).ok_or_else(|| anyhow!("ERR_MUL_OVERFLOW"))?;
+    let c1 = badd(c0, bone() / 2)?;
+    Ok(c1 / bone())
+}
+
+pub fn bdiv(a: U256, b: U256) -> Result<U256> {
+    if b.is_zero() {
+        bail!("ERR_DIV_ZERO");
+    }
+    let c0 = a.checked_mul(bone()).ok_or_else(|| anyhow!("ERR_DIV_INTERNAL"))?;
+    let c1 = badd(c0, b / 2)?;
+    Ok(c1 / b)
+}
+
+// a^n for a whole n, by squaring
+fn bpowi(mut a: U256, mut n: U256) -> Result<U256> {
+    let mut z = if n % 2 != U256::zero() { a } else { bone() };
+    n /= 2;
+    while !n.is_zero() {
+        a = bmul(a, a)?;
+        if n % 2 != U256::zero() {
+            z = bmul(z, a)?;
+        }
+        n /= 2;
+    }
+    Ok(z)
+}
+
+/// `base^exp` for an 18-decimal base in (0, 2) and any 18-decimal exponent
+pub fn bpow(base: U256, exp: U256) -> Result<U256> {
+    if base < min_bpow_base() {
+        bail!("ERR_BPOW_BASE_TOO_LOW");
+    }
+    if base > max_bpow_base() {
+        bail!("ERR_BPOW_BASE_TOO_HIGH");
+    }
+    let whole = bfloor(exp);
+    let remain = bsub(exp, whole)?;
+    let whole_pow = bpowi(base, btoi(whole))?;
+    if remain.is_zero() {
+        return Ok(whole_pow);
+    }
+    let partial = bpow_approx(base, remain, bpow_precision())?;
+    bmul(whole_pow, partial)
+}
+
+// Binomial series for base^exp with a fractional exp, summed until a term drops below precision
+fn bpow_approx(base: U256, exp: U256, precision: U256) -> Result<U256> {
+    let a = exp;
+    let (x, xneg) = bsub_sign(base, bone());
+    let mut term = bone();
+    let mut sum = term;
+    let mut negative = false;
+
+    let mut i = U256::one();
+    while term >= precision {
+        let big_k = i * bone();
+        let (c, cneg) = bsub_sign(a, bsub(big_k, bone())?);
+        term = bmul(term, bmul(c, x)?)?;
+        term = bdiv(term, big_k)?;
+        if term.is_zero() {
+            break;
+        }
+        if xneg {
+            negative = !negative;
+        }
+        if cneg {
+            negative = !negative;
+        }
+        sum = if negative { bsub(sum, term)? } else { badd(sum, term)? };
+        i += U256::one();
+    }
+    Ok(sum)
+}
+
+/// Price of the output token in input-token units, fee included, as an 18-decimal ratio of raw amounts
+pub fn calc_spot_price(
+    balance_in: U256,
+    weight_in: U256,
+    balance_out: U256,
+    weight_out: U256,
+    swap_fee: U256,
+) -> Result<U256> {
+    let numer = bdiv(balance_in, weight_in)?;
+    let denom = bdiv(balance_out, weight_out)?;
+    let ratio = bdiv(numer, denom)?;
+    let scale = bdiv(bone(), bsub(bone(), swap_fee)?)?;
+    bmul(ratio, scale)
+}
+
+/// Output amount for an exact input:
+/// `balance_out * (1 - (balance_in / (balance_in + amount_in * (1 - fee))) ^ (weight_in / weight_out))`
+pub fn calc_out_given_in(
+    balance_in: U256,
+    weight_in: U256,
+    balance_out: U256,
+    weight_out: U256,
+    amount_in: U256,
+    swap_fee: U256,
+) -> Result<U256> {
+    let weight_ratio = bdiv(weight_in, weight_out)?;
+    let adjusted_in = bmul(amount_in, bsub(bone(), swap_fee)?)?;
+    let y = bdiv(balance_in, badd(balance_in, adjusted_in)?)?;
+    let power = bpow(y, weight_ratio)?;
+    bmul(balance_out, bsub(bone(), power)?)
+}
+
+/// Input amount for an exact output:
+/// `balance_in * ((balance_out / (balance_out - amount_out)) ^ (weight_out / weight_in) - 1) / (1 - fee)`
+pub fn calc_in_given_out(
+    balance_in: U256,
+    weight_in: U256,
+    balance_out: U256,
+    weight_out: U256,
+    amount_out: U256,
+    swap_fee: U256,
+) -> Result<U256> {
+    let weight_ratio = bdiv(weight_out, weight_in)?;
+    let diff = bsub(balance_out, amount_out)?;
+    let y = bdiv(balance_out, diff)?;
+    let growth = bsub(bpow(y, weight_ratio)?, bone())?;
+    bdiv(bmul(balance_in, growth)?, bsub(bone(), swap_fee)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn e18(value: f64) -> U256 {
+        U256::from((value * 1e18) as u128)
+    }
+
+    fn to_f64(value: U256) -> f64 {
+        value.as_u128() as f64 / 1e18
+    }
+
+    #[test]
+    fn test_bpow_and_weighted_swaps() {
+        assert!((to_f64(bpow(e18(0.5), e18(0.5)).unwrap()) - 0.5f64.sqrt()).abs() < 1e-9);
+        assert!((to_f64(bpow(e18(1.5), e18(2.25)).unwrap()) - 1.5f64.powf(2.25)).abs() < 1e-9);
+        assert!(bpow(e18(2.0), bone()).is_err());
+
+        // 80/20 pool: 800 A and 200 B means 1 B is spot priced at 1 A before fees
+        let (balance_a, balance_b, weight_a, weight_b) = (e18(800.0), e18(200.0), e18(8.0), e18(2.0));
+        let fee = e18(0.003);
+        assert_eq!(calc_spot_price(balance_a, weight_a, balance_b, weight_b, U256::zero()).unwrap(), bone());
+
+        // Selling 10 A: 200 * (1 - (800 / 809.97)^4)
+        let amount_out = calc_out_given_in(balance_a, weight_a, balance_b, weight_b, e18(10.0), fee).unwrap();
+        let expected = 200.0 * (1.0 - (800.0f64 / (800.0 + 10.0 * 0.997)).powi(4));
+        assert!((to_f64(amount_out) - expected).abs() < 1e-9);
+
+        // Buying that output back needs (almost exactly) the same input
+        let amount_in = calc_in_given_out(balance_a, weight_a, balance_b, weight_b, amount_out, fee).unwrap();
+        assert!((to_f64(amount_in) - 10.0).abs() < 1e-9);
+    }
+}
@@ -1,7 +1,12 @@
 use ethers::prelude::*;
-use ethers::providers::{Provider, Http};
 use std::sync::Arc;
-use anyhow::Result;
+use ethers::types::U512;
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+use crate::amount::TokenAmount;
+use crate::output::{Report, Table};
+use crate::balancer::math::{bmul, calc_in_given_out, calc_out_given_in, calc_spot_price, max_in_ratio, max_out_ratio};
+use crate::pool::{decode_event, not_loaded, verify_contract, Pool, PoolSnapshot, PoolToken, RouteCall};
 
 abigen!(
     BalancerPool,
@@ -9,39 +14,126 @@ abigen!(
         function getSwapFee() external view returns (uint256)
         function getBalance(address token) external view returns (uint256)
         function getFinalTokens() external view returns (address[] memory)
+        function getDenormalizedWeight(address token) external view returns (uint256)
+        function getNormalizedWeight(address token) external view returns (uint256)
+        function swapExactAmountIn(address tokenIn, uint256 tokenAmountIn, address tokenOut, uint256 minAmountOut, uint256 maxPrice) external returns (uint256 tokenAmountOut, uint256 spotPriceAfter)
+        event LOG_SWAP(address indexed caller, address indexed tokenIn, address indexed tokenOut, uint256 tokenAmountIn, uint256 tokenAmountOut)
+        event LOG_JOIN(address indexed caller, address indexed tokenIn, uint256 tokenAmountIn)
+        event LOG_EXIT(address indexed caller, address indexed tokenOut, uint256 tokenAmountOut)
     ]"#
 );
 
-abigen!(
-    IERC20,
-    r#"[
-        function symbol() external view returns (string)
-        function decimals() external view returns (uint8)
-        function balanceOf(address owner) external view returns (uint256)
-    ]"#
-);
+#[derive(Debug, Clone, Serialize)]
+pub struct BalancerToken {
+    pub address: Address,
+    pub symbol: String,
+    pub decimals: u8,
+    pub balance: TokenAmount,
+    // Weight the pool's math uses, and its share of the total weight (18 decimals)
+    pub denormalized_weight: U256,
+    pub normalized_weight: TokenAmount,
+}
 
+#[derive(Debug, Clone, Serialize)]
 pub struct PoolInfo {
+    pub address: Address,
     pub fee: U256,
-    pub token_0_addr: Address,
-    pub token_1_addr: Address,
-    pub token_0_symbol: String,
-    pub token_1_symbol: String,
-    pub token_0_balance: f64,
-    pub token_1_balance: f64,
+    pub tokens: Vec<BalancerToken>,
 }
 
-pub struct PoolBalancer {
-    contract: BalancerPool<Provider<Http>>,
-    provider: Arc<Provider<Http>>,
+impl PoolInfo {
+    pub fn token(&self, address: Address) -> Result<&BalancerToken> {
+        self.tokens
+            .iter()
+            .find(|token| token.address == address)
+            .ok_or_else(|| anyhow!("Token {:?} is not in pool {:?}", address, self.address))
+    }
+}
+
+impl Report for PoolInfo {
+    fn text(&self) -> String {
+        let mut lines = vec![
+            "🪣  Balancer Pool Info:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Pool: {:?}", self.address),
+            format!("Fee: {}", self.fee),
+        ];
+        for token in &self.tokens {
+            lines.push(format!(
+                "{}: {:.6} (weight {:.2}%)",
+                token.symbol,
+                token.balance,
+                token.normalized_weight.to_f64() * 100.0
+            ));
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(["token", "address", "balance", "weight"]);
+        for token in &self.tokens {
+            table.row([
+                token.symbol.clone(),
+                format!("{:?}", token.address),
+                token.balance.to_string(),
+                token.normalized_weight.to_string(),
+            ]);
+        }
+        table
+    }
+}
+
+/// A swap quoted with the pool's own weighted math
+#[derive(Debug, Clone, Serialize)]
+pub struct BalancerQuote {
+    pub pool: Address,
+    pub token_in: Address,
+    pub token_out: Address,
+    pub symbol_in: String,
+    pub symbol_out: String,
+    pub amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+    // Price of the output in the input token before the swap, fee included
+    pub spot_price: TokenAmount,
+    pub exact_out: bool,
+}
+
+impl Report for BalancerQuote {
+    fn text(&self) -> String {
+        [
+            "🧮 Balancer Quote:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Pool: {:?}", self.pool),
+            format!("Sell: {} {}", self.amount_in, self.symbol_in),
+            format!("Buy: {} {}", self.amount_out, self.symbol_out),
+            format!("Spot price: {} {} per {}", self.spot_price, self.symbol_in, self.symbol_out),
+        ]
+        .join("\n")
+    }
+
+    fn table(&self) -> Table {
+        Table::key_value([
+            ("pool", format!("{:?}", self.pool)),
+            ("token_in", self.symbol_in.clone()),
+            ("token_out", self.symbol_out.clone()),
+            ("amount_in", self.amount_in.to_string()),
+            ("amount_out", self.amount_out.to_string()),
+            ("spot_price", self.spot_price.to_string()),
+        ])
+    }
+}
+
+pub struct PoolBalancer<M> {
+    contract: BalancerPool<M>,
+    provider: Arc<M>,
     address: Address,
     tokens: Vec<Address>,
     info: Option<PoolInfo>,
 }
 
-impl PoolBalancer {
+impl<M: Middleware + 'static> PoolBalancer<M> {
     // modify this to supply the pool address in args
-    pub async fn new(provider: Arc<Provider<Http>>, pool_address: Address) -> Result<Self> {
+    pub async fn new(provider: Arc<M>, pool_address: Address) -> Result<Self> {
         let contract = BalancerPool::new(pool_address, provider.clone());
 
         let tokens = contract.get_final_tokens().call().await?;
@@ -55,72 +147,300 @@ impl PoolBalancer {
         })
     }
 
-    pub async fn verify_contract(&self) -> Result<()> {
-        println!("\nVerifying contract at address: {:?}", self.address);
-        
-        // Check if contract exists by getting its code
-        match self.provider.get_code(self.address, None).await {
-            Ok(code) => {
-                if code.is_empty() {
-                    return Err(anyhow::anyhow!("No contract code found at address {:?}", self.address));
-                }
-                println!("Contract code found. Code size: {} bytes", code.len());
-            }
-            Err(e) => {
-                return Err(anyhow::anyhow!("Failed to get contract code: {}", e));
-            }
-        }
-        Ok(())
-    }
-
-    pub async fn get_pool_info(&mut self) -> Result<()> {        
+    pub async fn get_pool_info(&mut self) -> Result<&PoolInfo> {
         // First verify the contract exists
-        self.verify_contract().await?;
+        verify_contract(&*self.provider, self.address).await?;
 
         // Get swap fee
         let swap_fee = self.contract.get_swap_fee().call().await?;
 
-        let mut pool_info = PoolInfo {
+        let mut tokens = Vec::with_capacity(self.tokens.len());
+        for token in &self.tokens {
+            let metadata = PoolToken::fetch(self.provider.clone(), *token).await?;
+            let balance = self.contract.get_balance(*token).call().await?;
+            tokens.push(BalancerToken {
+                address: *token,
+                balance: TokenAmount::new(balance, metadata.decimals),
+                symbol: metadata.symbol,
+                decimals: metadata.decimals,
+                denormalized_weight: self.contract.get_denormalized_weight(*token).call().await?,
+                normalized_weight: TokenAmount::new(self.contract.get_normalized_weight(*token).call().await?, 18),
+            });
+        }
+
+        let pool_info = PoolInfo {
+            address: self.address,
             fee: swap_fee,
-            token_0_addr: self.tokens[0],
-            token_1_addr: self.tokens[1],
-            token_0_symbol: String::new(),
-            token_1_symbol: String::new(),
-            token_0_balance: 0.0,
-            token_1_balance: 0.0,
+            tokens,
         };
+        
+        Ok(self.info.insert(pool_info))
+    }
 
-        for (index, token) in self.tokens.iter().enumerate() {
-            let token_contract = IERC20::new(*token, self.provider.clone());
-            let token_symbol = token_contract.symbol().call().await?;
-            let token_decimals = token_contract.decimals().call().await?;   
-            let balance = self.contract.get_balance(*token).call().await?;
-            let human_balance = balance.as_u128() as f64 / 10f64.powi(token_decimals as i32);
-
-            // Modify this to support more than 2 tokens
-            if index == 0 && self.tokens.len() == 2 {
-                pool_info.token_0_addr = *token;
-                pool_info.token_0_symbol = token_symbol;
-                pool_info.token_0_balance = human_balance;
-            } else if index == 1 && self.tokens.len() == 2 {
-                pool_info.token_1_symbol = token_symbol;
-                pool_info.token_1_balance = human_balance;
+    pub fn get_info(&self) -> Option<&PoolInfo> {
+        self.info.as_ref()
+    }
+
+    /// Marginal price of `base` in units of `quote` before fees, from balances and weights
+    pub fn spot_price(&self, base: Address, quote: Address) -> Result<TokenAmount> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        let (base, quote) = (info.token(base)?, info.token(quote)?);
+        // calcSpotPrice works on raw amounts: quote wei per base wei
+        let raw = calc_spot_price(
+            quote.balance.raw(),
+            quote.denormalized_weight,
+            base.balance.raw(),
+            base.denormalized_weight,
+            U256::zero(),
+        )?;
+        let scaled = U512::from(raw) * U512::exp10(base.decimals as usize) / U512::exp10(quote.decimals as usize);
+        Ok(TokenAmount::new(U256::try_from(scaled).unwrap_or(U256::MAX), 18))
+    }
+
+    /// Quotes a swap of `amount` (input, or output with `exact_out`) with `calcOutGivenIn`/`calcInGivenOut`
+    pub fn quote(&self, token_in: Address, token_out: Address, amount: TokenAmount, exact_out: bool) -> Result<BalancerQuote> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        if token_in == token_out {
+            bail!("Cannot swap {:?} for itself", token_in);
+        }
+        let (token_in, token_out) = (info.token(token_in)?, info.token(token_out)?);
+        let (balance_in, balance_out) = (token_in.balance.raw(), token_out.balance.raw());
+        let (weight_in, weight_out) = (token_in.denormalized_weight, token_out.denormalized_weight);
+
+        let (amount_in, amount_out) = if exact_out {
+            if amount.raw() > bmul(balance_out, max_out_ratio())? {
+                bail!("ERR_MAX_OUT_RATIO: the pool pays out at most a third of its {} balance", token_out.symbol);
             }
+            let amount_in = calc_in_given_out(balance_in, weight_in, balance_out, weight_out, amount.raw(), info.fee)?;
+            (amount_in, amount.raw())
+        } else {
+            let amount_out = calc_out_given_in(balance_in, weight_in, balance_out, weight_out, amount.raw(), info.fee)?;
+            (amount.raw(), amount_out)
+        };
+        if amount_in > bmul(balance_in, max_in_ratio())? {
+            bail!("ERR_MAX_IN_RATIO: the pool takes in at most half its {} balance", token_in.symbol);
         }
-        
-         // Display pool information
-         println!("\n🪣  Balancer Pool Info:");
-         println!("-------------------------------------");
-         println!("Fee: {}", pool_info.fee);
-         println!("{}: {:.6}", pool_info.token_0_symbol, pool_info.token_0_balance);
-         println!("{}: {:.6}", pool_info.token_1_symbol, pool_info.token_1_balance);
 
-        self.info = Some(pool_info);
+        let spot_price = calc_spot_price(balance_in, weight_in, balance_out, weight_out, info.fee)?;
+        let spot_price = U512::from(spot_price) * U512::exp10(token_out.decimals as usize) / U512::exp10(token_in.decimals as usize);
+        Ok(BalancerQuote {
+            pool: self.address,
+            token_in: token_in.address,
+            token_out: token_out.address,
+            symbol_in: token_in.symbol.clone(),
+            symbol_out: token_out.symbol.clone(),
+            amount_in: TokenAmount::new(amount_in, token_in.decimals),
+            amount_out: TokenAmount::new(amount_out, token_out.decimals),
+            spot_price: TokenAmount::new(U256::try_from(spot_price).unwrap_or(U256::MAX), 18),
+            exact_out,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<M: Middleware + 'static> Pool for PoolBalancer<M> {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn venue(&self) -> &'static str {
+        "balancer"
+    }
+
+    fn tokens(&self) -> Result<Vec<PoolToken>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(info
+            .tokens
+            .iter()
+            .map(|token| PoolToken { address: token.address, symbol: token.symbol.clone(), decimals: token.decimals })
+            .collect())
+    }
+
+    fn fee(&self) -> Result<f64> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(TokenAmount::new(info.fee, 18).to_f64())
+    }
+
+    fn reserves(&self) -> Result<Vec<TokenAmount>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(info.tokens.iter().map(|token| token.balance).collect())
+    }
+
+    async fn spot_price(&self, base: Address, quote: Address) -> Result<f64> {
+        Ok(PoolBalancer::spot_price(self, base, quote)?.to_f64())
+    }
+
+    async fn quote_exact_in(&self, token_in: Address, token_out: Address, amount_in: TokenAmount) -> Result<TokenAmount> {
+        Ok(self.quote(token_in, token_out, amount_in, false)?.amount_out)
+    }
 
+    async fn refresh(&mut self) -> Result<()> {
+        self.get_pool_info().await?;
         Ok(())
     }
 
-    pub fn get_info(&self) -> Option<&PoolInfo> {
-        self.info.as_ref()
+    // The pool's balances are its own records, moved by exactly the amounts these events log
+    fn apply_log(&mut self, log: &Log) -> Result<bool> {
+        let info = self.info.as_mut().ok_or_else(|| not_loaded(self.address))?;
+        let moves = match decode_event::<BalancerPoolEvents>(log) {
+            Some(BalancerPoolEvents::LogSwapFilter(swap)) => {
+                vec![(swap.token_in, swap.token_amount_in, true), (swap.token_out, swap.token_amount_out, false)]
+            }
+            Some(BalancerPoolEvents::LogJoinFilter(join)) => vec![(join.token_in, join.token_amount_in, true)],
+            Some(BalancerPoolEvents::LogExitFilter(exit)) => vec![(exit.token_out, exit.token_amount_out, false)],
+            None => return Ok(false),
+        };
+        for (address, amount, into_pool) in moves {
+            let token = info
+                .tokens
+                .iter_mut()
+                .find(|token| token.address == address)
+                .ok_or_else(|| anyhow!("Token {:?} is not in pool {:?}", address, self.address))?;
+            let amount = TokenAmount::new(amount, token.decimals);
+            token.balance = match into_pool {
+                true => token.balance.checked_add(&amount),
+                false => token.balance.checked_sub(&amount),
+            }
+            .ok_or_else(|| anyhow!("{} balance out of range after event; pool state is stale", token.symbol))?;
+        }
+        Ok(true)
+    }
+
+    fn snapshot(&self) -> PoolSnapshot {
+        PoolSnapshot::new(self.info.clone())
+    }
+
+    fn restore(&mut self, snapshot: PoolSnapshot) -> Result<()> {
+        self.info = snapshot.take()?;
+        Ok(())
+    }
+
+    // No per-leg output or price limit; the executor checks what the whole route returns
+    fn swap_calls(&self, _executor: Address, token_in: Address, token_out: Address, amount_in: U256, _amount_out: U256) -> Result<Vec<RouteCall>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        let (token_in, token_out) = (info.token(token_in)?.address, info.token(token_out)?.address);
+        let swap = SwapExactAmountInCall {
+            token_in,
+            token_amount_in: amount_in,
+            token_out,
+            min_amount_out: U256::zero(),
+            max_price: U256::MAX,
+        };
+        Ok(vec![RouteCall::approve(token_in, self.address, amount_in), RouteCall::new(self.address, swap)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 50/50 WETH/USDC pool at 2000 with a 0.3% fee, loaded without touching the provider
+    fn weth_usdc() -> (PoolBalancer<Provider<MockProvider>>, Address, Address) {
+        let (provider, _mock) = Provider::mocked();
+        let provider = Arc::new(provider);
+        let (weth, usdc, address) = (Address::repeat_byte(1), Address::repeat_byte(2), Address::repeat_byte(3));
+        let token = |address: Address, symbol: &str, decimals: u8, balance: U256| BalancerToken {
+            address,
+            symbol: symbol.to_string(),
+            decimals,
+            balance: TokenAmount::new(balance, decimals),
+            denormalized_weight: U256::exp10(19) * 25,
+            normalized_weight: TokenAmount::new(U256::exp10(17) * 5, 18),
+        };
+        let info = PoolInfo {
+            address,
+            fee: U256::exp10(15) * 3,
+            tokens: vec![
+                token(weth, "WETH", 18, U256::exp10(21)),
+                token(usdc, "USDC", 6, U256::from(2_000_000u64) * U256::exp10(6)),
+            ],
+        };
+        let pool = PoolBalancer {
+            contract: BalancerPool::new(address, provider.clone()),
+            provider,
+            address,
+            tokens: vec![weth, usdc],
+            info: Some(info),
+        };
+        (pool, weth, usdc)
+    }
+
+    #[test]
+    fn test_pool_trait_reads_cached_info() {
+        let (mut balancer, _, usdc) = weth_usdc();
+        let pool: &dyn Pool = &balancer;
+        assert_eq!(pool.token_index(usdc).unwrap(), 1);
+        assert_eq!(pool.fee().unwrap(), 0.003);
+        assert_eq!(pool.reserves().unwrap()[0], TokenAmount::new(U256::exp10(21), 18));
+        assert!(pool.token_index(Address::repeat_byte(4)).is_err());
+
+        balancer.info = None;
+        assert!(Pool::tokens(&balancer).is_err());
+        assert!(Pool::reserves(&balancer).is_err());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_quote_with_weighted_math() {
+        let (balancer, weth, usdc) = weth_usdc();
+        let info = balancer.get_info().unwrap();
+        let (weth_token, usdc_token) = (&info.tokens[0], &info.tokens[1]);
+        let one = TokenAmount::new(U256::exp10(18), 18);
+        let out = calc_out_given_in(
+            weth_token.balance.raw(),
+            weth_token.denormalized_weight,
+            usdc_token.balance.raw(),
+            usdc_token.denormalized_weight,
+            one.raw(),
+            info.fee,
+        )
+        .unwrap();
+
+        let pool: &dyn Pool = &balancer;
+        assert_eq!(pool.quote_exact_in(weth, usdc, one).await.unwrap(), TokenAmount::new(out, 6));
+        assert!((pool.spot_price(weth, usdc).await.unwrap() - 2000.0).abs() < 1e-9);
+        assert!(pool.quote_exact_in(weth, weth, one).await.is_err());
+        // The pool pays out at most a third of a balance
+        assert!(balancer.quote(weth, usdc, TokenAmount::new(usdc_token.balance.raw() / 2, 6), true).is_err());
+    }
+
+    #[test]
+    fn test_apply_log_moves_balances() {
+        let (mut balancer, weth, usdc) = weth_usdc();
+        let log = |topics: Vec<H256>, amounts: Vec<U256>| Log {
+            topics,
+            data: ethers::abi::encode(&amounts.into_iter().map(ethers::abi::Token::Uint).collect::<Vec<_>>()).into(),
+            ..Default::default()
+        };
+        let topic = |address: Address| H256::from(address);
+
+        // Swaps, joins and exits move the balances by the logged amounts
+        let (amount_in, amount_out) = (U256::exp10(18), U256::from(1_990_000_000u64));
+        let swap = log(vec![LogSwapFilter::signature(), topic(Address::zero()), topic(weth), topic(usdc)], vec![amount_in, amount_out]);
+        assert!(balancer.apply_log(&swap).unwrap());
+        let join = log(vec![LogJoinFilter::signature(), topic(Address::zero()), topic(usdc)], vec![U256::exp10(6)]);
+        assert!(balancer.apply_log(&join).unwrap());
+        let balances = Pool::reserves(&balancer).unwrap();
+        assert_eq!(balances[0].raw(), U256::exp10(21) + amount_in);
+        assert_eq!(balances[1].raw(), U256::from(2_000_000u64) * U256::exp10(6) - amount_out + U256::exp10(6));
+
+        let exit = log(vec![LogExitFilter::signature(), topic(Address::zero()), topic(Address::repeat_byte(4))], vec![U256::one()]);
+        assert!(balancer.apply_log(&exit).is_err());
+        assert!(!balancer.apply_log(&Log::default()).unwrap());
+    }
+
+    #[test]
+    fn test_swap_calls_approve_and_swap() {
+        let (balancer, weth, usdc) = weth_usdc();
+        let amount_in = U256::exp10(18);
+        let calls = Pool::swap_calls(&balancer, Address::zero(), weth, usdc, amount_in, U256::zero()).unwrap();
+        assert_eq!(calls[0], RouteCall::approve(weth, balancer.address, amount_in));
+        let swap = SwapExactAmountInCall {
+            token_in: weth,
+            token_amount_in: amount_in,
+            token_out: usdc,
+            min_amount_out: U256::zero(),
+            max_price: U256::MAX,
+        };
+        assert_eq!(calls[1].data.to_vec(), ethers::abi::AbiEncode::encode(swap));
+    }
+}
@@ -0,0 +1,602 @@
+use ethers::prelude::*;
+use std::sync::Arc;
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+use crate::amount::TokenAmount;
+use crate::balancer::vault_math::{
+    complement, div_down, div_up, mul_down, mul_up, one, stable_in_given_out, stable_out_given_in,
+    weighted_in_given_out, weighted_out_given_in, AMP_PRECISION,
+};
+use crate::contracts::ChainAddresses;
+use crate::output::{Report, Table};
+use crate::pool::{decode_event, marginal_amount, not_loaded, verify_contract, Pool, PoolSnapshot, PoolToken, RouteCall};
+
+// Balancer V2 Vault ABI fragment; every V2 pool's tokens and balances live in the Vault
+abigen!(
+    BalancerVault,
+    r#"[
+        struct BatchSwapStep { bytes32 poolId; uint256 assetInIndex; uint256 assetOutIndex; uint256 amount; bytes userData; }
+        struct FundManagement { address sender; bool fromInternalBalance; address recipient; bool toInternalBalance; }
+        struct SingleSwap { bytes32 poolId; uint8 kind; address assetIn; address assetOut; uint256 amount; bytes userData; }
+        function getPoolTokens(bytes32 poolId) external view returns (address[] tokens, uint256[] balances, uint256 lastChangeBlock)
+        function getPool(bytes32 poolId) external view returns (address, uint8)
+        function queryBatchSwap(uint8 kind, BatchSwapStep[] swaps, address[] assets, FundManagement funds) external returns (int256[] assetDeltas)
+        function swap(SingleSwap singleSwap, FundManagement funds, uint256 limit, uint256 deadline) external returns (uint256 amountCalculated)
+        event Swap(bytes32 indexed poolId, address indexed tokenIn, address indexed tokenOut, uint256 amountIn, uint256 amountOut)
+        event PoolBalanceChanged(bytes32 indexed poolId, address indexed liquidityProvider, address[] tokens, int256[] deltas, uint256[] protocolFeeAmounts)
+    ]"#
+);
+
+// Pool contract getters; which ones exist tells the pool type apart
+abigen!(
+    BalancerV2Pool,
+    r#"[
+        function getPoolId() external view returns (bytes32)
+        function getSwapFeePercentage() external view returns (uint256)
+        function getNormalizedWeights() external view returns (uint256[])
+        function getAmplificationParameter() external view returns (uint256 value, bool isUpdating, uint256 precision)
+        function getScalingFactors() external view returns (uint256[])
+        function getBptIndex() external view returns (uint256)
+    ]"#
+);
+
+// `SwapKind` in the Vault
+const GIVEN_IN: u8 = 0;
+const GIVEN_OUT: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VaultPoolType {
+    Weighted,
+    Stable,
+    // Stable pool that holds its own BPT as one of the tokens
+    ComposableStable,
+    // Anything else; only `queryBatchSwap` can quote it
+    Other,
+}
+
+impl std::fmt::Display for VaultPoolType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Weighted => "weighted",
+            Self::Stable => "stable",
+            Self::ComposableStable => "composable stable",
+            Self::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultToken {
+    pub address: Address,
+    pub symbol: String,
+    pub decimals: u8,
+    pub balance: TokenAmount,
+    // Normalized weight, for weighted pools
+    pub weight: Option<TokenAmount>,
+    // Multiplier to 18 decimals (and, for rate providers, the token rate), 18 decimals itself
+    pub scaling_factor: U256,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolInfo {
+    pub pool_id: H256,
+    pub address: Address,
+    pub vault: Address,
+    pub pool_type: VaultPoolType,
+    pub fee: TokenAmount,
+    // Amplification with `AMP_PRECISION`, for stable pools
+    pub amplification: Option<U256>,
+    pub bpt_index: Option<usize>,
+    pub last_change_block: U256,
+    pub tokens: Vec<VaultToken>,
+}
+
+impl PoolInfo {
+    pub fn token_index(&self, address: Address) -> Result<usize> {
+        self.tokens
+            .iter()
+            .position(|token| token.address == address)
+            .ok_or_else(|| anyhow!("Token {:?} is not in pool {:?}", address, self.pool_id))
+    }
+
+    // Tokens that can be swapped with each other, i.e. all but a composable pool's BPT
+    fn swap_tokens(&self) -> impl Iterator<Item = (usize, &VaultToken)> {
+        self.tokens.iter().enumerate().filter(|(index, _)| Some(*index) != self.bpt_index)
+    }
+}
+
+impl Report for PoolInfo {
+    fn text(&self) -> String {
+        let mut lines = vec![
+            "🪣  Balancer V2 Pool Info:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Pool ID: {:?}", self.pool_id),
+            format!("Pool: {:?}", self.address),
+            format!("Type: {}", self.pool_type),
+            format!("Fee: {:.4}%", self.fee.to_f64() * 100.0),
+        ];
+        if let Some(amp) = self.amplification {
+            lines.push(format!("Amplification: {}", amp.as_u128() as f64 / AMP_PRECISION as f64));
+        }
+        for (_, token) in self.swap_tokens() {
+            match token.weight {
+                Some(weight) => lines.push(format!("{}: {:.6} (weight {:.2}%)", token.symbol, token.balance, weight.to_f64() * 100.0)),
+                None => lines.push(format!("{}: {:.6}", token.symbol, token.balance)),
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(["token", "address", "balance", "weight"]);
+        for (_, token) in self.swap_tokens() {
+            table.row([
+                token.symbol.clone(),
+                format!("{:?}", token.address),
+                token.balance.to_string(),
+                token.weight.map(|weight| weight.to_string()).unwrap_or_default(),
+            ]);
+        }
+        table
+    }
+}
+
+/// A swap quoted with local pool math, next to the Vault's own `queryBatchSwap` figure when available
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultQuote {
+    pub pool_id: H256,
+    pub pool_type: VaultPoolType,
+    pub token_in: Address,
+    pub token_out: Address,
+    pub symbol_in: String,
+    pub symbol_out: String,
+    pub amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+    pub exact_out: bool,
+    // The computed side (output, or input with `exact_out`) according to `queryBatchSwap`
+    pub queried: Option<TokenAmount>,
+}
+
+impl Report for VaultQuote {
+    fn text(&self) -> String {
+        let mut lines = vec![
+            "🧮 Balancer V2 Quote:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Pool ID: {:?}", self.pool_id),
+            format!("Sell: {} {}", self.amount_in, self.symbol_in),
+            format!("Buy: {} {}", self.amount_out, self.symbol_out),
+        ];
+        if let Some(queried) = self.queried {
+            let symbol = if self.exact_out { &self.symbol_in } else { &self.symbol_out };
+            lines.push(format!("queryBatchSwap: {} {}", queried, symbol));
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        Table::key_value([
+            ("pool_id", format!("{:?}", self.pool_id)),
+            ("token_in", self.symbol_in.clone()),
+            ("token_out", self.symbol_out.clone()),
+            ("amount_in", self.amount_in.to_string()),
+            ("amount_out", self.amount_out.to_string()),
+            ("queried", self.queried.map(|amount| amount.to_string()).unwrap_or_default()),
+        ])
+    }
+}
+
+pub struct PoolBalancerV2<M> {
+    vault: BalancerVault<M>,
+    provider: Arc<M>,
+    pool_id: H256,
+    address: Address,
+    info: Option<PoolInfo>,
+}
+
+impl<M: Middleware + 'static> PoolBalancerV2<M> {
+    pub fn new(provider: Arc<M>, vault_address: Address, pool_id: H256) -> Self {
+        let vault = BalancerVault::new(vault_address, provider.clone());
+        Self {
+            vault,
+            provider,
+            pool_id,
+            // A pool ID starts with the pool's address
+            address: Address::from_slice(&pool_id.as_bytes()[..20]),
+            info: None,
+        }
+    }
+
+    /// Looks up the pool ID from the pool contract
+    pub async fn from_pool_address(provider: Arc<M>, vault_address: Address, pool_address: Address) -> Result<Self> {
+        let pool = BalancerV2Pool::new(pool_address, provider.clone());
+        let pool_id = pool.get_pool_id().call().await?;
+        Ok(Self::new(provider, vault_address, H256(pool_id)))
+    }
+
+    /// A pool from its 32-byte pool ID, or from its address or address-book name
+    pub async fn from_id_or_address(
+        provider: Arc<M>,
+        vault_address: Address,
+        addresses: &ChainAddresses,
+        pool: &str,
+    ) -> Result<Self> {
+        if pool.len() == 66 && pool.starts_with("0x") {
+            let pool_id: H256 = pool.parse().map_err(|e| anyhow!("Invalid pool ID {}: {}", pool, e))?;
+            return Ok(Self::new(provider, vault_address, pool_id));
+        }
+        Self::from_pool_address(provider, vault_address, addresses.resolve(pool)?).await
+    }
+
+    pub fn pool_id(&self) -> H256 {
+        self.pool_id
+    }
+
+    pub async fn get_pool_info(&mut self) -> Result<&PoolInfo> {
+        verify_contract(&*self.provider, self.vault.address()).await?;
+
+        let (addresses, balances, last_change_block) = self.vault.get_pool_tokens(self.pool_id.0).call().await?;
+        let pool = BalancerV2Pool::new(self.address, self.provider.clone());
+        let fee = pool.get_swap_fee_percentage().call().await?;
+
+        let weights = if_implemented(pool.get_normalized_weights().call().await)?;
+        let amplification = match weights {
+            Some(_) => None,
+            None => if_implemented(pool.get_amplification_parameter().call().await)?.map(|(value, _, _)| value),
+        };
+        let bpt_index = match amplification {
+            Some(_) => if_implemented(pool.get_bpt_index().call().await)?.map(|index| index.as_usize()),
+            None => None,
+        };
+        let pool_type = match (&weights, amplification, bpt_index) {
+            (Some(_), _, _) => VaultPoolType::Weighted,
+            (None, Some(_), Some(_)) => VaultPoolType::ComposableStable,
+            (None, Some(_), None) => VaultPoolType::Stable,
+            _ => VaultPoolType::Other,
+        };
+        let scaling_factors = if_implemented(pool.get_scaling_factors().call().await)?;
+
+        // The pool's per-token arrays are trusted only as far as their length matches the vault's
+        let per_token = |values: &[U256], index: usize, what: &str| {
+            values.get(index).copied().ok_or_else(|| {
+                anyhow!("Pool {:?} returned {} {} for {} tokens", self.address, values.len(), what, addresses.len())
+            })
+        };
+
+        let mut tokens = Vec::with_capacity(addresses.len());
+        for (index, (address, balance)) in addresses.iter().zip(&balances).enumerate() {
+            let metadata = PoolToken::fetch(self.provider.clone(), *address).await?;
+            let scaling_factor = match &scaling_factors {
+                Some(factors) => per_token(factors, index, "scaling factors")?,
+                None => one() * U256::exp10(18usize.saturating_sub(metadata.decimals as usize)),
+            };
+            let weight = match &weights {
+                Some(weights) => Some(TokenAmount::new(per_token(weights, index, "weights")?, 18)),
+                None => None,
+            };
+            tokens.push(VaultToken {
+                address: *address,
+                balance: TokenAmount::new(*balance, metadata.decimals),
+                symbol: metadata.symbol,
+                decimals: metadata.decimals,
+                weight,
+                scaling_factor,
+            });
+        }
+
+        let pool_info = PoolInfo {
+            pool_id: self.pool_id,
+            address: self.address,
+            vault: self.vault.address(),
+            pool_type,
+            fee: TokenAmount::new(fee, 18),
+            amplification,
+            bpt_index,
+            last_change_block,
+            tokens,
+        };
+
+        Ok(self.info.insert(pool_info))
+    }
+
+    pub fn get_info(&self) -> Option<&PoolInfo> {
+        self.info.as_ref()
+    }
+
+    /// Quotes a swap of `amount` (input, or output with `exact_out`) with the pool's math, applying
+    /// the swap fee and scaling factors the way the pool contracts do
+    pub fn quote(&self, token_in: Address, token_out: Address, amount: TokenAmount, exact_out: bool) -> Result<VaultQuote> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        let (index_in, index_out) = (info.token_index(token_in)?, info.token_index(token_out)?);
+        if index_in == index_out {
+            bail!("Cannot swap {:?} for itself", token_in);
+        }
+        let (amount_in, amount_out) = if exact_out {
+            (swap_given_out(info, index_in, index_out, amount.raw(), info.fee.raw())?, amount.raw())
+        } else {
+            (amount.raw(), swap_given_in(info, index_in, index_out, amount.raw(), info.fee.raw())?)
+        };
+        let (token_in, token_out) = (&info.tokens[index_in], &info.tokens[index_out]);
+        Ok(VaultQuote {
+            pool_id: self.pool_id,
+            pool_type: info.pool_type,
+            token_in: token_in.address,
+            token_out: token_out.address,
+            symbol_in: token_in.symbol.clone(),
+            symbol_out: token_out.symbol.clone(),
+            amount_in: TokenAmount::new(amount_in, token_in.decimals),
+            amount_out: TokenAmount::new(amount_out, token_out.decimals),
+            exact_out,
+            queried: None,
+        })
+    }
+
+    /// The Vault's own single-swap `queryBatchSwap`: the output for an exact input, or the input for an exact output
+    pub async fn query_batch_swap(&self, token_in: Address, token_out: Address, amount: U256, exact_out: bool) -> Result<U256> {
+        let step = BatchSwapStep {
+            pool_id: self.pool_id.0,
+            asset_in_index: U256::zero(),
+            asset_out_index: U256::one(),
+            amount,
+            user_data: Bytes::new(),
+        };
+        let funds = FundManagement {
+            sender: Address::zero(),
+            from_internal_balance: false,
+            recipient: Address::zero(),
+            to_internal_balance: false,
+        };
+        let kind = if exact_out { GIVEN_OUT } else { GIVEN_IN };
+        let deltas = self
+            .vault
+            .query_batch_swap(kind, vec![step], vec![token_in, token_out], funds)
+            .call()
+            .await?;
+        if deltas.len() < 2 {
+            bail!("queryBatchSwap returned {} deltas for 2 assets", deltas.len());
+        }
+        // Positive deltas go into the Vault, negative ones come out
+        let delta = if exact_out { deltas[0] } else { -deltas[1] };
+        if delta.is_negative() {
+            bail!("queryBatchSwap returned an unexpected delta {}", delta);
+        }
+        Ok(delta.into_raw())
+    }
+}
+
+// A pool getter that reverts isn't part of this pool type; any other failure still fails the load
+fn if_implemented<T, M: Middleware + 'static>(result: std::result::Result<T, ContractError<M>>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.is_revert() => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// BaseGeneralPool/BaseMinimalSwapInfoPool.onSwap for GIVEN_IN: fee off the input, upscale, swap, round down
+fn swap_given_in(info: &PoolInfo, index_in: usize, index_out: usize, amount_in: U256, fee: U256) -> Result<U256> {
+    let (scale_in, scale_out) = (info.tokens[index_in].scaling_factor, info.tokens[index_out].scaling_factor);
+    let amount_in = amount_in - mul_up(amount_in, fee)?;
+    let amount_in = mul_down(amount_in, scale_in)?;
+    let amount_out = match info.pool_type {
+        VaultPoolType::Weighted => {
+            let (token_in, token_out) = (&info.tokens[index_in], &info.tokens[index_out]);
+            weighted_out_given_in(
+                mul_down(token_in.balance.raw(), scale_in)?,
+                token_in.weight.map(|weight| weight.raw()).unwrap_or_default(),
+                mul_down(token_out.balance.raw(), scale_out)?,
+                token_out.weight.map(|weight| weight.raw()).unwrap_or_default(),
+                amount_in,
+            )?
+        }
+        VaultPoolType::Stable | VaultPoolType::ComposableStable => {
+            let (balances, index_in, index_out) = stable_balances(info, index_in, index_out)?;
+            stable_out_given_in(amplification(info)?, &balances, index_in, index_out, amount_in)?
+        }
+        VaultPoolType::Other => bail!("No local math for this pool type, use queryBatchSwap"),
+    };
+    div_down(amount_out, scale_out)
+}
+
+// onSwap for GIVEN_OUT: upscale, swap, round up, then gross the input up by the fee
+fn swap_given_out(info: &PoolInfo, index_in: usize, index_out: usize, amount_out: U256, fee: U256) -> Result<U256> {
+    let (scale_in, scale_out) = (info.tokens[index_in].scaling_factor, info.tokens[index_out].scaling_factor);
+    let amount_out = mul_down(amount_out, scale_out)?;
+    let amount_in = match info.pool_type {
+        VaultPoolType::Weighted => {
+            let (token_in, token_out) = (&info.tokens[index_in], &info.tokens[index_out]);
+            weighted_in_given_out(
+                mul_down(token_in.balance.raw(), scale_in)?,
+                token_in.weight.map(|weight| weight.raw()).unwrap_or_default(),
+                mul_down(token_out.balance.raw(), scale_out)?,
+                token_out.weight.map(|weight| weight.raw()).unwrap_or_default(),
+                amount_out,
+            )?
+        }
+        VaultPoolType::Stable | VaultPoolType::ComposableStable => {
+            let (balances, index_in, index_out) = stable_balances(info, index_in, index_out)?;
+            stable_in_given_out(amplification(info)?, &balances, index_in, index_out, amount_out)?
+        }
+        VaultPoolType::Other => bail!("No local math for this pool type, use queryBatchSwap"),
+    };
+    div_up(div_up(amount_in, scale_in)?, complement(fee))
+}
+
+fn amplification(info: &PoolInfo) -> Result<U256> {
+    info.amplification.ok_or_else(|| anyhow!("Pool {:?} has no amplification parameter", info.pool_id))
+}
+
+// Upscaled balances without the BPT, with the swap indices shifted to match
+fn stable_balances(info: &PoolInfo, index_in: usize, index_out: usize) -> Result<(Vec<U256>, usize, usize)> {
+    if Some(index_in) == info.bpt_index || Some(index_out) == info.bpt_index {
+        bail!("Joining or exiting through the BPT is not supported, use queryBatchSwap");
+    }
+    let shift = |index: usize| match info.bpt_index {
+        Some(bpt) if index > bpt => index - 1,
+        _ => index,
+    };
+    let balances = info
+        .swap_tokens()
+        .map(|(_, token)| mul_down(token.balance.raw(), token.scaling_factor))
+        .collect::<Result<Vec<_>>>()?;
+    Ok((balances, shift(index_in), shift(index_out)))
+}
+
+#[async_trait::async_trait]
+impl<M: Middleware + 'static> Pool for PoolBalancerV2<M> {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn venue(&self) -> &'static str {
+        "balancer-v2"
+    }
+
+    fn tokens(&self) -> Result<Vec<PoolToken>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(info
+            .swap_tokens()
+            .map(|(_, token)| PoolToken { address: token.address, symbol: token.symbol.clone(), decimals: token.decimals })
+            .collect())
+    }
+
+    fn fee(&self) -> Result<f64> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(info.fee.to_f64())
+    }
+
+    fn reserves(&self) -> Result<Vec<TokenAmount>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(info.swap_tokens().map(|(_, token)| token.balance).collect())
+    }
+
+    async fn spot_price(&self, base: Address, quote: Address) -> Result<f64> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        let (index_base, index_quote) = (info.token_index(base)?, info.token_index(quote)?);
+        let (base, quote) = (&info.tokens[index_base], &info.tokens[index_quote]);
+        if let (Some(weight_base), Some(weight_quote)) = (base.weight, quote.weight) {
+            return Ok((quote.balance.to_f64() / weight_quote.to_f64()) / (base.balance.to_f64() / weight_base.to_f64()));
+        }
+        // Stable pools have no closed form, so swap a marginal amount
+        let amount = marginal_amount(base.balance.raw());
+        let out = swap_given_in(info, index_base, index_quote, amount, U256::zero())?;
+        Ok(TokenAmount::new(out, quote.decimals).to_f64() / TokenAmount::new(amount, base.decimals).to_f64())
+    }
+
+    async fn quote_exact_in(&self, token_in: Address, token_out: Address, amount_in: TokenAmount) -> Result<TokenAmount> {
+        Ok(self.quote(token_in, token_out, amount_in, false)?.amount_out)
+    }
+
+    async fn refresh(&mut self) -> Result<()> {
+        self.get_pool_info().await?;
+        Ok(())
+    }
+
+    // The Vault logs every swap with the pool's ID and the exact amounts its balances moved by
+    fn apply_log(&mut self, log: &Log) -> Result<bool> {
+        let info = self.info.as_mut().ok_or_else(|| not_loaded(self.address))?;
+        let Some(swap) = decode_event::<SwapFilter>(log) else {
+            return Ok(false);
+        };
+        if H256(swap.pool_id) != self.pool_id {
+            return Ok(false);
+        }
+        for (address, amount, into_pool) in [(swap.token_in, swap.amount_in, true), (swap.token_out, swap.amount_out, false)] {
+            let index = info.token_index(address)?;
+            let token = &mut info.tokens[index];
+            let amount = TokenAmount::new(amount, token.decimals);
+            token.balance = match into_pool {
+                true => token.balance.checked_add(&amount),
+                false => token.balance.checked_sub(&amount),
+            }
+            .ok_or_else(|| anyhow!("{} balance out of range after event; pool state is stale", token.symbol))?;
+        }
+        Ok(true)
+    }
+
+    fn snapshot(&self) -> PoolSnapshot {
+        PoolSnapshot::new(self.info.clone())
+    }
+
+    fn restore(&mut self, snapshot: PoolSnapshot) -> Result<()> {
+        self.info = snapshot.take()?;
+        Ok(())
+    }
+
+    // The Vault pulls the input from the executor under its approval; a zero limit leaves the
+    // output check to the executor
+    fn swap_calls(&self, executor: Address, token_in: Address, token_out: Address, amount_in: U256, _amount_out: U256) -> Result<Vec<RouteCall>> {
+        self.token_index(token_in)?;
+        self.token_index(token_out)?;
+        let swap = SwapCall {
+            single_swap: SingleSwap {
+                pool_id: self.pool_id.0,
+                kind: GIVEN_IN,
+                asset_in: token_in,
+                asset_out: token_out,
+                amount: amount_in,
+                user_data: Bytes::new(),
+            },
+            funds: FundManagement { sender: executor, from_internal_balance: false, recipient: executor, to_internal_balance: false },
+            limit: U256::zero(),
+            deadline: U256::MAX,
+        };
+        Ok(vec![RouteCall::approve(token_in, self.vault.address(), amount_in), RouteCall::new(self.vault.address(), swap)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(symbol: &str, decimals: u8, balance: u64, weight: Option<u64>) -> VaultToken {
+        VaultToken {
+            address: Address::from_low_u64_be(balance),
+            symbol: symbol.to_string(),
+            decimals,
+            balance: TokenAmount::new(U256::from(balance) * U256::exp10(decimals as usize), decimals),
+            weight: weight.map(|percent| TokenAmount::new(U256::from(percent) * U256::exp10(16), 18)),
+            scaling_factor: one() * U256::exp10(18 - decimals as usize),
+        }
+    }
+
+    #[test]
+    fn test_swaps_apply_fee_and_scaling() {
+        // 50/50 WETH/USDC with a 0.3% fee: 1 WETH into 1000 WETH / 2,000,000 USDC
+        let mut info = PoolInfo {
+            pool_id: H256::zero(),
+            address: Address::zero(),
+            vault: Address::zero(),
+            pool_type: VaultPoolType::Weighted,
+            fee: TokenAmount::new(U256::from(3) * U256::exp10(15), 18),
+            amplification: None,
+            bpt_index: None,
+            last_change_block: U256::zero(),
+            tokens: vec![token("WETH", 18, 1_000, Some(50)), token("USDC", 6, 2_000_000, Some(50))],
+        };
+        let amount_in = U256::exp10(18);
+        let out = swap_given_in(&info, 0, 1, amount_in, info.fee.raw()).unwrap();
+        let expected = 2_000_000.0 * 0.997 / 1_000.997;
+        assert!((out.as_u128() as f64 / 1e6 - expected).abs() < 1e-6);
+        let back = swap_given_out(&info, 0, 1, out, info.fee.raw()).unwrap();
+        // The output was rounded down to whole USDC units, worth about 5e8 wei of WETH each
+        assert!(back.abs_diff(amount_in) < U256::exp10(9));
+
+        // As a composable stable pool with its BPT in the middle, the BPT is skipped
+        info.pool_type = VaultPoolType::ComposableStable;
+        info.amplification = Some(U256::from(200 * AMP_PRECISION));
+        info.tokens = vec![token("DAI", 18, 1_000_000, None), token("BPT", 18, 5_000_000, None), token("USDC", 6, 1_000_000, None)];
+        info.bpt_index = Some(1);
+        let out = swap_given_in(&info, 0, 2, U256::exp10(21), info.fee.raw()).unwrap();
+        assert!(out > U256::from(996_000_000u64) && out < U256::from(997_000_000u64));
+        assert!(swap_given_in(&info, 0, 1, U256::exp10(21), info.fee.raw()).is_err());
+    }
+    #[test]
+    fn test_only_reverts_mean_not_implemented() {
+        type Error = ContractError<Provider<MockProvider>>;
+        assert_eq!(if_implemented::<U256, _>(Err(Error::Revert(Bytes::new()))).unwrap(), None);
+        let timeout = Error::ProviderError { e: ProviderError::CustomError("request timed out".to_string()) };
+        assert!(if_implemented::<U256, _>(Err(timeout)).is_err());
+    }
+}
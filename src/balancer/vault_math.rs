@@ -0,0 +1,409 @@
+// Port of the Balancer V2 math libraries (LogExpMath, FixedPoint, WeightedMath, StableMath);
+// errors carry the contract's error name
+use ethers::types::{I256, U256};
+use anyhow::{anyhow, bail, Result};
+
+// StableMath amplification parameters carry 3 decimals
+pub const AMP_PRECISION: u64 = 1_000;
+// Weighted pools reject trades above 30% of a balance
+const MAX_IN_RATIO: u64 = 300_000_000_000_000_000;
+const MAX_OUT_RATIO: u64 = 300_000_000_000_000_000;
+// powUp/powDown widen LogExpMath's result by this many 18-decimal units per one
+const MAX_POW_RELATIVE_ERROR: u64 = 10_000;
+
+pub fn one() -> U256 {
+    U256::exp10(18)
+}
+
+fn int(value: &str) -> I256 {
+    I256::from_dec_str(value).expect("valid constant")
+}
+
+fn one_18() -> I256 {
+    I256::exp10(18)
+}
+
+fn one_20() -> I256 {
+    I256::exp10(20)
+}
+
+fn one_36() -> I256 {
+    I256::exp10(36)
+}
+
+// x_n = 2^(7-n) and a_n = e^(x_n); x0, x1 and a0, a1 have 18 and 0 decimals, the rest 20 decimals
+fn exp_table() -> [(I256, I256); 12] {
+    [
+        (int("128000000000000000000"), int("38877084059945950922200000000000000000000000000000000000")),
+        (int("64000000000000000000"), int("6235149080811616882910000000")),
+        (int("3200000000000000000000"), int("7896296018268069516100000000000000")),
+        (int("1600000000000000000000"), int("888611052050787263676000000")),
+        (int("800000000000000000000"), int("298095798704172827474000")),
+        (int("400000000000000000000"), int("5459815003314423907810")),
+        (int("200000000000000000000"), int("738905609893065022723")),
+        (int("100000000000000000000"), int("271828182845904523536")),
+        (int("50000000000000000000"), int("164872127070012814685")),
+        (int("25000000000000000000"), int("128402541668774148407")),
+        (int("12500000000000000000"), int("113314845306682631683")),
+        (int("6250000000000000000"), int("106449445891785942956")),
+    ]
+}
+
+/// LogExpMath.pow: `x^y` for 18-decimal fixed point values, through `exp(y * ln(x))`
+pub fn pow(x: U256, y: U256) -> Result<U256> {
+    if y.is_zero() {
+        return Ok(one());
+    }
+    if x.is_zero() {
+        return Ok(U256::zero());
+    }
+    if x.bit(255) {
+        bail!("X_OUT_OF_BOUNDS");
+    }
+    // 2^254 / 1e20
+    let mild_exponent_bound = (U256::one() << 254) / U256::exp10(20);
+    if y >= mild_exponent_bound {
+        bail!("Y_OUT_OF_BOUNDS");
+    }
+    let (x, y) = (I256::from_raw(x), I256::from_raw(y));
+
+    let ln_36_lower_bound = one_18() - I256::exp10(17);
+    let ln_36_upper_bound = one_18() + I256::exp10(17);
+    let mut logx_times_y = if ln_36_lower_bound < x && x < ln_36_upper_bound {
+        let ln_36_x = ln_36(x);
+        (ln_36_x / one_18()) * y + ((ln_36_x % one_18()) * y) / one_18()
+    } else {
+        ln(x) * y
+    };
+    logx_times_y /= one_18();
+
+    if logx_times_y < min_natural_exponent() || logx_times_y > max_natural_exponent() {
+        bail!("PRODUCT_OUT_OF_BOUNDS");
+    }
+    Ok(exp(logx_times_y)?.into_raw())
+}
+
+fn max_natural_exponent() -> I256 {
+    I256::from(130) * one_18()
+}
+
+fn min_natural_exponent() -> I256 {
+    I256::from(-41) * one_18()
+}
+
+/// LogExpMath.exp: `e^x` for an 18-decimal x in [-41, 130]
+pub fn exp(x: I256) -> Result<I256> {
+    if x < min_natural_exponent() || x > max_natural_exponent() {
+        bail!("INVALID_EXPONENT");
+    }
+    if x.is_negative() {
+        return Ok((one_18() * one_18()) / exp(-x)?);
+    }
+
+    let table = exp_table();
+    let mut x = x;
+    let first_an = if x >= table[0].0 {
+        x -= table[0].0;
+        table[0].1
+    } else if x >= table[1].0 {
+        x -= table[1].0;
+        table[1].1
+    } else {
+        I256::one()
+    };
+
+    // Continue with 20 decimals
+    x *= I256::from(100);
+    let mut product = one_20();
+    for (x_n, a_n) in &table[2..10] {
+        if x >= *x_n {
+            x -= *x_n;
+            product = (product * *a_n) / one_20();
+        }
+    }
+
+    // Taylor series for the remaining x < 2^-3, 12 terms
+    let mut series_sum = one_20();
+    let mut term = x;
+    series_sum += term;
+    for i in 2..=12 {
+        term = ((term * x) / one_20()) / I256::from(i);
+        series_sum += term;
+    }
+
+    Ok((((product * series_sum) / one_20()) * first_an) / I256::from(100))
+}
+
+// Natural log of an 18-decimal a, with 18 decimals
+fn ln(a: I256) -> I256 {
+    if a < one_18() {
+        return -ln((one_18() * one_18()) / a);
+    }
+
+    let table = exp_table();
+    let mut a = a;
+    let mut sum = I256::zero();
+    for (x_n, a_n) in &table[0..2] {
+        if a >= *a_n * one_18() {
+            a /= *a_n;
+            sum += *x_n;
+        }
+    }
+
+    // Continue with 20 decimals
+    sum *= I256::from(100);
+    a *= I256::from(100);
+    for (x_n, a_n) in &table[2..] {
+        if a >= *a_n {
+            a = (a * one_20()) / *a_n;
+            sum += *x_n;
+        }
+    }
+
+    // ln(a) = 2 * atanh(z) with z = (a - 1) / (a + 1)
+    let z = ((a - one_20()) * one_20()) / (a + one_20());
+    let z_squared = (z * z) / one_20();
+    let mut num = z;
+    let mut series_sum = num;
+    for i in [3, 5, 7, 9, 11] {
+        num = (num * z_squared) / one_20();
+        series_sum += num / I256::from(i);
+    }
+    series_sum *= I256::from(2);
+
+    (sum + series_sum) / I256::from(100)
+}
+
+// Natural log with 36 decimals, for x close to one where `ln` loses precision
+fn ln_36(x: I256) -> I256 {
+    let x = x * one_18();
+    let z = ((x - one_36()) * one_36()) / (x + one_36());
+    let z_squared = (z * z) / one_36();
+    let mut num = z;
+    let mut series_sum = num;
+    for i in [3, 5, 7, 9, 11, 13, 15] {
+        num = (num * z_squared) / one_36();
+        series_sum += num / I256::from(i);
+    }
+    series_sum * I256::from(2)
+}
+
+fn add(a: U256, b: U256) -> Result<U256> {
+    a.checked_add(b).ok_or_else(|| anyhow!("ADD_OVERFLOW"))
+}
+
+fn sub(a: U256, b: U256) -> Result<U256> {
+    a.checked_sub(b).ok_or_else(|| anyhow!("SUB_OVERFLOW"))
+}
+
+fn mul(a: U256, b: U256) -> Result<U256> {
+    a.checked_mul(b).ok_or_else(|| anyhow!("MUL_OVERFLOW"))
+}
+
+// Math.divUp for plain integers
+fn div_up_raw(a: U256, b: U256) -> Result<U256> {
+    if b.is_zero() {
+        bail!("ZERO_DIVISION");
+    }
+    Ok(if a.is_zero() { U256::zero() } else { (a - 1) / b + 1 })
+}
+
+pub fn mul_down(a: U256, b: U256) -> Result<U256> {
+    Ok(mul(a, b)? / one())
+}
+
+pub fn mul_up(a: U256, b: U256) -> Result<U256> {
+    let product = mul(a, b)?;
+    Ok(if product.is_zero() { U256::zero() } else { (product - 1) / one() + 1 })
+}
+
+pub fn div_down(a: U256, b: U256) -> Result<U256> {
+    if b.is_zero() {
+        bail!("ZERO_DIVISION");
+    }
+    Ok(mul(a, one())? / b)
+}
+
+pub fn div_up(a: U256, b: U256) -> Result<U256> {
+    if b.is_zero() {
+        bail!("ZERO_DIVISION");
+    }
+    if a.is_zero() {
+        return Ok(U256::zero());
+    }
+    Ok((mul(a, one())? - 1) / b + 1)
+}
+
+pub fn complement(x: U256) -> U256 {
+    if x < one() { one() - x } else { U256::zero() }
+}
+
+/// FixedPoint.powUp: `x^y` rounded up, exact for the common weight ratios 1, 2 and 4
+pub fn pow_up(x: U256, y: U256) -> Result<U256> {
+    if y == one() {
+        Ok(x)
+    } else if y == one() * 2 {
+        mul_up(x, x)
+    } else if y == one() * 4 {
+        let square = mul_up(x, x)?;
+        mul_up(square, square)
+    } else {
+        let raw = pow(x, y)?;
+        let max_error = add(mul_up(raw, U256::from(MAX_POW_RELATIVE_ERROR))?, U256::one())?;
+        add(raw, max_error)
+    }
+}
+
+/// WeightedMath._calcOutGivenIn on upscaled balances, with the fee already taken from `amount_in`
+pub fn weighted_out_given_in(
+    balance_in: U256,
+    weight_in: U256,
+    balance_out: U256,
+    weight_out: U256,
+    amount_in: U256,
+) -> Result<U256> {
+    if amount_in > mul_down(balance_in, U256::from(MAX_IN_RATIO))? {
+        bail!("MAX_IN_RATIO");
+    }
+    let base = div_up(balance_in, add(balance_in, amount_in)?)?;
+    let exponent = div_down(weight_in, weight_out)?;
+    let power = pow_up(base, exponent)?;
+    mul_down(balance_out, complement(power))
+}
+
+/// WeightedMath._calcInGivenOut on upscaled balances, before the fee is added
+pub fn weighted_in_given_out(
+    balance_in: U256,
+    weight_in: U256,
+    balance_out: U256,
+    weight_out: U256,
+    amount_out: U256,
+) -> Result<U256> {
+    if amount_out > mul_down(balance_out, U256::from(MAX_OUT_RATIO))? {
+        bail!("MAX_OUT_RATIO");
+    }
+    let base = div_up(balance_out, sub(balance_out, amount_out)?)?;
+    let exponent = div_up(weight_out, weight_in)?;
+    let power = pow_up(base, exponent)?;
+    mul_up(balance_in, sub(power, one())?)
+}
+
+/// StableMath._calculateInvariant: D for upscaled balances and an amplification with `AMP_PRECISION`
+pub fn stable_invariant(amp: U256, balances: &[U256]) -> Result<U256> {
+    let sum = balances.iter().try_fold(U256::zero(), |sum, balance| add(sum, *balance))?;
+    if sum.is_zero() {
+        return Ok(U256::zero());
+    }
+    let n = U256::from(balances.len());
+    let amp_precision = U256::from(AMP_PRECISION);
+    let amp_times_total = mul(amp, n)?;
+
+    let mut invariant = sum;
+    for _ in 0..255 {
+        let mut d_p = invariant;
+        for balance in balances {
+            d_p = mul(d_p, invariant)? / mul(*balance, n)?;
+        }
+        let prev_invariant = invariant;
+        let numerator = mul(add(mul(amp_times_total, sum)? / amp_precision, mul(d_p, n)?)?, invariant)?;
+        let denominator = add(
+            mul(sub(amp_times_total, amp_precision)?, invariant)? / amp_precision,
+            mul(n + 1, d_p)?,
+        )?;
+        invariant = numerator / denominator;
+        if invariant.abs_diff(prev_invariant) <= U256::one() {
+            return Ok(invariant);
+        }
+    }
+    bail!("STABLE_INVARIANT_DIDNT_CONVERGE")
+}
+
+// StableMath._getTokenBalanceGivenInvariantAndAllOtherBalances
+fn stable_balance_given_invariant(amp: U256, balances: &[U256], invariant: U256, index: usize) -> Result<U256> {
+    let n = U256::from(balances.len());
+    let amp_precision = U256::from(AMP_PRECISION);
+    let amp_times_total = mul(amp, n)?;
+
+    let mut sum = balances[0];
+    let mut p_d = mul(balances[0], n)?;
+    for balance in &balances[1..] {
+        p_d = mul(mul(p_d, *balance)?, n)? / invariant;
+        sum = add(sum, *balance)?;
+    }
+    sum -= balances[index];
+
+    let inv2 = mul(invariant, invariant)?;
+    let c = mul(mul(div_up_raw(inv2, mul(amp_times_total, p_d)?)?, amp_precision)?, balances[index])?;
+    let b = add(sum, mul(invariant / amp_times_total, amp_precision)?)?;
+
+    let mut token_balance = div_up_raw(add(inv2, c)?, add(invariant, b)?)?;
+    for _ in 0..255 {
+        let prev_token_balance = token_balance;
+        token_balance = div_up_raw(
+            add(mul(token_balance, token_balance)?, c)?,
+            sub(add(mul(token_balance, U256::from(2))?, b)?, invariant)?,
+        )?;
+        if token_balance.abs_diff(prev_token_balance) <= U256::one() {
+            return Ok(token_balance);
+        }
+    }
+    bail!("STABLE_GET_BALANCE_DIDNT_CONVERGE")
+}
+
+/// StableMath._calcOutGivenIn on upscaled balances, with the fee already taken from `amount_in`
+pub fn stable_out_given_in(amp: U256, balances: &[U256], index_in: usize, index_out: usize, amount_in: U256) -> Result<U256> {
+    let invariant = stable_invariant(amp, balances)?;
+    let mut balances = balances.to_vec();
+    balances[index_in] = add(balances[index_in], amount_in)?;
+    let final_balance_out = stable_balance_given_invariant(amp, &balances, invariant, index_out)?;
+    sub(sub(balances[index_out], final_balance_out)?, U256::one())
+}
+
+/// StableMath._calcInGivenOut on upscaled balances, before the fee is added
+pub fn stable_in_given_out(amp: U256, balances: &[U256], index_in: usize, index_out: usize, amount_out: U256) -> Result<U256> {
+    let invariant = stable_invariant(amp, balances)?;
+    let mut balances = balances.to_vec();
+    balances[index_out] = sub(balances[index_out], amount_out)?;
+    let final_balance_in = stable_balance_given_invariant(amp, &balances, invariant, index_in)?;
+    add(sub(final_balance_in, balances[index_in])?, U256::one())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn e18(value: f64) -> U256 {
+        U256::from((value * 1e18) as u128)
+    }
+
+    fn to_f64(value: U256) -> f64 {
+        value.as_u128() as f64 / 1e18
+    }
+
+    #[test]
+    fn test_log_exp_pow() {
+        assert!((to_f64(pow(e18(2.0), e18(0.5)).unwrap()) - 2f64.sqrt()).abs() < 1e-12);
+        assert!((to_f64(pow(e18(0.999), e18(4.0)).unwrap()) - 0.999f64.powi(4)).abs() < 1e-12);
+        assert!((to_f64(pow(e18(1000.0), e18(0.25)).unwrap()) - 1000f64.powf(0.25)).abs() < 1e-12);
+        assert_eq!(pow(e18(3.0), U256::zero()).unwrap(), one());
+    }
+
+    #[test]
+    fn test_weighted_and_stable_swaps() {
+        // 80/20 pool, exact weight ratio 4 takes the exact pow path
+        let out = weighted_out_given_in(e18(800.0), e18(0.8), e18(200.0), e18(0.2), e18(10.0)).unwrap();
+        assert!((to_f64(out) - 200.0 * (1.0 - (800.0f64 / 810.0).powi(4))).abs() < 1e-9);
+        let back = weighted_in_given_out(e18(800.0), e18(0.8), e18(200.0), e18(0.2), out).unwrap();
+        assert!(back >= e18(10.0) && to_f64(back) - 10.0 < 1e-9);
+
+        // A balanced stable pool with A = 100 trades close to 1:1
+        let amp = U256::from(100 * AMP_PRECISION);
+        let balances = [e18(1_000_000.0), e18(1_000_000.0), e18(1_000_000.0)];
+        assert_eq!(stable_invariant(amp, &balances).unwrap(), e18(3_000_000.0));
+        let out = stable_out_given_in(amp, &balances, 0, 1, e18(1_000.0)).unwrap();
+        assert!(out < e18(1_000.0) && out > e18(999.9));
+        let back = stable_in_given_out(amp, &balances, 0, 1, out).unwrap();
+        // Both directions stop Newton's method within a wei, so the round trip is off by a few hundred wei at most
+        assert!(back.abs_diff(e18(1_000.0)) < U256::from(1_000));
+    }
+}
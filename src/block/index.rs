@@ -1,19 +1,80 @@
-use ethers::providers::{Provider, Http, Middleware};
-use ethers::types::{Block, H256, BlockNumber};
+use ethers::providers::Middleware;
+use ethers::types::{Block, H256, BlockNumber, U256, U64};
+use serde::Serialize;
 use std::sync::Arc;
-use anyhow::Result;
-
-pub async fn get_latest_block(provider: Arc<Provider<Http>>) -> Result<()> {
-    let latest_block: Option<Block<H256>> = provider.get_block(BlockNumber::Latest).await?;
-    match latest_block {
-    Some(block) => {
-        println!("Block Info");
-        println!("Latest block number: {:?}", block.number.unwrap_or_default());
-        println!("Latest block hash: {:?}", block.hash.unwrap_or_default());
-        println!("Latest block transactions: {}", block.transactions.len());
-        println!("Latest block timestamp: {}", block.timestamp);
-    },
-        None => println!("No block found"),
+use anyhow::{anyhow, Result};
+use crate::output::{Report, Table};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockInfo {
+    pub number: U64,
+    pub hash: H256,
+    pub transactions: usize,
+    pub timestamp: U256,
+}
+
+impl Report for BlockInfo {
+    fn text(&self) -> String {
+        format!(
+            "Block Info\nLatest block number: {:?}\nLatest block hash: {:?}\nLatest block transactions: {}\nLatest block timestamp: {}",
+            self.number, self.hash, self.transactions, self.timestamp
+        )
+    }
+
+    fn table(&self) -> Table {
+        Table::key_value([
+            ("number", self.number.to_string()),
+            ("hash", format!("{:?}", self.hash)),
+            ("transactions", self.transactions.to_string()),
+            ("timestamp", self.timestamp.to_string()),
+        ])
+    }
+}
+
+/// A pending transaction seen on the subscription stream
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingTx {
+    pub index: usize,
+    pub hash: H256,
+}
+
+impl Report for PendingTx {
+    fn text(&self) -> String {
+        format!("New pending transaction #{}: {:?}", self.index, self.hash)
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(["index", "hash"]);
+        table.row([self.index.to_string(), format!("{:?}", self.hash)]);
+        table
+    }
+}
+
+pub async fn get_latest_block<M: Middleware + 'static>(provider: Arc<M>) -> Result<BlockInfo> {
+    let latest_block: Option<Block<H256>> = provider
+        .get_block(BlockNumber::Latest)
+        .await
+        .map_err(|e| anyhow!("Failed to fetch latest block: {}", e))?;
+    let block = latest_block.ok_or_else(|| anyhow!("No block found"))?;
+    Ok(BlockInfo {
+        number: block.number.unwrap_or_default(),
+        hash: block.hash.unwrap_or_default(),
+        transactions: block.transactions.len(),
+        timestamp: block.timestamp,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::Provider;
+
+    #[tokio::test]
+    async fn test_get_latest_block_with_mock_provider() {
+        let (provider, mock) = Provider::mocked();
+        let block = Block::<H256> { number: Some(42u64.into()), ..Default::default() };
+        mock.push(block).unwrap();
+        let info = get_latest_block(Arc::new(provider)).await.unwrap();
+        assert_eq!(info.number, U64::from(42));
     }
-    Ok(())
 }
@@ -1,7 +1,9 @@
 use ethers::prelude::*;
 use std::sync::Arc;
 use anyhow::Result;
-use crate::contracts::ETH_USD_PRICE_FEED;
+use serde::Serialize;
+use crate::amount::TokenAmount;
+use crate::output::{Report, Table};
 
 // AggregatorV3Interface ABI for price feeds
 abigen!(
@@ -15,35 +17,68 @@ abigen!(
     ]"#
 );
 
-pub struct PriceFeed {
-    contract: AggregatorV3Interface<Provider<Http>>,
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceReport {
+    pub feed: Address,
+    pub description: String,
+    pub price: TokenAmount,
+    pub updated_at: u64,
 }
 
-impl PriceFeed {
-    pub fn new(provider: Arc<Provider<Http>>) -> Self {
-        let address: Address = ETH_USD_PRICE_FEED.parse().expect("Invalid address");
+impl Report for PriceReport {
+    fn text(&self) -> String {
+        format!(
+            "Chainlink {} Price Feed:\nLatest Price: {:.2}\nLast Updated: {}",
+            self.description, self.price, self.updated_at
+        )
+    }
+
+    fn table(&self) -> Table {
+        Table::key_value([
+            ("feed", format!("{:?}", self.feed)),
+            ("description", self.description.clone()),
+            ("price", self.price.to_string()),
+            ("updated_at", self.updated_at.to_string()),
+        ])
+    }
+}
+
+pub struct PriceFeed<M> {
+    contract: AggregatorV3Interface<M>,
+}
+
+impl<M: Middleware + 'static> PriceFeed<M> {
+    pub fn new(provider: Arc<M>, address: Address) -> Self {
         let contract = AggregatorV3Interface::new(address, provider);
         Self { contract }
     }
 
-    pub async fn get_latest_price(&self) -> Result<f64> {
+    pub async fn get_latest_price(&self) -> Result<TokenAmount> {
         let (_, price, _, _, _) = self.contract.latest_round_data().call().await?;
         let decimals = self.contract.decimals().call().await?;
         
-        // Convert price to float, accounting for decimals
-        let price_float = price.as_u128() as f64 / 10f64.powi(decimals as i32);
-        
-        Ok(price_float)
+        // Keep the answer exact, accounting for decimals
+        TokenAmount::from_signed(price, decimals)
     }
 
-    pub async fn get_price_with_timestamp(&self) -> Result<(f64, u64)> {
+    pub async fn get_price_with_timestamp(&self) -> Result<(TokenAmount, u64)> {
         let (_, price, _, timestamp, _) = self.contract.latest_round_data().call().await?;
         let decimals = self.contract.decimals().call().await?;
         
-        // Convert price to float, accounting for decimals
-        let price_float = price.as_u128() as f64 / 10f64.powi(decimals as i32);
+        // Keep the answer exact, accounting for decimals
+        let price = TokenAmount::from_signed(price, decimals)?;
         
-        Ok((price_float, timestamp.as_u64()))
+        Ok((price, timestamp.as_u64()))
+    }
+
+    pub async fn get_report(&self) -> Result<PriceReport> {
+        let (price, updated_at) = self.get_price_with_timestamp().await?;
+        Ok(PriceReport {
+            feed: self.contract.address(),
+            description: self.get_description().await?,
+            price,
+            updated_at,
+        })
     }
 
     pub async fn get_description(&self) -> Result<String> {
@@ -1,19 +1,154 @@
-use crate::constants::{RPC_URL_ETHEREUM, RPC_URL_SEPOLIA, WS_URL_ETHEREUM, WS_URL_SEPOLIA};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use crate::pool::PoolConfig;
 
+// Default location of the chain configuration, relative to the working directory
+pub const DEFAULT_CONFIG_PATH: &str = "chains.toml";
+// Default directory for on-disk caches (token metadata, ...), overridable with ETH_RUST_CACHE_DIR
+pub const DEFAULT_CACHE_DIR: &str = ".cache";
+
+pub fn cache_dir() -> String {
+    std::env::var("ETH_RUST_CACHE_DIR").unwrap_or_else(|_| DEFAULT_CACHE_DIR.to_string())
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Chain {
-    pub rpc_url: &'static str,
-    pub ws_url: &'static str,
+    #[serde(skip)]
+    pub name: String,
     pub chain_id: u64,
+    pub rpc_url: String,
+    pub ws_url: Option<String>,
+    pub explorer_url: Option<String>,
+    #[serde(default = "default_native_symbol")]
+    pub native_symbol: String,
+    // Symbolic contract name -> address, e.g. WETH = "0xC02a..."
+    #[serde(default)]
+    pub contracts: BTreeMap<String, String>,
+    // Uniswap token-list JSON files loaded into the token registry
+    #[serde(default)]
+    pub token_lists: Vec<String>,
+    // Pools the multi-hop arbitrage search runs over
+    #[serde(default)]
+    pub pools: Vec<PoolConfig>,
+}
+
+fn default_native_symbol() -> String {
+    "ETH".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChainConfig {
+    pub default: Option<String>,
+    #[serde(default)]
+    pub chains: BTreeMap<String, Chain>,
+}
+
+impl Chain {
+    /// Applies `ETH_RUST_<NAME>_RPC_URL`, `_WS_URL` and `_EXPLORER_URL` overrides from the environment
+    fn apply_env_overrides(&mut self) {
+        let prefix = format!("ETH_RUST_{}", self.name.to_uppercase().replace('-', "_"));
+        if let Ok(url) = std::env::var(format!("{}_RPC_URL", prefix)) {
+            self.rpc_url = url;
+        }
+        if let Ok(url) = std::env::var(format!("{}_WS_URL", prefix)) {
+            self.ws_url = Some(url);
+        }
+        if let Ok(url) = std::env::var(format!("{}_EXPLORER_URL", prefix)) {
+            self.explorer_url = Some(url);
+        }
+    }
+
+    /// Returns the WebSocket endpoint or an error if none is configured
+    pub fn ws_url(&self) -> Result<&str> {
+        self.ws_url
+            .as_deref()
+            .ok_or_else(|| anyhow!("No WebSocket URL configured for chain '{}'", self.name))
+    }
+}
+
+impl ChainConfig {
+    /// Loads a chain configuration from a `.toml` or `.json` file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read chain config {}", path.display()))?;
+
+        let mut config: ChainConfig = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&content)
+                .with_context(|| format!("Invalid JSON in {}", path.display()))?,
+            _ => toml::from_str(&content)
+                .with_context(|| format!("Invalid TOML in {}", path.display()))?,
+        };
+
+        for (name, chain) in config.chains.iter_mut() {
+            chain.name = name.clone();
+            chain.apply_env_overrides();
+        }
+        config.check_chain_ids()?;
+
+        Ok(config)
+    }
+
+    /// Fails if two chains share a chain id, since addresses and caches are keyed by it
+    pub fn check_chain_ids(&self) -> Result<()> {
+        let mut seen = BTreeMap::new();
+        for (name, chain) in &self.chains {
+            if let Some(first) = seen.insert(chain.chain_id, name) {
+                bail!("Chains '{}' and '{}' both have chain id {}", first, name, chain.chain_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Selects a chain by name, falling back to `ETH_RUST_CHAIN` and then the `default` entry
+    pub fn select(&self, name: Option<&str>) -> Result<Chain> {
+        self.select_with(name, std::env::var("ETH_RUST_CHAIN").ok().as_deref())
+    }
+
+    // `select` with the environment's chain passed in
+    fn select_with(&self, name: Option<&str>, env_chain: Option<&str>) -> Result<Chain> {
+        let name = name
+            .or(env_chain)
+            .or(self.default.as_deref())
+            .ok_or_else(|| anyhow!("No chain selected; pass --chain <name> or set `default` in the config"))?;
+
+        self.chains.get(name).cloned().ok_or_else(|| {
+            let known: Vec<&str> = self.chains.keys().map(String::as_str).collect();
+            anyhow!("Unknown chain '{}'. Configured chains: {}", name, known.join(", "))
+        })
+    }
 }
 
-pub const CHAIN_ETHEREUM: Chain = Chain {
-    rpc_url: RPC_URL_ETHEREUM,
-    ws_url: WS_URL_ETHEREUM,
-    chain_id: 1,
-};
-
-pub const CHAIN_SEPOLIA: Chain = Chain {
-    rpc_url: RPC_URL_SEPOLIA,
-    ws_url: WS_URL_SEPOLIA,
-    chain_id: 11155111,
-};
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_chain() {
+        let mut config: ChainConfig = toml::from_str(
+            r#"
+            default = "sepolia"
+
+            [chains.sepolia]
+            chain_id = 11155111
+            rpc_url = "http://localhost:8545"
+
+            [chains.sepolia.contracts]
+            WETH = "0xfFf9976782d46CC05630D1f6eBAb18b2324d6B14"
+            "#,
+        )
+        .unwrap();
+        config.chains.get_mut("sepolia").unwrap().name = "sepolia".to_string();
+
+        let chain = config.select_with(None, None).unwrap();
+        assert_eq!(chain.chain_id, 11155111);
+        assert_eq!(chain.native_symbol, "ETH");
+        assert!(chain.contracts.contains_key("WETH"));
+        assert!(config.select_with(Some("ethereum"), None).is_err());
+        // The environment's chain comes before the default, and an explicit name before both
+        assert!(config.select_with(None, Some("ethereum")).is_err());
+        assert!(config.select_with(Some("sepolia"), Some("ethereum")).is_ok());
+    }
+}
@@ -0,0 +1,475 @@
+use clap::{Args, Parser, Subcommand};
+use ethers::types::{Address, U256};
+use std::path::PathBuf;
+use crate::account::accounts::DEFAULT_GAP_LIMIT;
+use crate::account::wallet::DEFAULT_DERIVATION_PATH;
+use crate::amount::TokenAmount;
+use crate::chains::DEFAULT_CONFIG_PATH;
+use crate::contracts::parse_checksummed;
+use crate::executor::contract::DEFAULT_BYTECODE_PATH;
+use crate::mirror::DEFAULT_CHECK_INTERVAL;
+use crate::output::OutputFormat;
+use crate::uniswap::liquidity::DEFAULT_DEPTH_PERCENTAGES;
+
+#[derive(Debug, Parser)]
+#[command(name = "eth-rust", version, about = "Query and trade on Ethereum from the command line")]
+pub struct Cli {
+    /// Chain to use, as named in the config file
+    #[arg(long, global = true, env = "ETH_RUST_CHAIN")]
+    pub chain: Option<String>,
+
+    /// Chain configuration file (.toml or .json)
+    #[arg(long, global = true, env = "ETH_RUST_CONFIG", default_value = DEFAULT_CONFIG_PATH)]
+    pub config: PathBuf,
+
+    /// Output format
+    #[arg(long, short, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    /// Keystore (name in the keystore dir, or path) to sign with
+    #[arg(long, global = true, env = "ETH_RUST_KEYSTORE")]
+    pub keystore: Option<String>,
+
+    /// BIP-44 derivation path used with ETH_RUST_MNEMONIC
+    #[arg(long, global = true, default_value = DEFAULT_DERIVATION_PATH)]
+    pub derivation_path: String,
+
+    /// HD account (label or index from `accounts list`) to sign with, derived from ETH_RUST_MNEMONIC
+    #[arg(long, global = true, env = "ETH_RUST_ACCOUNT", conflicts_with = "keystore")]
+    pub account: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Show the latest block
+    Block,
+    /// Show ETH and ERC-20 balances of one or more wallets
+    Balances {
+        /// Wallet addresses or account labels/indices (defaults to all derived accounts)
+        addresses: Vec<String>,
+        /// Comma-separated token symbols or addresses (defaults to popular tokens)
+        #[arg(long, value_delimiter = ',')]
+        tokens: Vec<String>,
+    },
+    /// Inspect a liquidity pool
+    Pool {
+        #[command(subcommand)]
+        venue: PoolCommand,
+    },
+    /// Quote a swap by simulating the pool offline
+    Quote {
+        #[command(subcommand)]
+        venue: QuoteCommand,
+    },
+    /// Read a Chainlink price feed, e.g. `feed ETH/USD`
+    Feed {
+        /// Feed pair (looked up as <BASE>_<QUOTE>_PRICE_FEED) or feed address
+        pair: String,
+    },
+    /// Transfer ETH or ERC-20 tokens from the configured wallet
+    Transfer {
+        #[command(subcommand)]
+        kind: TransferCommand,
+    },
+    /// Manage encrypted keystores
+    Wallet {
+        #[command(subcommand)]
+        action: WalletCommand,
+    },
+    /// Manage accounts derived from one mnemonic
+    Accounts {
+        #[command(subcommand)]
+        action: AccountsCommand,
+    },
+    /// Compare a Uniswap and a Balancer pool for an arbitrage opportunity
+    Arb {
+        /// Uniswap V3 pool address or address-book name
+        #[arg(long, default_value = crate::contracts::UNISWAP_WETH_USDC)]
+        uniswap: String,
+        /// Balancer pool address or address-book name
+        #[arg(long, default_value = crate::contracts::BALANCER_BCOW_50WETH_50USDC)]
+        balancer: String,
+        /// Smallest profit after gas worth reporting, in the Uniswap pool's token0
+        #[arg(long, default_value_t = 0.0)]
+        min_profit: f64,
+        /// Priority fee in gwei, instead of the node's suggestion
+        #[arg(long = "priority-fee-gwei", value_parser = parse_gwei)]
+        priority_fee: Option<U256>,
+    },
+    /// Search the chain's configured pools for profitable multi-hop cycles
+    ArbSearch {
+        #[command(flatten)]
+        search: SearchArgs,
+        /// Opportunities to show
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Mirror the configured pools block by block from their events, re-evaluating cycles whenever one changes
+    Monitor {
+        #[command(flatten)]
+        search: SearchArgs,
+        /// Also append each block's report as a JSON line to this file
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Stop after this many blocks
+        #[arg(long)]
+        blocks: Option<usize>,
+        /// Blocks between reloading every pool to check the mirrored state; 0 turns checks off
+        #[arg(long, default_value_t = DEFAULT_CHECK_INTERVAL)]
+        check_interval: u64,
+    },
+    /// Deploy and drive the arbitrage executor contract
+    Executor {
+        #[command(subcommand)]
+        action: ExecutorCommand,
+    },
+    /// Stream pending transaction hashes over WebSocket
+    Subscribe {
+        /// Stop after this many transactions
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PoolCommand {
+    /// Uniswap V3 pool
+    Uniswap {
+        /// Pool address or address-book name
+        pool: String,
+    },
+    /// Uniswap V2 or Sushiswap pair, by address or found through the factory with --tokens
+    UniswapV2 {
+        /// Pair address or address-book name
+        #[arg(required_unless_present = "tokens")]
+        pool: Option<String>,
+        /// Two tokens (symbols or addresses) to look the pair up by, e.g. WETH,USDC
+        #[arg(long, value_delimiter = ',', conflicts_with = "pool")]
+        tokens: Vec<String>,
+        /// Factory address or address-book name, e.g. SUSHISWAP_FACTORY
+        #[arg(long, default_value = crate::contracts::UNISWAP_V2_FACTORY)]
+        factory: String,
+        /// Swap fee in basis points
+        #[arg(long, default_value_t = crate::uniswap::v2::pool::DEFAULT_FEE_BPS)]
+        fee_bps: u32,
+    },
+    /// Balancer pool
+    Balancer {
+        /// Pool address or address-book name
+        pool: String,
+    },
+    /// Balancer V2 pool behind the Vault
+    BalancerV2 {
+        /// Pool ID, or pool address or address-book name
+        pool: String,
+        /// Vault address or address-book name
+        #[arg(long, default_value = crate::contracts::BALANCER_VAULT)]
+        vault: String,
+    },
+    /// Curve StableSwap pool
+    Curve {
+        /// Pool address or address-book name
+        pool: String,
+    },
+    /// Curve CryptoSwap (v2) pool, e.g. tricrypto
+    CurveCrypto {
+        /// Pool address or address-book name
+        pool: String,
+    },
+    /// Uniswap V3 liquidity per tick range and depth around spot
+    Liquidity {
+        /// Pool address or address-book name
+        pool: String,
+        /// Comma-separated price moves in percent to report depth for
+        #[arg(long, value_delimiter = ',', default_values_t = DEFAULT_DEPTH_PERCENTAGES)]
+        depth: Vec<f64>,
+        /// Tick bitmap words to load on each side of the current price
+        #[arg(long, default_value_t = 8)]
+        words: i16,
+        /// Also write the distribution to a file: .csv for the ranges, anything else for JSON
+        #[arg(long)]
+        export: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum QuoteCommand {
+    /// Uniswap V3 pool, simulated tick by tick
+    Uniswap {
+        /// Pool address or address-book name
+        pool: String,
+        /// Token to sell (symbol or address)
+        token_in: String,
+        /// Amount to sell, or to buy with --exact-out, in whole tokens
+        amount: String,
+        /// Treat the amount as the exact output to buy
+        #[arg(long)]
+        exact_out: bool,
+        /// Tick bitmap words to load on each side of the current price (defaults to the full range)
+        #[arg(long)]
+        words: Option<i16>,
+    },
+    /// Balancer weighted pool, with the pool's fixed-point math
+    Balancer {
+        /// Pool address or address-book name
+        pool: String,
+        /// Token to sell (symbol or address)
+        token_in: String,
+        /// Token to buy (symbol or address)
+        token_out: String,
+        /// Amount to sell, or to buy with --exact-out, in whole tokens
+        amount: String,
+        /// Treat the amount as the exact output to buy
+        #[arg(long)]
+        exact_out: bool,
+    },
+    /// Balancer V2 pool, with local pool math checked against the Vault's queryBatchSwap
+    BalancerV2 {
+        /// Pool ID, or pool address or address-book name
+        pool: String,
+        /// Token to sell (symbol or address)
+        token_in: String,
+        /// Token to buy (symbol or address)
+        token_out: String,
+        /// Amount to sell, or to buy with --exact-out, in whole tokens
+        amount: String,
+        /// Treat the amount as the exact output to buy
+        #[arg(long)]
+        exact_out: bool,
+        /// Vault address or address-book name
+        #[arg(long, default_value = crate::contracts::BALANCER_VAULT)]
+        vault: String,
+    },
+    /// Uniswap V2 or Sushiswap pair, with the pair's exact integer math
+    UniswapV2 {
+        /// Pair address or address-book name
+        pool: String,
+        /// Token to sell (symbol or address)
+        token_in: String,
+        /// Amount to sell, or to buy with --exact-out, in whole tokens
+        amount: String,
+        /// Treat the amount as the exact output to buy
+        #[arg(long)]
+        exact_out: bool,
+        /// Swap fee in basis points
+        #[arg(long, default_value_t = crate::uniswap::v2::pool::DEFAULT_FEE_BPS)]
+        fee_bps: u32,
+    },
+    /// Curve StableSwap pool, with the invariant math checked against the pool's get_dy
+    Curve {
+        /// Pool address or address-book name
+        pool: String,
+        /// Token to sell (symbol or address)
+        token_in: String,
+        /// Token to buy (symbol or address)
+        token_out: String,
+        /// Amount to sell, in whole tokens
+        amount: String,
+    },
+    /// Curve CryptoSwap (v2) pool, with the invariant math checked against the pool's get_dy
+    CurveCrypto {
+        /// Pool address or address-book name
+        pool: String,
+        /// Token to sell (symbol or address)
+        token_in: String,
+        /// Token to buy (symbol or address)
+        token_out: String,
+        /// Amount to sell, in whole tokens
+        amount: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TransferCommand {
+    /// Send ETH
+    Eth {
+        /// Recipient address
+        #[arg(value_parser = parse_address)]
+        to: Address,
+        /// Amount in ETH, e.g. 0.001
+        amount: String,
+        /// Broadcast the transfer; without it only gas is estimated
+        #[arg(long)]
+        send: bool,
+    },
+    /// Send ERC-20 tokens, or spend an allowance with --from
+    Token {
+        /// Token symbol or address
+        token: String,
+        /// Recipient address
+        #[arg(value_parser = parse_address)]
+        to: Address,
+        /// Amount in whole tokens, e.g. 10.5
+        amount: String,
+        /// Owner to transfer from using `transferFrom` (requires an allowance)
+        #[arg(long, value_parser = parse_address)]
+        from: Option<Address>,
+        /// Broadcast the transfer; without it only gas is estimated
+        #[arg(long)]
+        send: bool,
+    },
+    /// Approve a spender for ERC-20 tokens
+    Approve {
+        /// Token symbol or address
+        token: String,
+        /// Spender address
+        #[arg(value_parser = parse_address)]
+        spender: Address,
+        /// Amount in whole tokens
+        amount: String,
+        /// Broadcast the approval; without it only gas is estimated
+        #[arg(long)]
+        send: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ExecutorCommand {
+    /// Deploy the executor, owned by the wallet
+    Deploy {
+        /// Creation bytecode from solc
+        #[arg(long, default_value = DEFAULT_BYTECODE_PATH)]
+        bytecode: PathBuf,
+    },
+    /// Execute the trade `arb` finds between a Uniswap and a Balancer pool
+    Arb {
+        #[command(flatten)]
+        execution: ExecutionArgs,
+        /// Uniswap V3 pool address or address-book name
+        #[arg(long, default_value = crate::contracts::UNISWAP_WETH_USDC)]
+        uniswap: String,
+        /// Balancer pool address or address-book name
+        #[arg(long, default_value = crate::contracts::BALANCER_BCOW_50WETH_50USDC)]
+        balancer: String,
+        /// Smallest profit after gas worth executing, in the Uniswap pool's token0
+        #[arg(long, default_value_t = 0.0)]
+        min_profit: f64,
+        /// Priority fee in gwei, instead of the node's suggestion
+        #[arg(long = "priority-fee-gwei", value_parser = parse_gwei)]
+        priority_fee: Option<U256>,
+    },
+    /// Execute the best cycle `arb-search` finds in the chain's configured pools
+    Search {
+        #[command(flatten)]
+        execution: ExecutionArgs,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Withdraw tokens held by the executor
+    Withdraw {
+        /// Executor address
+        #[arg(long, env = "ETH_RUST_EXECUTOR", value_parser = parse_address)]
+        executor: Address,
+        /// Token symbol or address
+        token: String,
+        /// Amount in whole tokens, e.g. 10.5
+        amount: String,
+        /// Recipient (defaults to the wallet)
+        #[arg(long, value_parser = parse_address)]
+        to: Option<Address>,
+    },
+}
+
+/// Options shared by the executor commands that trade
+#[derive(Debug, Args)]
+pub struct ExecutionArgs {
+    /// Executor address
+    #[arg(long, env = "ETH_RUST_EXECUTOR", value_parser = parse_address)]
+    pub executor: Address,
+    /// Shortfall from the quoted output still accepted, in basis points; the trade never accepts a loss
+    #[arg(long, default_value_t = 30)]
+    pub slippage_bps: u32,
+    /// Only simulate the trade, don't send it
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum WalletCommand {
+    /// Generate a new key and store it encrypted
+    Create {
+        /// Keystore name (defaults to the address)
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Encrypt an existing private key, or a mnemonic with --mnemonic
+    Import {
+        /// Keystore name (defaults to the address)
+        #[arg(long)]
+        name: Option<String>,
+        /// Import from a BIP-39 mnemonic at --derivation-path
+        #[arg(long)]
+        mnemonic: bool,
+    },
+    /// List keystores in the keystore dir
+    List,
+    /// Decrypt a keystore and print its private key
+    Export {
+        /// Keystore name or path
+        name: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AccountsCommand {
+    /// Derive accounts 0..count from ETH_RUST_MNEMONIC (or a prompt)
+    Derive {
+        /// Number of accounts
+        #[arg(long, default_value_t = 5)]
+        count: u32,
+    },
+    /// List derived accounts
+    List,
+    /// Give an account a label usable with --account and `balances`
+    Label {
+        /// Current label or index
+        account: String,
+        /// New label
+        label: String,
+    },
+    /// Discover used accounts and show their balances, stopping after --gap empty ones
+    Scan {
+        /// Consecutive empty accounts that end the scan
+        #[arg(long, default_value_t = DEFAULT_GAP_LIMIT)]
+        gap: u32,
+        /// Comma-separated token symbols or addresses (defaults to popular tokens)
+        #[arg(long, value_delimiter = ',')]
+        tokens: Vec<String>,
+    },
+}
+
+impl Command {
+    /// Whether the command signs transactions and therefore needs a wallet
+    pub fn needs_wallet(&self) -> bool {
+        matches!(self, Command::Transfer { .. } | Command::Executor { .. })
+    }
+}
+
+/// Cycle search options shared by `arb-search` and `monitor`
+#[derive(Debug, Args)]
+pub struct SearchArgs {
+    /// Tokens cycles start and end at (symbols or addresses); profits are ranked in the first
+    #[arg(long, value_delimiter = ',', default_values_t = ["USDC".to_string(), "WETH".to_string()])]
+    pub tokens: Vec<String>,
+    /// Most swaps in a cycle
+    #[arg(long, default_value_t = 3)]
+    pub max_hops: usize,
+    /// Smallest profit after gas worth reporting, in the first of --tokens
+    #[arg(long, default_value_t = 0.0)]
+    pub min_profit: f64,
+    /// Priority fee in gwei, instead of the node's suggestion
+    #[arg(long = "priority-fee-gwei", value_parser = parse_gwei)]
+    pub priority_fee: Option<U256>,
+}
+
+// Gwei amount such as "1.5", as wei
+fn parse_gwei(value: &str) -> Result<U256, String> {
+    TokenAmount::parse(value, 9).map(|amount| amount.raw()).map_err(|e| e.to_string())
+}
+
+fn parse_address(value: &str) -> Result<Address, String> {
+    parse_checksummed(value).map_err(|e| e.to_string())
+}
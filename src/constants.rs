@@ -1,7 +0,0 @@
-pub const RPC_URL_ETHEREUM: &str = "https://mainnet.infura.io/v3/ead2ef3f85cc4585bae41820e252b7e2";
-pub const RPC_URL_SEPOLIA: &str = "https://sepolia.infura.io/v3/ead2ef3f85cc4585bae41820e252b7e2";
-pub const WS_URL_ETHEREUM: &str = "wss://mainnet.infura.io/ws/v3/ead2ef3f85cc4585bae41820e252b7e2";
-pub const WS_URL_SEPOLIA: &str = "wss://sepolia.infura.io/ws/v3/ead2ef3f85cc4585bae41820e252b7e2";
-// pub const CHAIN_ID: u64 = 1;
-pub const ACCOUNT_PRIVATE_KEY: &str = "42d7ba7778669d318904dd37b28c487c3e176cef0a87618f2e018a7f79a214c8";
-pub const RECIPIENT_ADDRESS: &str = "0xDC4830c68c7f009ce96A444ea316632dd4B7F9dc"; 
\ No newline at end of file
@@ -1,18 +1,161 @@
-// USDT contract address on Ethereum mainnet
-pub const USDT_CONTRACT: &str = "0xdAC17F958D2ee523a2206206994597C13D831ec7";
-pub const WETH_CONTRACT: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
-pub const USDC_CONTRACT: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
-pub const USDL_CONTRACT: &str = "0x7f850b0ab1988dd17b69ac564c1e2857949e4dee";
-pub const WBTC_CONTRACT: &str = "0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599";
-pub const DAI_CONTRACT: &str = "0x6B175474E89094C44Da98b954EedeAC495271d0F";
-pub const LINK_CONTRACT: &str = "0x514910771AF9Ca656af840dff83E8264EcF986CA";
-pub const CSUSDL_CONTRACT: &str = "0xbeefc011e94f43b8b7b455ebab290c7ab4e216f1";
+use anyhow::{anyhow, bail, Result};
+use ethers::types::Address;
+use ethers::utils::to_checksum;
+use std::collections::BTreeMap;
+use crate::chains::ChainConfig;
+
+// Symbolic contract names used as keys in the `[chains.<name>.contracts]` tables
+// Tokens
+pub const USDT: &str = "USDT";
+pub const WETH: &str = "WETH";
+pub const USDC: &str = "USDC";
+pub const USDL: &str = "USDL";
+pub const WBTC: &str = "WBTC";
+pub const DAI: &str = "DAI";
+pub const LINK: &str = "LINK";
+pub const CSUSDL: &str = "CSUSDL";
 // Uniswap V3
-pub const UNISWAP_ETHEREUM_WETH_USDC: &str = "0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640";
-pub const UNISWAP_ETHEREUM_WBTC_USDC: &str = "0x99ac8cA7087fA4A2A1FB6357269965A2014ABc35";
-// Curve
-pub const CURVE_ETHEREUM_WETH_USDC: &str = "";
+pub const UNISWAP_WETH_USDC: &str = "UNISWAP_WETH_USDC";
+pub const UNISWAP_WBTC_USDC: &str = "UNISWAP_WBTC_USDC";
+// Uniswap V2 and Sushiswap
+pub const UNISWAP_V2_FACTORY: &str = "UNISWAP_V2_FACTORY";
+pub const UNISWAP_V2_WETH_USDC: &str = "UNISWAP_V2_WETH_USDC";
+pub const SUSHISWAP_FACTORY: &str = "SUSHISWAP_FACTORY";
+pub const SUSHISWAP_WETH_USDC: &str = "SUSHISWAP_WETH_USDC";
 // Balancer
-pub const BALANCER_ETHEREUM_BCoW_50WETH_50USDC: &str = "0xf08d4dea369c456d26a3168ff0024b904f2d8b91";
+pub const BALANCER_BCOW_50WETH_50USDC: &str = "BALANCER_BCOW_50WETH_50USDC";
+pub const BALANCER_VAULT: &str = "BALANCER_VAULT";
+pub const BALANCER_V2_80BAL_20WETH: &str = "BALANCER_V2_80BAL_20WETH";
+// Curve
+pub const CURVE_3POOL: &str = "CURVE_3POOL";
+pub const CURVE_TRICRYPTO2: &str = "CURVE_TRICRYPTO2";
+pub const CURVE_TRICRYPTO_USDC: &str = "CURVE_TRICRYPTO_USDC";
 // Price Feeds
-pub const ETH_USD_PRICE_FEED: &str = "0x5f4eC3Df9cbd43714FE2740f5E3616155c5b8419";
\ No newline at end of file
+pub const ETH_USD_PRICE_FEED: &str = "ETH_USD_PRICE_FEED";
+// Utilities
+pub const MULTICALL3: &str = "MULTICALL3";
+
+/// Parses an address, rejecting mixed-case input whose EIP-55 checksum doesn't match
+pub fn parse_checksummed(raw: &str) -> Result<Address> {
+    let address: Address = raw
+        .parse()
+        .map_err(|e| anyhow!("Invalid address '{}': {}", raw, e))?;
+
+    let hex_part = raw.trim_start_matches("0x");
+    let is_mixed_case = hex_part.chars().any(|c| c.is_ascii_lowercase())
+        && hex_part.chars().any(|c| c.is_ascii_uppercase());
+    if is_mixed_case && to_checksum(&address, None) != raw {
+        bail!("Address '{}' has an invalid EIP-55 checksum", raw);
+    }
+
+    Ok(address)
+}
+
+#[derive(Debug, Clone)]
+pub struct ChainAddresses {
+    pub chain_name: String,
+    pub chain_id: u64,
+    // The chain's native currency, which has no contract address
+    pub native_symbol: String,
+    addresses: BTreeMap<String, Address>,
+}
+
+impl ChainAddresses {
+    /// Looks up a contract by symbolic name
+    pub fn get(&self, name: &str) -> Result<Address> {
+        self.addresses.get(name).copied().ok_or_else(|| {
+            anyhow!(
+                "Contract '{}' is not deployed on chain '{}' (chain id {})",
+                name, self.chain_name, self.chain_id
+            )
+        })
+    }
+
+    /// Accepts either a hex address or a symbolic name from the address book
+    pub fn resolve(&self, name_or_address: &str) -> Result<Address> {
+        if name_or_address.starts_with("0x") {
+            return parse_checksummed(name_or_address);
+        }
+        self.get(name_or_address)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.addresses.contains_key(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Address)> {
+        self.addresses.iter().map(|(name, address)| (name.as_str(), *address))
+    }
+}
+
+/// Validated contract addresses for every configured chain, keyed by chain id
+#[derive(Debug, Clone, Default)]
+pub struct AddressBook {
+    chains: BTreeMap<u64, ChainAddresses>,
+}
+
+impl AddressBook {
+    /// Builds the address book from the chain config, failing on the first invalid address
+    pub fn from_config(config: &ChainConfig) -> Result<Self> {
+        config.check_chain_ids()?;
+        let mut book = AddressBook::default();
+        for (chain_name, chain) in &config.chains {
+            let mut addresses = BTreeMap::new();
+            for (name, raw) in &chain.contracts {
+                let address = parse_checksummed(raw)
+                    .map_err(|e| anyhow!("chains.{}.contracts.{}: {}", chain_name, name, e))?;
+                addresses.insert(name.clone(), address);
+            }
+            let entry = ChainAddresses {
+                chain_name: chain_name.clone(),
+                chain_id: chain.chain_id,
+                native_symbol: chain.native_symbol.clone(),
+                addresses,
+            };
+            book.chains.insert(chain.chain_id, entry);
+        }
+        Ok(book)
+    }
+
+    /// Returns all addresses known for a chain id
+    pub fn chain(&self, chain_id: u64) -> Result<&ChainAddresses> {
+        self.chains
+            .get(&chain_id)
+            .ok_or_else(|| anyhow!("No contracts configured for chain id {}", chain_id))
+    }
+
+    pub fn get(&self, chain_id: u64, name: &str) -> Result<Address> {
+        self.chain(chain_id)?.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_chain_ids() {
+        let config: ChainConfig = toml::from_str(
+            r#"
+            [chains.ethereum]
+            chain_id = 1
+            rpc_url = "http://localhost:8545"
+
+            [chains.mainnet-archive]
+            chain_id = 1
+            rpc_url = "http://localhost:8546"
+            "#,
+        )
+        .unwrap();
+        assert!(config.check_chain_ids().is_err());
+        assert!(AddressBook::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_parse_checksummed() {
+        assert!(parse_checksummed("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").is_ok());
+        assert!(parse_checksummed("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2").is_ok());
+        assert!(parse_checksummed("0xc02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").is_err());
+        assert!(parse_checksummed("0x1234").is_err());
+    }
+}
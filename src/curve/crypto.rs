@@ -0,0 +1,280 @@
+use ethers::prelude::*;
+use std::sync::Arc;
+use anyhow::{bail, Result};
+use serde::Serialize;
+use crate::amount::TokenAmount;
+use crate::curve::crypto_math::CryptoSwap;
+use crate::curve::math::FEE_DENOMINATOR;
+use crate::curve::pool::CurveQuote;
+use crate::output::{Report, Table};
+use crate::pool::{marginal_amount, not_loaded, verify_contract, Pool, PoolSnapshot, PoolToken, RouteCall};
+
+abigen!(
+    CurveCryptoPool,
+    r#"[
+        function coins(uint256 i) external view returns (address)
+        function balances(uint256 i) external view returns (uint256)
+        function A() external view returns (uint256)
+        function gamma() external view returns (uint256)
+        function D() external view returns (uint256)
+        function mid_fee() external view returns (uint256)
+        function out_fee() external view returns (uint256)
+        function fee_gamma() external view returns (uint256)
+        function fee() external view returns (uint256)
+        function price_scale(uint256 k) external view returns (uint256)
+        function get_dy(uint256 i, uint256 j, uint256 dx) external view returns (uint256)
+        function exchange(uint256 i, uint256 j, uint256 dx, uint256 min_dy) external
+        event TokenExchange(address indexed buyer, uint256 sold_id, uint256 tokens_sold, uint256 bought_id, uint256 tokens_bought)
+    ]"#
+);
+
+// tricrypto-ng also logs the fee and the new packed price scale, so its exchanges have their own topic
+abigen!(
+    CurveTricryptoNg,
+    r#"[
+        event TokenExchange(address indexed buyer, uint256 sold_id, uint256 tokens_sold, uint256 bought_id, uint256 tokens_bought, uint256 fee, uint256 packed_price_scale)
+    ]"#
+);
+
+// Two-coin crypto pools keep a single price scale
+abigen!(
+    CurveCryptoPool2,
+    r#"[
+        function price_scale() external view returns (uint256)
+    ]"#
+);
+
+// Crypto pools hold 2 or 3 coins
+const MAX_COINS: usize = 3;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolInfo {
+    pub address: Address,
+    pub coins: Vec<PoolToken>,
+    pub balances: Vec<TokenAmount>,
+    // Price of coins 1.. in coin 0, 18 decimals
+    pub price_scale: Vec<U256>,
+    pub a: U256,
+    pub gamma: U256,
+    pub d: U256,
+    pub mid_fee: U256,
+    pub out_fee: U256,
+    pub fee_gamma: U256,
+    // The dynamic fee at the current balances
+    pub fee: U256,
+}
+
+impl PoolInfo {
+    pub fn coin_index(&self, address: Address) -> Option<usize> {
+        self.coins.iter().position(|coin| coin.address == address)
+    }
+
+    pub fn crypto_swap(&self) -> CryptoSwap {
+        CryptoSwap {
+            balances: self.balances.iter().map(|balance| balance.raw()).collect(),
+            precisions: self.coins.iter().map(|coin| U256::exp10(18 - coin.decimals as usize)).collect(),
+            price_scale: self.price_scale.clone(),
+            a: self.a,
+            gamma: self.gamma,
+            d: self.d,
+            mid_fee: self.mid_fee,
+            out_fee: self.out_fee,
+            fee_gamma: self.fee_gamma,
+        }
+    }
+}
+
+fn fee_percent(fee: U256) -> f64 {
+    fee.as_u128() as f64 / FEE_DENOMINATOR as f64 * 100.0
+}
+
+impl Report for PoolInfo {
+    fn text(&self) -> String {
+        let mut lines = vec![
+            "🪣  Curve Crypto Pool Info:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Pool: {:?}", self.address),
+            format!("A: {}, gamma: {}", self.a, self.gamma),
+            format!("Fee: {:.4}% (mid {:.4}%, out {:.4}%)", fee_percent(self.fee), fee_percent(self.mid_fee), fee_percent(self.out_fee)),
+        ];
+        for (k, (coin, balance)) in self.coins.iter().zip(&self.balances).enumerate() {
+            match k {
+                0 => lines.push(format!("{}: {:.6}", coin.symbol, balance)),
+                _ => lines.push(format!(
+                    "{}: {:.6} (price scale {:.4} {})",
+                    coin.symbol,
+                    balance,
+                    TokenAmount::new(self.price_scale[k - 1], 18),
+                    self.coins[0].symbol,
+                )),
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(["coin", "address", "balance", "price_scale"]);
+        for (k, (coin, balance)) in self.coins.iter().zip(&self.balances).enumerate() {
+            let price_scale = match k {
+                0 => "1".to_string(),
+                _ => TokenAmount::new(self.price_scale[k - 1], 18).to_string(),
+            };
+            table.row([coin.symbol.clone(), format!("{:?}", coin.address), balance.to_string(), price_scale]);
+        }
+        table
+    }
+}
+
+pub struct PoolCurveCrypto<M> {
+    contract: CurveCryptoPool<M>,
+    provider: Arc<M>,
+    address: Address,
+    info: Option<PoolInfo>,
+}
+
+impl<M: Middleware + 'static> PoolCurveCrypto<M> {
+    pub fn new(provider: Arc<M>, pool_address: Address) -> Self {
+        let contract = CurveCryptoPool::new(pool_address, provider.clone());
+        Self {
+            contract,
+            provider,
+            address: pool_address,
+            info: None,
+        }
+    }
+
+    pub async fn get_pool_info(&mut self) -> Result<&PoolInfo> {
+        verify_contract(&*self.provider, self.address).await?;
+
+        // `coins` reverts past the last coin
+        let mut coins = Vec::new();
+        let mut balances = Vec::new();
+        for i in 0..MAX_COINS {
+            let Ok(coin) = self.contract.coins(U256::from(i)).call().await else {
+                break;
+            };
+            let token = PoolToken::fetch(self.provider.clone(), coin).await?;
+            let balance = self.contract.balances(U256::from(i)).call().await?;
+            balances.push(TokenAmount::new(balance, token.decimals));
+            coins.push(token);
+        }
+        if coins.len() < 2 {
+            bail!("No Curve coins found at {:?}", self.address);
+        }
+
+        let price_scale = match coins.len() {
+            2 => vec![CurveCryptoPool2::new(self.address, self.provider.clone()).price_scale().call().await?],
+            n => {
+                let mut price_scale = Vec::new();
+                for k in 0..n - 1 {
+                    price_scale.push(self.contract.price_scale(U256::from(k)).call().await?);
+                }
+                price_scale
+            }
+        };
+
+        let pool_info = PoolInfo {
+            address: self.address,
+            coins,
+            balances,
+            price_scale,
+            a: self.contract.a().call().await?,
+            gamma: self.contract.gamma().call().await?,
+            d: self.contract.d().call().await?,
+            mid_fee: self.contract.mid_fee().call().await?,
+            out_fee: self.contract.out_fee().call().await?,
+            fee_gamma: self.contract.fee_gamma().call().await?,
+            fee: self.contract.fee().call().await?,
+        };
+
+        Ok(self.info.insert(pool_info))
+    }
+
+    pub fn get_info(&self) -> Option<&PoolInfo> {
+        self.info.as_ref()
+    }
+
+    /// The pool's own `get_dy` quote between two coin indices
+    pub async fn get_dy(&self, i: usize, j: usize, dx: U256) -> Result<U256> {
+        Ok(self.contract.get_dy(U256::from(i), U256::from(j), dx).call().await?)
+    }
+
+    /// Quotes selling `amount` of `token_in` offline with the CryptoSwap invariant; needs `get_pool_info` first
+    pub fn quote(&self, token_in: Address, token_out: Address, amount: TokenAmount) -> Result<CurveQuote> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        let (i, j) = (self.token_index(token_in)?, self.token_index(token_out)?);
+        let dy = info.crypto_swap().get_dy(i, j, amount.raw())?;
+        Ok(CurveQuote {
+            pool: self.address,
+            token_in,
+            token_out,
+            symbol_in: info.coins[i].symbol.clone(),
+            symbol_out: info.coins[j].symbol.clone(),
+            amount_in: TokenAmount::new(amount.raw(), info.coins[i].decimals),
+            amount_out: TokenAmount::new(dy, info.coins[j].decimals),
+            on_chain: None,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<M: Middleware + 'static> Pool for PoolCurveCrypto<M> {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn venue(&self) -> &'static str {
+        "curve-crypto"
+    }
+
+    fn tokens(&self) -> Result<Vec<PoolToken>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(info.coins.clone())
+    }
+
+    fn fee(&self) -> Result<f64> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(info.fee.low_u128() as f64 / FEE_DENOMINATOR as f64)
+    }
+
+    fn reserves(&self) -> Result<Vec<TokenAmount>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(info.balances.clone())
+    }
+
+    async fn spot_price(&self, base: Address, quote: Address) -> Result<f64> {
+        let (i, j) = (self.token_index(base)?, self.token_index(quote)?);
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        let mut pool = info.crypto_swap();
+        pool.mid_fee = U256::zero();
+        pool.out_fee = U256::zero();
+        let dx = marginal_amount(info.balances[i].raw());
+        let dy = pool.get_dy(i, j, dx)?;
+        Ok(TokenAmount::new(dy, info.coins[j].decimals).to_f64() / TokenAmount::new(dx, info.coins[i].decimals).to_f64())
+    }
+
+    async fn quote_exact_in(&self, token_in: Address, token_out: Address, amount_in: TokenAmount) -> Result<TokenAmount> {
+        Ok(self.quote(token_in, token_out, amount_in)?.amount_out)
+    }
+
+    async fn refresh(&mut self) -> Result<()> {
+        self.get_pool_info().await?;
+        Ok(())
+    }
+
+    fn snapshot(&self) -> PoolSnapshot {
+        PoolSnapshot::new(self.info.clone())
+    }
+
+    fn restore(&mut self, snapshot: PoolSnapshot) -> Result<()> {
+        self.info = snapshot.take()?;
+        Ok(())
+    }
+
+    // Coins are pulled as ERC-20s, WETH included
+    fn swap_calls(&self, _executor: Address, token_in: Address, token_out: Address, amount_in: U256, _amount_out: U256) -> Result<Vec<RouteCall>> {
+        let (i, j) = (self.token_index(token_in)?, self.token_index(token_out)?);
+        let exchange = ExchangeCall { i: U256::from(i), j: U256::from(j), dx: amount_in, min_dy: U256::zero() };
+        Ok(vec![RouteCall::approve(token_in, self.address, amount_in), RouteCall::new(self.address, exchange)])
+    }
+}
@@ -0,0 +1,211 @@
+// Port of the Curve CryptoSwap (v2) math behind tricrypto and the two-coin crypto pools:
+// `newton_y`, the dynamic fee and `get_dy`
+use ethers::types::U256;
+use anyhow::{anyhow, bail, Result};
+use crate::curve::math::{div, mul, precision, FEE_DENOMINATOR};
+
+// Crypto pools store A times N^N times this
+pub const A_MULTIPLIER: u64 = 10_000;
+
+// Newton's method gives up after this many rounds, like the contracts
+const MAX_ITERATIONS: usize = 255;
+
+/// Fee weight between `mid_fee` and `out_fee`: 1e18 for a balanced pool, falling towards 0 off balance
+pub fn reduction_coefficient(xp: &[U256], fee_gamma: U256) -> Result<U256> {
+    let n = U256::from(xp.len());
+    let s = xp.iter().fold(U256::zero(), |sum, x| sum + x);
+    if s.is_zero() {
+        return Ok(U256::zero());
+    }
+    let mut k = precision();
+    for x in xp {
+        k = div(mul(mul(k, n)?, *x)?, s)?;
+    }
+    if !fee_gamma.is_zero() {
+        k = div(mul(fee_gamma, precision())?, fee_gamma + precision() - k)?;
+    }
+    Ok(k)
+}
+
+// Each balance as a fraction of D must stay within [0.01, 100]
+fn check_frac(x: U256, d: U256) -> Result<()> {
+    let frac = div(mul(x, precision())?, d)?;
+    if frac < U256::exp10(16) || frac > U256::exp10(20) {
+        bail!("Unsafe value for y");
+    }
+    Ok(())
+}
+
+/// The balance of coin `i` that keeps the invariant at `d`, given the other scaled balances in `x`
+pub fn newton_y(ann: U256, gamma: U256, x: &[U256], d: U256, i: usize) -> Result<U256> {
+    if i >= x.len() {
+        bail!("Invalid coin index {}", i);
+    }
+    if d < U256::exp10(17) || d > U256::exp10(33) {
+        bail!("Unsafe value for D");
+    }
+    for (k, x_k) in x.iter().enumerate() {
+        if k != i {
+            check_frac(*x_k, d)?;
+        }
+    }
+
+    let one = precision();
+    let n = U256::from(x.len());
+    let mut x_sorted = x.to_vec();
+    x_sorted[i] = U256::zero();
+    x_sorted.sort_unstable_by(|a, b| b.cmp(a));
+    let others = &x_sorted[..x.len() - 1];
+
+    let convergence_limit = (x_sorted[0] / U256::exp10(14)).max(d / U256::exp10(14)).max(U256::from(100));
+    let mut y = d / n;
+    let mut s_i = U256::zero();
+    // Small balances first for y, large ones first for K0
+    for x_k in others.iter().rev() {
+        y = div(mul(y, d)?, mul(*x_k, n)?)?;
+        s_i += *x_k;
+    }
+    let mut k0_i = one;
+    for x_k in others {
+        k0_i = div(mul(mul(k0_i, *x_k)?, n)?, d)?;
+    }
+
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        let k0 = div(mul(mul(k0_i, y)?, n)?, d)?;
+        let s = s_i + y;
+
+        let g1k0 = gamma + one;
+        let g1k0 = if g1k0 > k0 { g1k0 - k0 + 1 } else { k0 - g1k0 + 1 };
+
+        // D / (A * N^N) * g1k0^2 / gamma^2
+        let mul1 = div(mul(mul(div(mul(div(mul(one, d)?, gamma)?, g1k0)?, gamma)?, g1k0)?, U256::from(A_MULTIPLIER))?, ann)?;
+        // 1 + 2 * K0 / g1k0
+        let mul2 = one + div(mul(one * 2, k0)?, g1k0)?;
+
+        let yfprime = mul(one, y)? + mul(s, mul2)? + mul1;
+        let dyfprime = mul(d, mul2)?;
+        if yfprime < dyfprime {
+            y = y_prev / 2;
+            continue;
+        }
+        let yfprime = yfprime - dyfprime;
+        let fprime = div(yfprime, y)?;
+
+        let y_minus = div(mul1, fprime)?;
+        let y_plus = div(yfprime + mul(one, d)?, fprime)? + div(mul(y_minus, one)?, k0)?;
+        let y_minus = y_minus + div(mul(one, s)?, fprime)?;
+        y = if y_plus < y_minus { y_prev / 2 } else { y_plus - y_minus };
+
+        if y.abs_diff(y_prev) < convergence_limit.max(y / U256::exp10(14)) {
+            check_frac(y, d)?;
+            return Ok(y);
+        }
+    }
+    bail!("newton_y did not converge")
+}
+
+/// A CryptoSwap pool's state; coin 0 is the numeraire that `price_scale` is quoted in
+#[derive(Debug, Clone)]
+pub struct CryptoSwap {
+    pub balances: Vec<U256>,
+    // 10^(18 - decimals) for each coin
+    pub precisions: Vec<U256>,
+    // Price of coins 1.. in coin 0, 18 decimals
+    pub price_scale: Vec<U256>,
+    // A times N^N times `A_MULTIPLIER`, as the pool returns it
+    pub a: U256,
+    pub gamma: U256,
+    pub d: U256,
+    pub mid_fee: U256,
+    pub out_fee: U256,
+    pub fee_gamma: U256,
+}
+
+impl CryptoSwap {
+    /// Balances in coin-0 value with 18 decimals
+    pub fn xp(&self, balances: &[U256]) -> Result<Vec<U256>> {
+        balances
+            .iter()
+            .zip(&self.precisions)
+            .enumerate()
+            .map(|(k, (balance, precision_k))| {
+                let x = mul(*balance, *precision_k)?;
+                match k {
+                    0 => Ok(x),
+                    _ => Ok(mul(x, self.price_scale[k - 1])? / precision()),
+                }
+            })
+            .collect()
+    }
+
+    /// The fee for scaled balances `xp`, with `FEE_DENOMINATOR` decimals
+    pub fn fee(&self, xp: &[U256]) -> Result<U256> {
+        let f = reduction_coefficient(xp, self.fee_gamma)?;
+        Ok((mul(self.mid_fee, f)? + mul(self.out_fee, precision() - f)?) / precision())
+    }
+
+    /// The fee the pool charges at its current balances
+    pub fn current_fee(&self) -> Result<U256> {
+        self.fee(&self.xp(&self.balances)?)
+    }
+
+    /// Output of coin `j` for `dx` of coin `i`, after the fee, in coin `j`'s own decimals.
+    /// Uses the stored D, which the pool only recomputes while A and gamma are ramping.
+    pub fn get_dy(&self, i: usize, j: usize, dx: U256) -> Result<U256> {
+        let n = self.balances.len();
+        if i == j || i >= n || j >= n {
+            bail!("Invalid coin indices {} and {}", i, j);
+        }
+        let mut balances = self.balances.clone();
+        balances[i] = balances[i].checked_add(dx).ok_or_else(|| anyhow!("Curve math overflow"))?;
+        let mut xp = self.xp(&balances)?;
+
+        let y = newton_y(self.a, self.gamma, &xp, self.d, j)?;
+        let mut dy = xp[j]
+            .checked_sub(y + 1)
+            .ok_or_else(|| anyhow!("Swap exceeds the pool's balance"))?;
+        xp[j] = y;
+        if j > 0 {
+            dy = div(mul(dy, precision())?, self.price_scale[j - 1])?;
+        }
+        dy /= self.precisions[j];
+        Ok(dy - mul(self.fee(&xp)?, dy)? / U256::from(FEE_DENOMINATOR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balanced_tricrypto_swap() {
+        // USDT/WBTC/WETH at 25,000 and 2,000 USDT, $10M of each, with tricrypto2's parameters
+        let pool = CryptoSwap {
+            balances: vec![
+                U256::from(10_000_000u64) * U256::exp10(6),
+                U256::from(400u64) * U256::exp10(8),
+                U256::from(5_000u64) * U256::exp10(18),
+            ],
+            precisions: vec![U256::exp10(12), U256::exp10(10), U256::one()],
+            price_scale: vec![U256::from(25_000u64) * precision(), U256::from(2_000u64) * precision()],
+            a: U256::from(1_707_629u64),
+            gamma: U256::from(11_809_167_828_997u64),
+            d: U256::from(30_000_000u64) * precision(),
+            mid_fee: U256::from(3_000_000u64),
+            out_fee: U256::from(30_000_000u64),
+            fee_gamma: U256::from(500_000_000_000_000u64),
+        };
+        // A perfectly balanced pool charges the mid fee
+        assert_eq!(pool.current_fee().unwrap(), pool.mid_fee);
+
+        // 1 WETH buys just under 2,000 USDT: 0.03% fee and a little slippage
+        let dy = pool.get_dy(2, 0, precision()).unwrap();
+        assert!(dy > U256::from(1_999_000_000u64) && dy < U256::from(1_999_400_000u64), "{}", dy);
+
+        // A trade that unbalances the pool pays a higher fee
+        let dy_large = pool.get_dy(2, 0, U256::from(1_000u64) * precision()).unwrap();
+        assert!(dy_large < dy * 1_000);
+        assert!(pool.get_dy(0, 0, dy).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+// Port of the Curve StableSwap invariant math (`get_D`, `get_y`, `get_dy`) from the pool contracts
+use ethers::types::U256;
+use anyhow::{anyhow, bail, Result};
+
+// Rates and normalised balances carry 18 decimals
+pub fn precision() -> U256 {
+    U256::exp10(18)
+}
+
+// Fees are fractions with 10 decimals
+pub const FEE_DENOMINATOR: u64 = 10_000_000_000;
+
+// Newton's method gives up after this many rounds, like the contracts
+const MAX_ITERATIONS: usize = 255;
+
+pub(crate) fn mul(a: U256, b: U256) -> Result<U256> {
+    a.checked_mul(b).ok_or_else(|| anyhow!("Curve math overflow"))
+}
+
+pub(crate) fn div(a: U256, b: U256) -> Result<U256> {
+    a.checked_div(b).ok_or_else(|| anyhow!("Curve math division by zero"))
+}
+
+/// Balances in a common 18-decimal unit: `balance * rate / 1e18`
+pub fn xp(balances: &[U256], rates: &[U256]) -> Result<Vec<U256>> {
+    balances
+        .iter()
+        .zip(rates)
+        .map(|(balance, rate)| Ok(mul(*balance, *rate)? / precision()))
+        .collect()
+}
+
+/// The invariant D for normalised balances; `amp` is A times `a_precision`
+pub fn get_d(xp: &[U256], amp: U256, a_precision: U256) -> Result<U256> {
+    let n = U256::from(xp.len());
+    let s = xp.iter().fold(U256::zero(), |sum, x| sum + x);
+    if s.is_zero() {
+        return Ok(U256::zero());
+    }
+
+    let ann = mul(amp, n)?;
+    let mut d = s;
+    for _ in 0..MAX_ITERATIONS {
+        let mut d_p = d;
+        for x in xp {
+            d_p = div(mul(d_p, d)?, mul(*x, n)?)?;
+        }
+        let d_prev = d;
+        let numerator = mul(div(mul(ann, s)?, a_precision)? + mul(d_p, n)?, d)?;
+        let denominator = div(mul(ann - a_precision, d)?, a_precision)? + mul(n + 1, d_p)?;
+        d = div(numerator, denominator)?;
+        if d.abs_diff(d_prev) <= U256::one() {
+            return Ok(d);
+        }
+    }
+    bail!("get_D did not converge")
+}
+
+/// The new balance of coin `j` that keeps D constant when coin `i` is set to `x`
+pub fn get_y(i: usize, j: usize, x: U256, xp: &[U256], amp: U256, a_precision: U256) -> Result<U256> {
+    if i == j || i >= xp.len() || j >= xp.len() {
+        bail!("Invalid coin indices {} and {}", i, j);
+    }
+    let n = U256::from(xp.len());
+    let d = get_d(xp, amp, a_precision)?;
+    let ann = mul(amp, n)?;
+
+    let mut c = d;
+    let mut s = U256::zero();
+    for (k, balance) in xp.iter().enumerate() {
+        let x_k = if k == i {
+            x
+        } else if k != j {
+            *balance
+        } else {
+            continue;
+        };
+        s += x_k;
+        c = div(mul(c, d)?, mul(x_k, n)?)?;
+    }
+    c = div(mul(mul(c, d)?, a_precision)?, mul(ann, n)?)?;
+    let b = s + div(mul(d, a_precision)?, ann)?;
+
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        let denominator = (mul(y, U256::from(2))? + b)
+            .checked_sub(d)
+            .ok_or_else(|| anyhow!("get_y diverged"))?;
+        y = div(mul(y, y)? + c, denominator)?;
+        if y.abs_diff(y_prev) <= U256::one() {
+            return Ok(y);
+        }
+    }
+    bail!("get_y did not converge")
+}
+
+/// A StableSwap pool's state: raw balances, rates that scale them to 18 decimals, A and the fee
+#[derive(Debug, Clone)]
+pub struct StableSwap {
+    pub balances: Vec<U256>,
+    pub rates: Vec<U256>,
+    // A times `a_precision`
+    pub amp: U256,
+    pub a_precision: U256,
+    pub fee: U256,
+}
+
+impl StableSwap {
+    pub fn get_d(&self) -> Result<U256> {
+        get_d(&xp(&self.balances, &self.rates)?, self.amp, self.a_precision)
+    }
+
+    /// Output of coin `j` for `dx` of coin `i`, after the fee, in coin `j`'s own decimals
+    pub fn get_dy(&self, i: usize, j: usize, dx: U256) -> Result<U256> {
+        let xp = xp(&self.balances, &self.rates)?;
+        let x = xp[i] + mul(dx, self.rates[i])? / precision();
+        let y = get_y(i, j, x, &xp, self.amp, self.a_precision)?;
+        let dy = xp[j]
+            .checked_sub(y + 1)
+            .ok_or_else(|| anyhow!("Swap exceeds the pool's balance"))?;
+        let dy = mul(dy, precision())? / self.rates[j];
+        Ok(dy - mul(self.fee, dy)? / U256::from(FEE_DENOMINATOR))
+    }
+
+    /// What the pool's `exchange` takes out of coin `j`'s balance for `dx` of coin `i`: the output
+    /// paid and the admin's share of the fee, in coin `j`'s own decimals. `exchange` charges the fee
+    /// before scaling back, so the output can be a wei off `get_dy`.
+    pub fn exchange(&self, i: usize, j: usize, dx: U256, admin_fee: U256) -> Result<(U256, U256)> {
+        let xp = xp(&self.balances, &self.rates)?;
+        let x = xp[i] + mul(dx, self.rates[i])? / precision();
+        let y = get_y(i, j, x, &xp, self.amp, self.a_precision)?;
+        let dy = xp[j]
+            .checked_sub(y + 1)
+            .ok_or_else(|| anyhow!("Swap exceeds the pool's balance"))?;
+        let dy_fee = mul(dy, self.fee)? / U256::from(FEE_DENOMINATOR);
+        let dy_admin_fee = mul(dy_fee, admin_fee)? / U256::from(FEE_DENOMINATOR);
+        Ok((
+            div(mul(dy - dy_fee, precision())?, self.rates[j])?,
+            div(mul(dy_admin_fee, precision())?, self.rates[j])?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balanced_pool_trades_near_par() {
+        // DAI/USDC/USDT, 100M each, A = 2000, 0.01% fee, as in the 3pool
+        let balances = [
+            U256::from(100_000_000u64) * U256::exp10(18),
+            U256::from(100_000_000u64) * U256::exp10(6),
+            U256::from(100_000_000u64) * U256::exp10(6),
+        ];
+        let mut pool = StableSwap {
+            balances: balances.to_vec(),
+            rates: vec![precision(), U256::exp10(30), U256::exp10(30)],
+            amp: U256::from(2000),
+            a_precision: U256::one(),
+            fee: U256::from(1_000_000),
+        };
+        assert_eq!(pool.get_d().unwrap(), U256::from(300_000_000u64) * U256::exp10(18));
+
+        // 1M DAI in: slippage well under a basis point at A = 2000, plus the 1bp fee
+        let dx = U256::from(1_000_000u64) * U256::exp10(18);
+        let dy = pool.get_dy(0, 1, dx).unwrap();
+        assert!(dy < U256::from(999_900u64) * U256::exp10(6) && dy > U256::from(999_800u64) * U256::exp10(6));
+
+        // The same pool with A expressed in hundredths quotes the same
+        pool.amp *= 100;
+        pool.a_precision = U256::from(100);
+        assert!(pool.get_dy(0, 1, dx).unwrap().abs_diff(dy) <= U256::one());
+
+        // `exchange` pays the same to within a wei and keeps half the ~100 USDC fee for the admin
+        let (paid, admin_fee) = pool.exchange(0, 1, dx, U256::from(5_000_000_000u64)).unwrap();
+        assert!(paid.abs_diff(dy) <= U256::one());
+        assert!(admin_fee > U256::from(49u64) * U256::exp10(6) && admin_fee < U256::from(51u64) * U256::exp10(6));
+    }
+}
@@ -1 +1,4 @@
-pub mod pool;
\ No newline at end of file
+pub mod pool;
+pub mod math;
+pub mod crypto;
+pub mod crypto_math;
@@ -1,6 +1,11 @@
 use ethers::prelude::*;
-use ethers::providers::{Provider, Http};
 use std::sync::Arc;
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+use crate::amount::TokenAmount;
+use crate::curve::math::{StableSwap, FEE_DENOMINATOR};
+use crate::output::{Report, Table};
+use crate::pool::{decode_event, marginal_amount, not_loaded, verify_contract, Pool, PoolSnapshot, PoolToken, RouteCall};
 
 abigen!(
     CurvePool,
@@ -8,22 +13,409 @@ abigen!(
         function coins(uint256 i) external view returns (address)
         function balances(uint256 i) external view returns (uint256)
         function A() external view returns (uint256)
+        function A_precise() external view returns (uint256)
+        function fee() external view returns (uint256)
+        function admin_fee() external view returns (uint256)
+        function stored_rates() external view
+        function get_dy(int128 i, int128 j, uint256 dx) external view returns (uint256)
+        function exchange(int128 i, int128 j, uint256 dx, uint256 min_dy) external
+        event TokenExchange(address indexed buyer, int128 sold_id, uint256 tokens_sold, int128 bought_id, uint256 tokens_bought)
     ]"#
 );
 
-pub struct PoolCurve {
-    contract: CurvePool<Provider<Http>>,
-    provider: Arc<Provider<Http>>,
+// Vyper's `stored_rates` returns a `uint256[N_COINS]`, which no single ABI declaration fits, so
+// the binding above leaves out the return and `decode_stored_rates` reads the raw words
+
+// Curve pools hold at most 8 coins
+const MAX_COINS: usize = 8;
+
+/// Topics of the stable pools' liquidity events. They change balances in ways `apply_log` can't
+/// follow, so a pool that logs one is reloaded. Older pools log a fixed-size array per coin count,
+/// stableswap-ng pools a dynamic one, and `RemoveLiquidityOne` differs between pool generations.
+pub fn liquidity_event_topics() -> Vec<H256> {
+    let mut signatures = Vec::new();
+    for array in (2..=MAX_COINS).map(|coins| format!("uint256[{}]", coins)).chain(["uint256[]".to_string()]) {
+        signatures.push(format!("AddLiquidity(address,{0},{0},uint256,uint256)", array));
+        signatures.push(format!("RemoveLiquidity(address,{0},{0},uint256)", array));
+        signatures.push(format!("RemoveLiquidityImbalance(address,{0},{0},uint256,uint256)", array));
+    }
+    signatures.extend([
+        "RemoveLiquidityOne(address,uint256,uint256)".to_string(),
+        "RemoveLiquidityOne(address,uint256,uint256,uint256)".to_string(),
+        "RemoveLiquidityOne(address,int128,uint256,uint256,uint256)".to_string(),
+    ]);
+    signatures.iter().map(|signature| H256(ethers::utils::keccak256(signature))).collect()
+}
+// Pools with `A_precise` keep A in hundredths
+const A_PRECISION: u64 = 100;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolInfo {
+    pub address: Address,
+    pub coins: Vec<PoolToken>,
+    pub balances: Vec<TokenAmount>,
+    // Rates that bring each raw balance to 18 decimals, e.g. 1e30 for a 6-decimal coin
+    pub rates: Vec<U256>,
+    // A times `a_precision`, exactly as the pool uses it
+    pub a: U256,
+    pub a_precision: U256,
+    pub fee: U256,
+    // Share of the fee kept for the admin rather than the pool's balances, 10 decimals like `fee`
+    pub admin_fee: U256,
+}
+
+impl PoolInfo {
+    pub fn coin_index(&self, address: Address) -> Option<usize> {
+        self.coins.iter().position(|coin| coin.address == address)
+    }
+
+    pub fn stable_swap(&self) -> StableSwap {
+        StableSwap {
+            balances: self.balances.iter().map(|balance| balance.raw()).collect(),
+            rates: self.rates.clone(),
+            amp: self.a,
+            a_precision: self.a_precision,
+            fee: self.fee,
+        }
+    }
+}
+
+impl Report for PoolInfo {
+    fn text(&self) -> String {
+        let mut lines = vec![
+            "🪣  Curve Pool Info:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Pool: {:?}", self.address),
+            format!("A: {}", self.a.as_u128() as f64 / self.a_precision.as_u128() as f64),
+            format!("Fee: {:.4}%", self.fee.as_u128() as f64 / FEE_DENOMINATOR as f64 * 100.0),
+        ];
+        for (coin, balance) in self.coins.iter().zip(&self.balances) {
+            lines.push(format!("{}: {:.6}", coin.symbol, balance));
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(["coin", "address", "balance", "rate"]);
+        for ((coin, balance), rate) in self.coins.iter().zip(&self.balances).zip(&self.rates) {
+            table.row([coin.symbol.clone(), format!("{:?}", coin.address), balance.to_string(), rate.to_string()]);
+        }
+        table
+    }
+}
+
+/// An offline `get_dy` next to the pool's own, when it was asked
+#[derive(Debug, Clone, Serialize)]
+pub struct CurveQuote {
+    pub pool: Address,
+    pub token_in: Address,
+    pub token_out: Address,
+    pub symbol_in: String,
+    pub symbol_out: String,
+    pub amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+    pub on_chain: Option<TokenAmount>,
+}
+
+impl Report for CurveQuote {
+    fn text(&self) -> String {
+        let mut lines = vec![
+            "🧮 Curve Quote:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Pool: {:?}", self.pool),
+            format!("Sell: {} {}", self.amount_in, self.symbol_in),
+            format!("Buy: {} {}", self.amount_out, self.symbol_out),
+        ];
+        if let Some(on_chain) = self.on_chain {
+            let check = if on_chain == self.amount_out { "✅" } else { "⚠️" };
+            lines.push(format!("Pool get_dy: {} {} {}", on_chain, self.symbol_out, check));
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        Table::key_value([
+            ("pool", format!("{:?}", self.pool)),
+            ("token_in", self.symbol_in.clone()),
+            ("token_out", self.symbol_out.clone()),
+            ("amount_in", self.amount_in.to_string()),
+            ("amount_out", self.amount_out.to_string()),
+            ("on_chain", self.on_chain.map(|amount| amount.to_string()).unwrap_or_default()),
+        ])
+    }
+}
+
+/// Rates from a `stored_rates` return: one word per coin, as a fixed-size Vyper array encodes
+fn decode_stored_rates(data: &[u8], coin_count: usize) -> Option<Vec<U256>> {
+    if data.len() != 32 * coin_count {
+        return None;
+    }
+    Some(data.chunks(32).map(U256::from_big_endian).collect())
+}
+
+pub struct PoolCurve<M> {
+    contract: CurvePool<M>,
+    provider: Arc<M>,
     address: Address,
+    info: Option<PoolInfo>,
 }
 
-impl PoolCurve {
-    pub fn new(provider: Arc<Provider<Http>>, pool_address: Address) -> Self {
+impl<M: Middleware + 'static> PoolCurve<M> {
+    pub fn new(provider: Arc<M>, pool_address: Address) -> Self {
         let contract = CurvePool::new(pool_address, provider.clone());
-        Self { 
+        Self {
             contract,
             provider,
             address: pool_address,
+            info: None,
+        }
+    }
+
+    pub async fn get_pool_info(&mut self) -> Result<&PoolInfo> {
+        verify_contract(&*self.provider, self.address).await?;
+
+        // `coins` reverts past the last coin
+        let mut coins = Vec::new();
+        let mut balances = Vec::new();
+        for i in 0..MAX_COINS {
+            let Ok(coin) = self.contract.coins(U256::from(i)).call().await else {
+                break;
+            };
+            let token = PoolToken::fetch(self.provider.clone(), coin).await?;
+            let balance = self.contract.balances(U256::from(i)).call().await?;
+            balances.push(TokenAmount::new(balance, token.decimals));
+            coins.push(token);
+        }
+        if coins.len() < 2 {
+            bail!("No Curve coins found at {:?}", self.address);
+        }
+
+        // Older pools keep A as a whole number and fixed rates from the coin decimals
+        let (a, a_precision) = match self.contract.a_precise().call().await {
+            Ok(a) => (a, U256::from(A_PRECISION)),
+            Err(_) => (self.contract.a().call().await?, U256::one()),
+        };
+        let stored_rates = self.provider.call(&self.contract.stored_rates().tx, None).await.ok();
+        let rates = match stored_rates.and_then(|data| decode_stored_rates(&data, coins.len())) {
+            Some(rates) => rates,
+            None => coins.iter().map(|coin| U256::exp10(36 - coin.decimals as usize)).collect(),
+        };
+
+        let pool_info = PoolInfo {
+            address: self.address,
+            coins,
+            balances,
+            rates,
+            a,
+            a_precision,
+            fee: self.contract.fee().call().await?,
+            admin_fee: self.contract.admin_fee().call().await?,
+        };
+
+        Ok(self.info.insert(pool_info))
+    }
+
+    pub fn get_info(&self) -> Option<&PoolInfo> {
+        self.info.as_ref()
+    }
+
+    /// The pool's own `get_dy` quote between two coin indices
+    pub async fn get_dy(&self, i: usize, j: usize, dx: U256) -> Result<U256> {
+        Ok(self.contract.get_dy(i as i128, j as i128, dx).call().await?)
+    }
+
+    /// Quotes selling `amount` of `token_in` offline with the StableSwap invariant; needs `get_pool_info` first
+    pub fn quote(&self, token_in: Address, token_out: Address, amount: TokenAmount) -> Result<CurveQuote> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        let (i, j) = (self.token_index(token_in)?, self.token_index(token_out)?);
+        let dy = info.stable_swap().get_dy(i, j, amount.raw())?;
+        Ok(CurveQuote {
+            pool: self.address,
+            token_in,
+            token_out,
+            symbol_in: info.coins[i].symbol.clone(),
+            symbol_out: info.coins[j].symbol.clone(),
+            amount_in: TokenAmount::new(amount.raw(), info.coins[i].decimals),
+            amount_out: TokenAmount::new(dy, info.coins[j].decimals),
+            on_chain: None,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<M: Middleware + 'static> Pool for PoolCurve<M> {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn venue(&self) -> &'static str {
+        "curve"
+    }
+
+    fn tokens(&self) -> Result<Vec<PoolToken>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(info.coins.clone())
+    }
+
+    fn fee(&self) -> Result<f64> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(info.fee.low_u128() as f64 / FEE_DENOMINATOR as f64)
+    }
+
+    fn reserves(&self) -> Result<Vec<TokenAmount>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(info.balances.clone())
+    }
+
+    async fn spot_price(&self, base: Address, quote: Address) -> Result<f64> {
+        let (i, j) = (self.token_index(base)?, self.token_index(quote)?);
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        let mut pool = info.stable_swap();
+        pool.fee = U256::zero();
+        let dx = marginal_amount(info.balances[i].raw());
+        let dy = pool.get_dy(i, j, dx)?;
+        Ok(TokenAmount::new(dy, info.coins[j].decimals).to_f64() / TokenAmount::new(dx, info.coins[i].decimals).to_f64())
+    }
+
+    async fn quote_exact_in(&self, token_in: Address, token_out: Address, amount_in: TokenAmount) -> Result<TokenAmount> {
+        Ok(self.quote(token_in, token_out, amount_in)?.amount_out)
+    }
+
+    async fn refresh(&mut self) -> Result<()> {
+        self.get_pool_info().await?;
+        Ok(())
+    }
+
+    // Replays the exchange with the pool's math; an output that differs from the logged one means the state is stale
+    fn apply_log(&mut self, log: &Log) -> Result<bool> {
+        let info = self.info.as_mut().ok_or_else(|| not_loaded(self.address))?;
+        let Some(exchange) = decode_event::<TokenExchangeFilter>(log) else {
+            return Ok(false);
+        };
+        let (i, j) = (exchange.sold_id as usize, exchange.bought_id as usize);
+        if i.max(j) >= info.coins.len() {
+            bail!("TokenExchange for coins {} and {} in a {}-coin pool", i, j, info.coins.len());
+        }
+        let (dy, dy_admin_fee) = info.stable_swap().exchange(i, j, exchange.tokens_sold, info.admin_fee)?;
+        if dy != exchange.tokens_bought {
+            bail!("Mirrored exchange paid {} but the pool paid {}", dy, exchange.tokens_bought);
         }
+        let (balance_i, balance_j) = (info.balances[i].raw(), info.balances[j].raw());
+        info.balances[i] = TokenAmount::new(balance_i + exchange.tokens_sold, info.coins[i].decimals);
+        info.balances[j] = TokenAmount::new(
+            balance_j.checked_sub(dy + dy_admin_fee).ok_or_else(|| anyhow!("Exchange exceeds the mirrored balance"))?,
+            info.coins[j].decimals,
+        );
+        Ok(true)
     }
-}
\ No newline at end of file
+
+    fn snapshot(&self) -> PoolSnapshot {
+        PoolSnapshot::new(self.info.clone())
+    }
+
+    fn restore(&mut self, snapshot: PoolSnapshot) -> Result<()> {
+        self.info = snapshot.take()?;
+        Ok(())
+    }
+
+    fn swap_calls(&self, _executor: Address, token_in: Address, token_out: Address, amount_in: U256, _amount_out: U256) -> Result<Vec<RouteCall>> {
+        let (i, j) = (self.token_index(token_in)?, self.token_index(token_out)?);
+        let exchange = ExchangeCall { i: i as i128, j: j as i128, dx: amount_in, min_dy: U256::zero() };
+        Ok(vec![RouteCall::approve(token_in, self.address, amount_in), RouteCall::new(self.address, exchange)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::Token;
+    use crate::curve::math::precision;
+
+    // A DAI/USDC pool with 100M of each, A = 2000 and a 0.01% fee, loaded without touching the provider
+    fn dai_usdc() -> (PoolCurve<Provider<MockProvider>>, Address, Address) {
+        let (provider, _mock) = Provider::mocked();
+        let (dai, usdc) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let mut curve = PoolCurve::new(Arc::new(provider), Address::repeat_byte(3));
+        curve.info = Some(PoolInfo {
+            address: curve.address,
+            coins: vec![
+                PoolToken { address: dai, symbol: "DAI".to_string(), decimals: 18 },
+                PoolToken { address: usdc, symbol: "USDC".to_string(), decimals: 6 },
+            ],
+            balances: vec![TokenAmount::new(U256::exp10(26), 18), TokenAmount::new(U256::exp10(14), 6)],
+            rates: vec![precision(), U256::exp10(30)],
+            a: U256::from(200_000),
+            a_precision: U256::from(A_PRECISION),
+            fee: U256::from(1_000_000),
+            admin_fee: U256::from(5_000_000_000u64),
+        });
+        (curve, dai, usdc)
+    }
+
+    #[test]
+    fn test_decode_stored_rates() {
+        // A two-coin pool's `uint256[2]` comes back as two bare words, with no offset or length
+        let rates = [precision(), U256::exp10(30)];
+        let data = ethers::abi::encode(&[Token::FixedArray(rates.map(Token::Uint).to_vec())]);
+        assert_eq!(data.len(), 64);
+        assert_eq!(decode_stored_rates(&data, 2).unwrap(), rates);
+        assert!(decode_stored_rates(&data, 3).is_none());
+        assert!(decode_stored_rates(&[], 2).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_quote_picks_coins_by_address() {
+        let (mut curve, dai, usdc) = dai_usdc();
+        let dx = U256::exp10(24);
+        let quote = curve.quote(dai, usdc, TokenAmount::new(dx, 18)).unwrap();
+        let dy = curve.get_info().unwrap().stable_swap().get_dy(0, 1, dx).unwrap();
+        assert_eq!((quote.symbol_in.as_str(), quote.symbol_out.as_str()), ("DAI", "USDC"));
+        assert_eq!(quote.amount_out, TokenAmount::new(dy, 6));
+
+        let pool: &dyn Pool = &curve;
+        assert_eq!(pool.token_index(usdc).unwrap(), 1);
+        assert_eq!(pool.quote_exact_in(dai, usdc, TokenAmount::new(dx, 18)).await.unwrap(), quote.amount_out);
+        assert!((pool.spot_price(dai, usdc).await.unwrap() - 1.0).abs() < 1e-6);
+        assert!(pool.quote_exact_in(dai, Address::repeat_byte(4), TokenAmount::new(dx, 18)).await.is_err());
+
+        curve.info = None;
+        assert!(Pool::tokens(&curve).is_err());
+    }
+
+    #[test]
+    fn test_apply_log_replays_exchanges() {
+        let (mut curve, _, _) = dai_usdc();
+        let info = curve.get_info().unwrap().clone();
+        let dx = U256::exp10(24);
+        let exchange = |sold_id: u64, bought_id: u64, tokens_bought: U256| Log {
+            topics: vec![TokenExchangeFilter::signature(), H256::repeat_byte(5)],
+            data: ethers::abi::encode(&[
+                Token::Int(sold_id.into()),
+                Token::Uint(dx),
+                Token::Int(bought_id.into()),
+                Token::Uint(tokens_bought),
+            ])
+            .into(),
+            ..Default::default()
+        };
+
+        // A logged exchange moves the balances by what it paid, less the admin's share of the fee
+        let (paid, admin_fee) = info.stable_swap().exchange(0, 1, dx, info.admin_fee).unwrap();
+        assert!(curve.apply_log(&exchange(0, 1, paid + 1)).is_err());
+        assert!(curve.apply_log(&exchange(0, 2, paid)).is_err());
+        assert!(curve.apply_log(&exchange(0, 1, paid)).unwrap());
+        let balances = Pool::reserves(&curve).unwrap();
+        assert_eq!(balances[0].raw(), info.balances[0].raw() + dx);
+        assert_eq!(balances[1].raw(), info.balances[1].raw() - paid - admin_fee);
+        assert!(!curve.apply_log(&Log::default()).unwrap());
+    }
+
+    #[test]
+    fn test_swap_calls_approve_and_exchange() {
+        let (curve, dai, usdc) = dai_usdc();
+        let calls = Pool::swap_calls(&curve, Address::zero(), usdc, dai, U256::exp10(6), U256::zero()).unwrap();
+        assert_eq!(calls[0], RouteCall::approve(usdc, curve.address, U256::exp10(6)));
+        let exchange = ExchangeCall { i: 1, j: 0, dx: U256::exp10(6), min_dy: U256::zero() };
+        assert_eq!(calls[1].data.to_vec(), ethers::abi::AbiEncode::encode(exchange));
+    }
+}
@@ -0,0 +1,374 @@
+use ethers::prelude::*;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{anyhow, bail, Result};
+use crate::amount::TokenAmount;
+use crate::arbitrage::gas::{GasBreakdown, GasModel};
+use crate::executor::route::ExecutorRoute;
+use crate::output::{Report, Table};
+
+// Bindings for contracts/ArbExecutor.sol, from the ABI solc writes next to its bytecode
+abigen!(ArbExecutor, "./contracts/out/ArbExecutor.abi");
+
+// Where contracts/build.sh puts the executor's creation bytecode
+pub const DEFAULT_BYTECODE_PATH: &str = "contracts/out/ArbExecutor.bin";
+
+/// Reads creation bytecode as solc writes it, hex with or without a 0x prefix
+pub fn load_bytecode(path: &Path) -> Result<Bytes> {
+    let hex = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {} (run contracts/build.sh first)", path.display(), e))?;
+    hex.trim().parse().map_err(|e| anyhow!("Invalid bytecode in {}: {}", path.display(), e))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecutorDeployment {
+    pub address: Address,
+    pub owner: Address,
+    pub tx_hash: H256,
+    pub gas_used: Option<U256>,
+}
+
+impl Report for ExecutorDeployment {
+    fn text(&self) -> String {
+        let gas = self.gas_used.map(|gas| format!(" ({} gas)", gas)).unwrap_or_default();
+        [
+            format!("✅ Executor deployed at {:?}{}", self.address, gas),
+            "-------------------------------------".to_string(),
+            format!("Owner: {:?}", self.owner),
+            format!("Transaction: {:?}", self.tx_hash),
+        ]
+        .join("\n")
+    }
+
+    fn table(&self) -> Table {
+        Table::key_value([
+            ("address", format!("{:?}", self.address)),
+            ("owner", format!("{:?}", self.owner)),
+            ("tx_hash", format!("{:?}", self.tx_hash)),
+            ("gas_used", self.gas_used.map(|gas| gas.to_string()).unwrap_or_else(|| "-".to_string())),
+        ])
+    }
+}
+
+/// Deploys the executor; the client's sender becomes its owner
+pub async fn deploy_executor<M: Middleware + 'static>(client: Arc<M>, bytecode: Bytes) -> Result<ExecutorDeployment> {
+    let factory = ContractFactory::new(ARBEXECUTOR_ABI.clone(), bytecode, client.clone());
+    let (contract, receipt) = factory.deploy(())?.send_with_receipt().await?;
+    let executor = ArbExecutor::new(contract.address(), client);
+    Ok(ExecutorDeployment {
+        address: executor.address(),
+        owner: executor.owner().call().await?,
+        tx_hash: receipt.transaction_hash,
+        gas_used: receipt.gas_used,
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecutionResult {
+    pub executor: Address,
+    pub route: ExecutorRoute,
+    // What the route returned when simulated against the latest block
+    pub simulated_out: TokenAmount,
+    pub gas_estimate: U256,
+    pub dry_run: bool,
+    pub tx_hash: Option<H256>,
+}
+
+impl Report for ExecutionResult {
+    fn text(&self) -> String {
+        let route = &self.route;
+        let mut lines = vec![
+            format!("⚡ Executor {:?}: {} legs ({})", self.executor, route.venues.len(), route.venues.join(" → ")),
+            "-------------------------------------".to_string(),
+            format!("In: {}", route.amount_in),
+            format!("Expected out: {}", route.expected_out),
+            format!("Minimum out: {}", route.min_amount_out),
+            format!("Simulated out: {}", self.simulated_out),
+            format!("⛽ Estimated gas: {}", self.gas_estimate),
+        ];
+        match self.tx_hash {
+            Some(tx_hash) => lines.push(format!("✅ Executed: {:?}", tx_hash)),
+            None => lines.push("🚀 Ready to execute (dry run, not sent)".to_string()),
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        Table::key_value([
+            ("executor", format!("{:?}", self.executor)),
+            ("venues", self.route.venues.join(" → ")),
+            ("amount_in", self.route.amount_in.to_string()),
+            ("expected_out", self.route.expected_out.to_string()),
+            ("min_amount_out", self.route.min_amount_out.to_string()),
+            ("simulated_out", self.simulated_out.to_string()),
+            ("gas_estimate", self.gas_estimate.to_string()),
+            ("tx_hash", self.tx_hash.map(|h| format!("{:?}", h)).unwrap_or_else(|| "-".to_string())),
+        ])
+    }
+}
+
+/// Simulates the route through the executor and, unless `dry_run`, sends it. A route that would
+/// revert fails here with the contract's reason rather than being sent.
+pub async fn execute_route<M: Middleware + 'static>(
+    executor: &ArbExecutor<M>,
+    route: ExecutorRoute,
+    dry_run: bool,
+) -> Result<ExecutionResult> {
+    let decimals = route.amount_in.decimals();
+    let call = executor.execute(route.token, route.amount_in.raw(), route.min_amount_out.raw(), route.contract_calls());
+    let simulated_out = call.call().await.map_err(|e| revert_error(e, decimals))?;
+    let gas_estimate = call.estimate_gas().await.map_err(|e| revert_error(e, decimals))?;
+
+    let tx_hash = match dry_run {
+        true => None,
+        false => {
+            let pending = call.send().await.map_err(|e| revert_error(e, decimals))?;
+            let receipt = pending.await?.ok_or_else(|| anyhow!("Execution transaction was dropped"))?;
+            if receipt.status != Some(U64::one()) {
+                bail!("Execution reverted on chain: {:?}", receipt.transaction_hash);
+            }
+            Some(receipt.transaction_hash)
+        }
+    };
+    Ok(ExecutionResult {
+        executor: executor.address(),
+        route,
+        simulated_out: TokenAmount::new(simulated_out, decimals),
+        gas_estimate,
+        dry_run,
+        tx_hash,
+    })
+}
+
+/// Gas for the route as the node estimates it through the executor, priced like `GasModel::breakdown`.
+/// Falls back to the per-venue table when the estimate fails, e.g. because the route would revert.
+pub async fn route_gas<M: Middleware + 'static>(
+    executor: &ArbExecutor<M>,
+    route: &ExecutorRoute,
+    gas: &GasModel,
+    native_price: Option<f64>,
+) -> GasBreakdown {
+    let call = executor.execute(route.token, route.amount_in.raw(), route.min_amount_out.raw(), route.contract_calls());
+    let gas_units = match call.estimate_gas().await {
+        Ok(estimate) => estimate.low_u64(),
+        Err(_) => gas.gas_units(route.venues.iter().copied()),
+    };
+    gas.priced(gas_units, native_price, route.amount_in.decimals())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WithdrawResult {
+    pub executor: Address,
+    pub token: Address,
+    pub to: Address,
+    pub amount: TokenAmount,
+    pub tx_hash: H256,
+}
+
+impl Report for WithdrawResult {
+    fn text(&self) -> String {
+        format!("✅ Withdrew {} of {:?} from {:?} to {:?}: {:?}", self.amount, self.token, self.executor, self.to, self.tx_hash)
+    }
+
+    fn table(&self) -> Table {
+        Table::key_value([
+            ("executor", format!("{:?}", self.executor)),
+            ("token", format!("{:?}", self.token)),
+            ("to", format!("{:?}", self.to)),
+            ("amount", self.amount.to_string()),
+            ("tx_hash", format!("{:?}", self.tx_hash)),
+        ])
+    }
+}
+
+/// Sends tokens held by the executor to `to`
+pub async fn withdraw<M: Middleware + 'static>(
+    executor: &ArbExecutor<M>,
+    token: Address,
+    to: Address,
+    amount: TokenAmount,
+) -> Result<WithdrawResult> {
+    let call = executor.withdraw(token, to, amount.raw());
+    let pending = call.send().await.map_err(|e| revert_error(e, amount.decimals()))?;
+    let receipt = pending.await?.ok_or_else(|| anyhow!("Withdrawal transaction was dropped"))?;
+    if receipt.status != Some(U64::one()) {
+        bail!("Withdrawal reverted on chain: {:?}", receipt.transaction_hash);
+    }
+    Ok(WithdrawResult { executor: executor.address(), token, to, amount, tx_hash: receipt.transaction_hash })
+}
+
+// The executor's own revert reasons, with amounts in the route's token
+fn revert_error<M: Middleware + 'static>(error: ContractError<M>, decimals: u8) -> anyhow::Error {
+    match error.decode_contract_revert::<ArbExecutorErrors>() {
+        Some(ArbExecutorErrors::InsufficientOutput(e)) => anyhow!(
+            "Route returns {}, below the minimum of {}",
+            TokenAmount::new(e.amount_out, decimals),
+            TokenAmount::new(e.min_amount_out, decimals),
+        ),
+        Some(ArbExecutorErrors::CallFailed(e)) => anyhow!("Leg call {} reverted: {}", e.index, e.reason),
+        Some(ArbExecutorErrors::NotOwner(_)) => anyhow!("Only the executor's owner can do that"),
+        Some(e) => anyhow!("Executor reverted: {:?}", e),
+        None => anyhow!(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::{self, Token};
+    use ethers::utils::{Anvil, AnvilInstance};
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use crate::executor::route::RouteBuilder;
+    use crate::pool::testing::ConstantProductPool;
+    use crate::pool::{Pool, PoolToken, IERC20};
+    use crate::uniswap::pool::PoolUniswap;
+    use crate::uniswap::v2::pool::PoolUniswapV2;
+
+    // Test mocks compiled next to the executor by contracts/build.sh
+    abigen!(MockERC20, "./contracts/out/MockERC20.abi");
+    abigen!(MockPair, "./contracts/out/MockPair.abi");
+
+    // Mainnet WETH, the 0.05% Uniswap V3 WETH/USDC pool and the Uniswap V2 WETH/USDC pair
+    const WETH: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
+    const UNISWAP_V3_WETH_USDC: &str = "0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640";
+    const UNISWAP_V2_WETH_USDC: &str = "0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc";
+
+    type Client = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+    // Signs with one of anvil's funded accounts, polling for receipts at the pace anvil mines
+    fn anvil_client(anvil: &AnvilInstance, account: usize) -> Arc<Client> {
+        let wallet = LocalWallet::from(anvil.keys()[account].clone()).with_chain_id(anvil.chain_id());
+        let provider = Provider::<Http>::try_from(anvil.endpoint()).unwrap().interval(Duration::from_millis(10));
+        Arc::new(SignerMiddleware::new(provider, wallet))
+    }
+
+    // The devnet tests spawn anvil, and are skipped where it isn't installed
+    fn anvil_installed() -> bool {
+        let installed = std::process::Command::new("anvil").arg("--version").output().is_ok();
+        if !installed {
+            eprintln!("anvil not found, skipping");
+        }
+        installed
+    }
+
+    fn out_path(file: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("contracts/out").join(file)
+    }
+
+    async fn deploy_mock<T: abi::Tokenize>(client: Arc<Client>, abi: abi::Abi, name: &str, args: T) -> Address {
+        let bytecode = load_bytecode(&out_path(&format!("{}.bin", name))).unwrap();
+        ContractFactory::new(abi, bytecode, client).deploy(args).unwrap().send().await.unwrap().address()
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_mock_tokens() {
+        if !anvil_installed() {
+            return;
+        }
+        let anvil = Anvil::new().spawn();
+        let client = anvil_client(&anvil, 0);
+        let bytecode = load_bytecode(&Path::new(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_BYTECODE_PATH)).unwrap();
+        let deployment = deploy_executor(client.clone(), bytecode).await.unwrap();
+        assert_eq!(deployment.owner, client.address());
+        let executor = ArbExecutor::new(deployment.address, client.clone());
+
+        // Two mock pairs standing in for pools that price WETH at 2000 and 2200 USDC
+        let usdc = deploy_mock(client.clone(), MOCKERC20_ABI.clone(), "MockERC20", ()).await;
+        let weth = deploy_mock(client.clone(), MOCKERC20_ABI.clone(), "MockERC20", ()).await;
+        let token = |address: Address, symbol: &str| PoolToken { address, symbol: symbol.to_string(), decimals: 18 };
+        let (usdc_token, weth_token) = (token(usdc, "USDC"), token(weth, "WETH"));
+        let mut cheap = ConstantProductPool::new(1, (&usdc_token, 2_000_000.0), (&weth_token, 1_000.0), 30);
+        let mut dear = ConstantProductPool::new(2, (&usdc_token, 2_200_000.0), (&weth_token, 1_000.0), 30);
+        for pool in [&mut cheap, &mut dear] {
+            pool.address = deploy_mock(client.clone(), MOCKPAIR_ABI.clone(), "MockPair", (usdc, weth)).await;
+            for token in [usdc, weth] {
+                MockERC20::new(token, client.clone()).mint(pool.address, U256::exp10(24)).send().await.unwrap().await.unwrap();
+            }
+        }
+        let usdc_contract = MockERC20::new(usdc, client.clone());
+        let amount_in = TokenAmount::parse("10000", 18).unwrap();
+        usdc_contract.mint(deployment.address, amount_in.raw()).send().await.unwrap().await.unwrap();
+
+        // Buying WETH from the cheap pair and selling it to the dear one pays
+        let mid = cheap.quote_exact_in(usdc, weth, amount_in).await.unwrap();
+        let out = dear.quote_exact_in(weth, usdc, mid).await.unwrap();
+        let route = RouteBuilder::new(deployment.address, usdc, amount_in)
+            .swap(&cheap, weth, mid)
+            .and_then(|builder| builder.swap(&dear, usdc, out))
+            .and_then(RouteBuilder::build)
+            .unwrap();
+        let result = execute_route(&executor, route.clone(), false).await.unwrap();
+        assert_eq!(result.simulated_out, out);
+        assert!(result.tx_hash.is_some());
+        assert_eq!(usdc_contract.balance_of(deployment.address).call().await.unwrap(), out.raw());
+
+        // Demanding a wei more than the route returns reverts it
+        let mut greedy = route.clone();
+        greedy.min_amount_out = TokenAmount::new(out.raw() + 1, 18);
+        let error = execute_route(&executor, greedy, true).await.unwrap_err();
+        assert!(error.to_string().contains("below the minimum"), "{}", error);
+
+        // Only the owner can execute or withdraw
+        let stranger = ArbExecutor::new(deployment.address, anvil_client(&anvil, 1));
+        let error = execute_route(&stranger, route, true).await.unwrap_err();
+        assert!(error.to_string().contains("owner"), "{}", error);
+        let error = withdraw(&stranger, usdc, client.address(), amount_in).await.unwrap_err();
+        assert!(error.to_string().contains("owner"), "{}", error);
+
+        // The swap callback pays nobody outside a route
+        let data = abi::encode(&[Token::Address(usdc)]);
+        let error = executor.uniswap_v3_swap_callback(I256::one(), I256::zero(), data.into()).call().await.unwrap_err();
+        assert!(matches!(error.decode_contract_revert::<ArbExecutorErrors>(), Some(ArbExecutorErrors::UnexpectedCallback(_))));
+
+        // The owner takes the profit out
+        let profit = TokenAmount::new(out.raw() - amount_in.raw(), 18);
+        withdraw(&executor, usdc, client.address(), profit).await.unwrap();
+        assert_eq!(usdc_contract.balance_of(client.address()).call().await.unwrap(), profit.raw());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs anvil and a mainnet RPC in ETH_RUST_FORK_URL"]
+    async fn test_execute_on_fork() {
+        let anvil = Anvil::new().fork(std::env::var("ETH_RUST_FORK_URL").unwrap()).spawn();
+        let client = anvil_client(&anvil, 0);
+
+        let bytecode = load_bytecode(&Path::new(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_BYTECODE_PATH)).unwrap();
+        let deployment = deploy_executor(client.clone(), bytecode).await.unwrap();
+        assert_eq!(deployment.owner, client.address());
+        let executor = ArbExecutor::new(deployment.address, client.clone());
+
+        // Wrap 1 ETH and fund the executor with it
+        let weth: Address = WETH.parse().unwrap();
+        let one = TokenAmount::parse("1", 18).unwrap();
+        let deposit = TransactionRequest::new().to(weth).value(one.raw());
+        client.send_transaction(deposit, None).await.unwrap().await.unwrap();
+        IERC20::new(weth, client.clone()).transfer(deployment.address, one.raw()).send().await.unwrap().await.unwrap();
+
+        // WETH → USDC on V3 and back on V2, quoted off-chain from the forked state
+        let mut v3 = PoolUniswap::new(client.clone(), UNISWAP_V3_WETH_USDC.parse().unwrap());
+        let mut v2 = PoolUniswapV2::new(client.clone(), UNISWAP_V2_WETH_USDC.parse().unwrap());
+        v3.refresh().await.unwrap();
+        v2.refresh().await.unwrap();
+        let usdc = v3.tokens().unwrap()[0].address;
+        let amount_in = TokenAmount::parse("0.1", 18).unwrap();
+        let mid = v3.quote_exact_in(weth, usdc, amount_in).await.unwrap();
+        let out = v2.quote_exact_in(usdc, weth, mid).await.unwrap();
+        let mut route = RouteBuilder::new(deployment.address, weth, amount_in)
+            .swap(&v3, usdc, mid)
+            .and_then(|builder| builder.swap(&v2, weth, out))
+            .and_then(RouteBuilder::build)
+            .unwrap();
+
+        // The round trip loses the fees, so the profit check reverts it
+        let error = execute_route(&executor, route.clone(), true).await.unwrap_err();
+        assert!(error.to_string().contains("below the minimum"), "{}", error);
+
+        // Accepting the quoted output, the simulation returns exactly that
+        route.min_amount_out = route.expected_out;
+        let result = execute_route(&executor, route, false).await.unwrap();
+        assert_eq!(result.simulated_out, out);
+        assert!(result.tx_hash.is_some());
+    }
+}
@@ -0,0 +1,190 @@
+use anyhow::{anyhow, bail, Result};
+use ethers::types::{Address, U256};
+use serde::Serialize;
+use crate::amount::TokenAmount;
+use crate::arbitrage::graph::{CycleOpportunity, TokenGraph};
+use crate::arbitrage::index::ArbitragePlan;
+use crate::executor::contract::arb_executor::Call;
+use crate::pool::{Pool, RouteCall};
+
+const BPS: u32 = 10_000;
+
+/// Everything one `execute` call needs: the token the route starts and ends in, what it spends,
+/// and the calls that make up its legs
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecutorRoute {
+    pub token: Address,
+    pub amount_in: TokenAmount,
+    // What the legs are quoted to return
+    pub expected_out: TokenAmount,
+    // The contract reverts below this
+    pub min_amount_out: TokenAmount,
+    // Venue of each leg, for gas estimates and reports
+    pub venues: Vec<&'static str>,
+    pub calls: Vec<RouteCall>,
+}
+
+impl ExecutorRoute {
+    /// The calls as the contract's `Call` structs
+    pub fn contract_calls(&self) -> Vec<Call> {
+        self.calls.iter().map(|call| Call { target: call.target, data: call.data.clone() }).collect()
+    }
+}
+
+/// Encodes a route leg by leg. Each leg spends exactly what the previous one is quoted to return,
+/// so a leg that comes up short makes a later one fail; only the final output is checked.
+#[derive(Clone)]
+pub struct RouteBuilder {
+    executor: Address,
+    amount_in: TokenAmount,
+    token: Address,
+    // Token and amount the legs so far leave the executor holding
+    holding: (Address, TokenAmount),
+    venues: Vec<&'static str>,
+    calls: Vec<RouteCall>,
+    slippage_bps: u32,
+    min_profit: TokenAmount,
+}
+
+impl RouteBuilder {
+    pub fn new(executor: Address, token: Address, amount_in: TokenAmount) -> Self {
+        Self {
+            executor,
+            amount_in,
+            token,
+            holding: (token, amount_in),
+            venues: Vec::new(),
+            calls: Vec::new(),
+            slippage_bps: 0,
+            min_profit: TokenAmount::zero(amount_in.decimals()),
+        }
+    }
+
+    /// Shortfall from the expected output the route still accepts
+    pub fn slippage_bps(mut self, slippage_bps: u32) -> Self {
+        self.slippage_bps = slippage_bps.min(BPS);
+        self
+    }
+
+    /// Smallest profit the route must make whatever the slippage allowance, in the start token
+    pub fn min_profit(mut self, min_profit: TokenAmount) -> Self {
+        self.min_profit = min_profit;
+        self
+    }
+
+    /// Swaps everything held so far on `pool` for `amount_out` of `token_out`, as quoted
+    pub fn swap(mut self, pool: &dyn Pool, token_out: Address, amount_out: TokenAmount) -> Result<Self> {
+        let (token_in, amount_in) = self.holding;
+        let calls = pool.swap_calls(self.executor, token_in, token_out, amount_in.raw(), amount_out.raw())?;
+        self.calls.extend(calls);
+        self.venues.push(pool.venue());
+        self.holding = (token_out, amount_out);
+        Ok(self)
+    }
+
+    /// Finishes the route. The minimum output is the expected output less slippage, but never
+    /// below the input plus `min_profit`, so the contract won't accept a losing trade.
+    pub fn build(self) -> Result<ExecutorRoute> {
+        let (token_out, expected_out) = self.holding;
+        if self.calls.is_empty() {
+            bail!("Route has no legs");
+        }
+        if token_out != self.token {
+            bail!("Route ends in {:?}, not {:?} where it started", token_out, self.token);
+        }
+        let after_slippage = expected_out.raw() * U256::from(BPS - self.slippage_bps) / U256::from(BPS);
+        let floor = self.amount_in.raw() + self.min_profit.raw();
+        Ok(ExecutorRoute {
+            token: self.token,
+            amount_in: self.amount_in,
+            expected_out,
+            min_amount_out: TokenAmount::new(after_slippage.max(floor), expected_out.decimals()),
+            venues: self.venues,
+            calls: self.calls,
+        })
+    }
+}
+
+/// Route for a two-pool plan from `call_arbitrage`, given the pools it was planned on
+pub fn route_for_plan(plan: &ArbitragePlan, pools: [&dyn Pool; 2], executor: Address) -> Result<RouteBuilder> {
+    let pool = |address: Address| {
+        pools
+            .into_iter()
+            .find(|pool| pool.address() == address)
+            .ok_or_else(|| anyhow!("Pool {:?} is not one the plan was made on", address))
+    };
+    RouteBuilder::new(executor, plan.token_in, plan.amount_in)
+        .swap(pool(plan.buy_pool)?, plan.token_mid, plan.amount_mid)?
+        .swap(pool(plan.sell_pool)?, plan.token_in, plan.expected_out)
+}
+
+/// Route for a cycle found by the graph search, re-quoting each hop on the graph's pools
+pub async fn route_for_cycle(graph: &TokenGraph, opportunity: &CycleOpportunity, executor: Address) -> Result<RouteBuilder> {
+    let Some(first) = opportunity.hops.first() else {
+        bail!("Opportunity has no hops");
+    };
+    let mut builder = RouteBuilder::new(executor, first.token_in, opportunity.amount_in);
+    let mut amount = opportunity.amount_in;
+    for hop in &opportunity.hops {
+        let pool = graph.pools()[hop.pool].as_ref();
+        amount = pool.quote_exact_in(hop.token_in, hop.token_out, amount).await?;
+        builder = builder.swap(pool, hop.token_out, amount)?;
+    }
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::AbiEncode;
+    use crate::arbitrage::gas::{GasModel, GasPrice};
+    use crate::pool::testing::{token, ConstantProductPool};
+    use crate::uniswap::v2::pool::SwapCall;
+
+    #[tokio::test]
+    async fn test_route_for_cycle() {
+        let (usdc, weth) = (token(1, "USDC"), token(2, "WETH"));
+        let cheap = ConstantProductPool::new(1, (&usdc, 2_000_000.0), (&weth, 1_000.0), 30);
+        let dear = ConstantProductPool::new(2, (&usdc, 2_200_000.0), (&weth, 1_000.0), 30);
+        let pools: Vec<Box<dyn Pool>> = vec![Box::new(cheap), Box::new(dear)];
+        let graph = TokenGraph::new(pools).unwrap();
+        let gas = GasModel { gas_price: GasPrice { base_fee: U256::zero(), priority_fee: U256::zero() }, native_token: weth.address, native_symbol: "ETH".to_string() };
+        let search = graph.search(&[usdc.address], 2, &gas, 0.0).await.unwrap();
+        let opportunity = &search.opportunities[0];
+
+        let executor = Address::from_low_u64_be(99);
+        let route = route_for_cycle(&graph, opportunity, executor).await.unwrap().slippage_bps(50).build().unwrap();
+        assert_eq!(route.token, usdc.address);
+        assert_eq!(route.expected_out, opportunity.amount_out);
+        assert_eq!(route.venues, ["mock", "mock"]);
+
+        // Buy WETH from the cheap pool: send it the USDC, then take token1 out to the executor
+        let mid = graph.pools()[0].quote_exact_in(usdc.address, weth.address, opportunity.amount_in).await.unwrap();
+        assert_eq!(route.calls[0], RouteCall::transfer(usdc.address, Address::from_low_u64_be(101), opportunity.amount_in.raw()));
+        let swap = SwapCall { amount_0_out: U256::zero(), amount_1_out: mid.raw(), to: executor, data: Default::default() };
+        assert_eq!(route.calls[1].data.to_vec(), swap.encode());
+        assert_eq!(route.calls[2].data.to_vec(), RouteCall::transfer(weth.address, Address::from_low_u64_be(102), mid.raw()).data.to_vec());
+        assert_eq!(route.calls.len(), 4);
+    }
+
+    #[test]
+    fn test_min_amount_out() {
+        let (usdc, weth) = (token(1, "USDC"), token(2, "WETH"));
+        let pool = ConstantProductPool::new(1, (&usdc, 2_000_000.0), (&weth, 1_000.0), 30);
+        let amount = |value: &str| TokenAmount::parse(value, 18).unwrap();
+        let route = |amount_out: &str, slippage_bps: u32| {
+            RouteBuilder::new(Address::zero(), usdc.address, amount("100"))
+                .swap(&pool, weth.address, amount("0.05"))
+                .and_then(|builder| builder.swap(&pool, usdc.address, amount(amount_out)))
+                .map(|builder| builder.slippage_bps(slippage_bps).min_profit(amount("1")))
+                .and_then(RouteBuilder::build)
+        };
+        // 1% off 110 still clears the input plus the minimum profit
+        assert_eq!(route("110", 100).unwrap().min_amount_out, amount("108.9"));
+        // Slippage never lets the minimum fall below 101
+        assert_eq!(route("102", 100).unwrap().min_amount_out, amount("101"));
+        assert_eq!(route("90", 100).unwrap().min_amount_out, amount("101"));
+        // A route has to come back to where it started
+        assert!(RouteBuilder::new(Address::zero(), usdc.address, amount("100")).swap(&pool, weth.address, amount("0.05")).unwrap().build().is_err());
+    }
+}
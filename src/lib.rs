@@ -0,0 +1,19 @@
+//! The `math`, `crypto_math` and `vault_math` modules under `uniswap`, `balancer` and `curve` port
+//! pool contract math. Integer steps and rounding follow the Solidity or Vyper source so offline quotes
+//! match the pool to the wei, and `bail!` takes the place of a revert.
+
+pub mod amount;
+pub mod block;
+pub mod cli;
+pub mod output;
+pub mod contracts;
+pub mod chains;
+pub mod account;
+pub mod chainlink;
+pub mod uniswap;
+pub mod balancer;
+pub mod curve;
+pub mod pool;
+pub mod mirror;
+pub mod arbitrage;
+pub mod executor;
@@ -1,143 +1,476 @@
-mod block;
-mod constants;
-mod contracts;
-mod chains;
-mod account;
-mod chainlink;
-mod uniswap;
-mod balancer;
-mod curve;
-mod arbitrage;
-
 use anyhow::Result;
+use clap::Parser;
 use ethers::prelude::*;
 use ethers::providers::{Provider, Http, Ws, StreamExt};
-use ethers::types::{U256};
 use ethers::providers::Middleware;
 use ethers::middleware::SignerMiddleware;
 use std::convert::TryFrom;
 use std::sync::Arc;
 
 // modules
-use constants::{ACCOUNT_PRIVATE_KEY, RECIPIENT_ADDRESS};
-use chains::{CHAIN_SEPOLIA};
-use account::token_balances::get_token_balances;
-use account::token_transfer::transfer_eth;
-use chainlink::price::PriceFeed;
-use uniswap::pool::PoolUniswap;
-use balancer::pool::PoolBalancer;
-use contracts::{UNISWAP_ETHEREUM_WETH_USDC, BALANCER_ETHEREUM_BCoW_50WETH_50USDC};
-use arbitrage::index::call_arbitrage;
-use block::index::get_latest_block;
+use eth_rust::amount::TokenAmount;
+use eth_rust::chains::{cache_dir, Chain, ChainConfig};
+use eth_rust::cli::{AccountsCommand, Cli, Command, ExecutorCommand, PoolCommand, QuoteCommand, TransferCommand, WalletCommand};
+use eth_rust::output::{render, render_line, OutputFormat};
+use eth_rust::account::token_balances::get_token_balances;
+use eth_rust::account::token_registry::TokenRegistry;
+use eth_rust::account::accounts::{accounts_path, AccountSet};
+use eth_rust::account::wallet::{create_keystore, export_keystore, import_keystore, list_keystores, read_secret, Wallet};
+use eth_rust::account::token_transfer::{approve_spender, transfer_eth, transfer_tokens, transfer_tokens_from};
+use eth_rust::chainlink::price::PriceFeed;
+use eth_rust::uniswap::pool::PoolUniswap;
+use eth_rust::uniswap::v2::pool::PoolUniswapV2;
+use eth_rust::balancer::pool::PoolBalancer;
+use eth_rust::balancer::vault::PoolBalancerV2;
+use eth_rust::curve::pool::PoolCurve;
+use eth_rust::curve::crypto::PoolCurveCrypto;
+use eth_rust::contracts::{AddressBook, ChainAddresses, BALANCER_VAULT, MULTICALL3, WETH};
+use eth_rust::arbitrage::index::{call_arbitrage, evaluate_arbitrage, load_arbitrage_pools};
+use eth_rust::arbitrage::graph::TokenGraph;
+use eth_rust::arbitrage::gas::GasModel;
+use eth_rust::arbitrage::monitor::{run_monitor, MonitorConfig, Sink};
+use eth_rust::executor::contract::{deploy_executor, execute_route, load_bytecode, route_gas, withdraw, ArbExecutor};
+use eth_rust::executor::route::{route_for_cycle, route_for_plan};
+use eth_rust::pool::load_pool;
+use eth_rust::block::index::{get_latest_block, PendingTx};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // modify this code such that we shall be giving an argument to cargo run to select the function to run
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() < 2 {
-        println!("Usage: cargo run -- <function_name>");
-        return Ok(());
-    }
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
 
     // Chain to use
-    let CHAIN = CHAIN_SEPOLIA;
-    
-    // Connect to Ethereum provider
-    let provider = Arc::new(Provider::<Http>::try_from(CHAIN.rpc_url)?);
+    let config = ChainConfig::load(&cli.config)?;
+    let chain = config.select(cli.chain.as_deref())?;
+    eprintln!("Using chain '{}' (chain id {})", chain.name, chain.chain_id);
+    let output = cli.output;
 
-    // Fetch latest block number
-    if args.contains(&"block".to_string()) {
-        get_latest_block(provider.clone()).await?;
+    // Contract addresses for the selected chain
+    let address_book = AddressBook::from_config(&config)?;
+    let addresses = address_book.chain(chain.chain_id)?;
+
+    // Token metadata: cached on disk, seeded from the chain's token lists
+    let mut registry = TokenRegistry::open(chain.chain_id, cache_dir())?;
+    for token_list in &chain.token_lists {
+        registry.load_token_list(token_list)?;
     }
 
-    // Note: Transaction subscription requires WebSocket provider, not HTTP
-    if args.contains(&"subscribe".to_string()) {
-        println!("Connecting to WebSocket for transaction subscription...");
-        
-        match Provider::<Ws>::connect(CHAIN.ws_url).await {
-            Ok(ws_provider) => {
-                let mut stream = ws_provider.subscribe_pending_txs().await?;
-                println!("Listening for pending transactions... (Press Ctrl+C to stop)");
-                
-                let mut count = 0;
-                while let Some(tx_hash) = stream.next().await {
-                    println!("New pending transaction #{}: {:?}", count + 1, tx_hash);
-                    count += 1;
-                    
-                    // Limit to first 10 transactions for demo purposes
-                    if count >= 100 {
-                        println!("Received 10 transactions, stopping subscription.");
-                        break;
+    // Connect to Ethereum provider
+    let provider = Arc::new(Provider::<Http>::try_from(chain.rpc_url.as_str())?);
+
+    // Accounts derived from the mnemonic, addressable by label or index
+    let mut accounts = AccountSet::open(accounts_path())?;
+
+    // Only commands that sign transactions load the wallet
+    let wallet = if cli.command.needs_wallet() {
+        let wallet = match &cli.account {
+            Some(account) => accounts.wallet(account)?,
+            None => Wallet::load(cli.keystore.as_deref(), &cli.derivation_path)?,
+        };
+        eprintln!("Wallet address: {}", wallet.address_str());
+        Some(wallet.signer(chain.chain_id))
+    } else {
+        None
+    };
+
+    match cli.command {
+        // Fetch latest block number
+        Command::Block => {
+            let block = get_latest_block(provider.clone()).await?;
+            println!("{}", render(&block, output)?);
+        }
+
+        // Note: Transaction subscription requires WebSocket provider, not HTTP
+        Command::Subscribe { limit } => {
+            eprintln!("Connecting to WebSocket for transaction subscription...");
+
+            match Provider::<Ws>::connect(chain.ws_url()?).await {
+                Ok(ws_provider) => {
+                    let mut stream = ws_provider.subscribe_pending_txs().await?;
+                    eprintln!("Listening for pending transactions... (Press Ctrl+C to stop)");
+
+                    // One record per line so JSON output can be consumed as JSON lines
+                    let mut count = 0;
+                    while let Some(hash) = stream.next().await {
+                        count += 1;
+                        let pending = PendingTx { index: count, hash };
+                        let line = match output {
+                            OutputFormat::Table => format!("{}  {:?}", pending.index, pending.hash),
+                            _ => render_line(&pending, output)?,
+                        };
+                        println!("{}", line);
+
+                        if count >= limit {
+                            eprintln!("Received {} transactions, stopping subscription.", count);
+                            break;
+                        }
                     }
                 }
+                Err(e) => {
+                    eprintln!("Failed to connect to WebSocket: {}", e);
+                    eprintln!("Make sure the WebSocket URL is correct and accessible.");
+                }
             }
-            Err(e) => {
-                println!("Failed to connect to WebSocket: {}", e);
-                println!("Make sure the WebSocket URL is correct and accessible.");
+        }
+
+        // Native and token balances for one or more wallets
+        Command::Balances { addresses: wallets, tokens } => {
+            let wallets = if wallets.is_empty() {
+                accounts.addresses()
+            } else {
+                wallets.iter().map(|wallet| accounts.resolve(wallet)).collect::<Result<Vec<_>>>()?
+            };
+            if wallets.is_empty() {
+                anyhow::bail!("No wallets given and no accounts derived (see `accounts derive`)");
             }
+            let symbols: Vec<&str> = tokens.iter().map(String::as_str).collect();
+            let matrix = get_token_balances(provider.clone(), &mut registry, addresses, &wallets, &symbols).await?;
+            registry.save()?;
+            println!("{}", render(&matrix, output)?);
         }
-    }
 
-    // Create wallet from private key using ethers LocalWallet with correct chain ID
-    let private_key = ACCOUNT_PRIVATE_KEY.trim_start_matches("0x");
-    let private_key_bytes = hex::decode(private_key)?;
-    let wallet = LocalWallet::from_bytes(&private_key_bytes)?.with_chain_id(CHAIN.chain_id);
-    let address = wallet.address();
-    let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet));
-    println!("\n");
-    println!("Wallet from private key:");
-    println!("Address: {:?}", address);
-
-    // List of token symbols to check
-    if args.contains(&"account_balances".to_string()) {
-        let symbols = ["ETH"];
-        let balances = get_token_balances(provider.clone(), address, &symbols).await?;
-        for (symbol, balance) in balances {
-            println!("\n");
-            println!("Wallet Balance");
-            println!("{} Balance: {}", symbol, balance);
+        // Offline swap quotes
+        Command::Quote { venue: QuoteCommand::Uniswap { pool, token_in, amount, exact_out, words } } => {
+            let mut pool = PoolUniswap::new(provider.clone(), addresses.resolve(&pool)?);
+            let info = pool.get_pool_info().await?.clone();
+            let token_in = registry.metadata(provider.clone(), registry.resolve(&token_in)?).await?;
+            // An exact-out amount is denominated in the other token
+            let decimals = match (exact_out, token_in.address == info.token_0_addr) {
+                (false, true) | (true, false) => info.token_0_decimals,
+                _ => info.token_1_decimals,
+            };
+            let amount = TokenAmount::parse(&amount, decimals)?;
+            let state = pool.load_state(addresses.get(MULTICALL3).ok(), words).await?;
+            let quote = pool.quote(&state, token_in.address, amount, exact_out)?;
+            registry.save()?;
+            println!("{}", render(&quote, output)?);
+        }
+        Command::Quote { venue: QuoteCommand::UniswapV2 { pool, token_in, amount, exact_out, fee_bps } } => {
+            let mut pool = PoolUniswapV2::new(provider.clone(), addresses.resolve(&pool)?).fee_bps(fee_bps);
+            let info = pool.get_pool_info().await?.clone();
+            let token_in = registry.resolve(&token_in)?;
+            let decimals = match (exact_out, token_in == info.token_0_addr) {
+                (false, true) | (true, false) => info.token_0_decimals,
+                _ => info.token_1_decimals,
+            };
+            let quote = pool.quote(token_in, TokenAmount::parse(&amount, decimals)?, exact_out)?;
+            println!("{}", render(&quote, output)?);
+        }
+        Command::Quote { venue: QuoteCommand::Balancer { pool, token_in, token_out, amount, exact_out } } => {
+            let mut pool = PoolBalancer::new(provider.clone(), addresses.resolve(&pool)?).await?;
+            let info = pool.get_pool_info().await?.clone();
+            let (token_in, token_out) = (registry.resolve(&token_in)?, registry.resolve(&token_out)?);
+            let decimals = info.token(if exact_out { token_out } else { token_in })?.decimals;
+            let quote = pool.quote(token_in, token_out, TokenAmount::parse(&amount, decimals)?, exact_out)?;
+            println!("{}", render(&quote, output)?);
+        }
+        Command::Quote { venue: QuoteCommand::BalancerV2 { pool, token_in, token_out, amount, exact_out, vault } } => {
+            let mut pool = PoolBalancerV2::from_id_or_address(provider.clone(), addresses.resolve(&vault)?, addresses, &pool).await?;
+            let info = pool.get_pool_info().await?.clone();
+            let (token_in, token_out) = (registry.resolve(&token_in)?, registry.resolve(&token_out)?);
+            let decimals = info.tokens[info.token_index(if exact_out { token_out } else { token_in })?].decimals;
+            let amount = TokenAmount::parse(&amount, decimals)?;
+            let mut quote = pool.quote(token_in, token_out, amount, exact_out)?;
+            match pool.query_batch_swap(token_in, token_out, amount.raw(), exact_out).await {
+                Ok(queried) => {
+                    let decimals = if exact_out { quote.amount_in.decimals() } else { quote.amount_out.decimals() };
+                    quote.queried = Some(TokenAmount::new(queried, decimals));
+                }
+                Err(e) => eprintln!("queryBatchSwap failed: {}", e),
+            }
+            println!("{}", render(&quote, output)?);
+        }
+        Command::Quote { venue: QuoteCommand::Curve { pool, token_in, token_out, amount } } => {
+            let mut pool = PoolCurve::new(provider.clone(), addresses.resolve(&pool)?);
+            let info = pool.get_pool_info().await?.clone();
+            let (token_in, token_out) = (registry.resolve(&token_in)?, registry.resolve(&token_out)?);
+            let (Some(i), Some(j)) = (info.coin_index(token_in), info.coin_index(token_out)) else {
+                anyhow::bail!("Both tokens must be coins of the pool {:?}", info.address);
+            };
+            let amount = TokenAmount::parse(&amount, info.coins[i].decimals)?;
+            let mut quote = pool.quote(token_in, token_out, amount)?;
+            match pool.get_dy(i, j, amount.raw()).await {
+                Ok(dy) => quote.on_chain = Some(TokenAmount::new(dy, info.coins[j].decimals)),
+                Err(e) => eprintln!("get_dy failed: {}", e),
+            }
+            println!("{}", render(&quote, output)?);
+        }
+        Command::Quote { venue: QuoteCommand::CurveCrypto { pool, token_in, token_out, amount } } => {
+            let mut pool = PoolCurveCrypto::new(provider.clone(), addresses.resolve(&pool)?);
+            let info = pool.get_pool_info().await?.clone();
+            let (token_in, token_out) = (registry.resolve(&token_in)?, registry.resolve(&token_out)?);
+            let (Some(i), Some(j)) = (info.coin_index(token_in), info.coin_index(token_out)) else {
+                anyhow::bail!("Both tokens must be coins of the pool {:?}", info.address);
+            };
+            let amount = TokenAmount::parse(&amount, info.coins[i].decimals)?;
+            let mut quote = pool.quote(token_in, token_out, amount)?;
+            match pool.get_dy(i, j, amount.raw()).await {
+                Ok(dy) => quote.on_chain = Some(TokenAmount::new(dy, info.coins[j].decimals)),
+                Err(e) => eprintln!("get_dy failed: {}", e),
+            }
+            println!("{}", render(&quote, output)?);
         }
-    }
-    
-    // Fetch ETH/USD price from Chainlink
-    if args.contains(&"chainlink".to_string()) {
-        let price_feed = PriceFeed::new(provider.clone());
-        let (price, timestamp) = price_feed.get_price_with_timestamp().await?;
-        println!("\n");
-        println!("Chainlink ETH/USD Price Feed:");
-        println!("Description: {}", price_feed.get_description().await?);
-        println!("Latest Price: ${:.2}", price);
-        println!("Last Updated: {}", timestamp);
-    }
-   
-    // Get Uniswap pool info
-    if args.contains(&"pool_uniswap".to_string()) {
-        let mut pool = PoolUniswap::new(provider.clone(), UNISWAP_ETHEREUM_WETH_USDC.parse().expect("invalid address"));
-        pool.get_pool_info().await?;
-        println!("\n");
-    }
 
-    // Get Balancer pool info
-    if args.contains(&"pool_balancer".to_string()) {
-        let mut pool = PoolBalancer::new(provider.clone(), BALANCER_ETHEREUM_BCoW_50WETH_50USDC.parse().expect("invalid address")).await?;
-        pool.get_pool_info().await?;
-        println!("\n");
-    }
+        // Fetch a price from Chainlink
+        Command::Feed { pair } => {
+            let feed_address = if pair.starts_with("0x") {
+                addresses.resolve(&pair)?
+            } else {
+                addresses.get(&format!("{}_PRICE_FEED", pair.to_uppercase().replace('/', "_")))?
+            };
+            let price_feed = PriceFeed::new(provider.clone(), feed_address);
+            println!("{}", render(&price_feed.get_report().await?, output)?);
+        }
 
-    // Arbitrage
-    if args.contains(&"arbitrage".to_string()) {
-        call_arbitrage(provider.clone()).await?;
-        println!("\n");
-    }
+        // Get pool info
+        Command::Pool { venue: PoolCommand::Uniswap { pool } } => {
+            let mut pool = PoolUniswap::new(provider.clone(), addresses.resolve(&pool)?);
+            println!("{}", render(pool.get_pool_info().await?, output)?);
+        }
+        Command::Pool { venue: PoolCommand::UniswapV2 { pool, tokens, factory, fee_bps } } => {
+            let pool = match pool {
+                Some(pool) => PoolUniswapV2::new(provider.clone(), addresses.resolve(&pool)?),
+                None if tokens.len() != 2 => anyhow::bail!("--tokens takes exactly two tokens, e.g. WETH,USDC"),
+                None => PoolUniswapV2::from_factory(
+                    provider.clone(),
+                    addresses.resolve(&factory)?,
+                    registry.resolve(&tokens[0])?,
+                    registry.resolve(&tokens[1])?,
+                ).await?,
+            };
+            let mut pool = pool.fee_bps(fee_bps);
+            println!("{}", render(pool.get_pool_info().await?, output)?);
+        }
+        Command::Pool { venue: PoolCommand::BalancerV2 { pool, vault } } => {
+            let mut pool = PoolBalancerV2::from_id_or_address(provider.clone(), addresses.resolve(&vault)?, addresses, &pool).await?;
+            println!("{}", render(pool.get_pool_info().await?, output)?);
+        }
+        Command::Pool { venue: PoolCommand::Curve { pool } } => {
+            let mut pool = PoolCurve::new(provider.clone(), addresses.resolve(&pool)?);
+            println!("{}", render(pool.get_pool_info().await?, output)?);
+        }
+        Command::Pool { venue: PoolCommand::CurveCrypto { pool } } => {
+            let mut pool = PoolCurveCrypto::new(provider.clone(), addresses.resolve(&pool)?);
+            println!("{}", render(pool.get_pool_info().await?, output)?);
+        }
+        Command::Pool { venue: PoolCommand::Liquidity { pool, depth, words, export } } => {
+            let mut pool = PoolUniswap::new(provider.clone(), addresses.resolve(&pool)?);
+            let distribution = pool.liquidity_distribution(addresses.get(MULTICALL3).ok(), words, &depth).await?;
+            if let Some(path) = export {
+                let content = match path.extension().and_then(|ext| ext.to_str()) {
+                    Some("csv") => distribution.to_csv(),
+                    _ => serde_json::to_string_pretty(&distribution)?,
+                };
+                std::fs::write(&path, content)?;
+                eprintln!("Wrote {}", path.display());
+            }
+            println!("{}", render(&distribution, output)?);
+        }
+        Command::Pool { venue: PoolCommand::Balancer { pool } } => {
+            let mut pool = PoolBalancer::new(provider.clone(), addresses.resolve(&pool)?).await?;
+            println!("{}", render(pool.get_pool_info().await?, output)?);
+        }
+
+        // Arbitrage
+        Command::Arb { uniswap, balancer, min_profit, priority_fee } => {
+            let gas = GasModel::fetch(&*provider, addresses.get(WETH)?, &addresses.native_symbol, priority_fee).await?;
+            let result = call_arbitrage(
+                provider.clone(),
+                addresses.resolve(&uniswap)?,
+                addresses.resolve(&balancer)?,
+                &gas,
+                min_profit,
+            ).await?;
+            println!("{}", render(&result, output)?);
+        }
+        Command::ArbSearch { search, top } => {
+            let graph = load_graph(provider.clone(), addresses, &chain).await?;
+            let start_tokens = search.tokens.iter().map(|token| registry.resolve(token)).collect::<Result<Vec<_>>>()?;
+            let gas = GasModel::fetch(&*provider, addresses.get(WETH)?, &addresses.native_symbol, search.priority_fee).await?;
+            let mut result = graph.search(&start_tokens, search.max_hops, &gas, search.min_profit).await?;
+            result.opportunities.truncate(top);
+            println!("{}", render(&result, output)?);
+        }
+        Command::Monitor { search, out, blocks, check_interval } => {
+            // New heads come over the WebSocket, and the pools are read through it too
+            let ws_provider = Arc::new(Provider::<Ws>::connect(chain.ws_url()?).await?);
+            let mut graph = load_graph(ws_provider.clone(), addresses, &chain).await?;
+            let config = MonitorConfig {
+                start_tokens: search.tokens.iter().map(|token| registry.resolve(token)).collect::<Result<Vec<_>>>()?,
+                max_hops: search.max_hops,
+                min_profit: search.min_profit,
+                gas: GasModel::fetch(&*ws_provider, addresses.get(WETH)?, &addresses.native_symbol, search.priority_fee).await?,
+                vault: addresses.get(BALANCER_VAULT).ok(),
+                check_interval,
+                blocks,
+            };
+            let mut sinks = vec![Sink::Stdout(output)];
+            if let Some(path) = out {
+                sinks.push(Sink::file(&path)?);
+            }
+            eprintln!("Monitoring {} pools for arbitrage... (Press Ctrl+C to stop)", graph.pools().len());
+            run_monitor(ws_provider, &mut graph, config, &mut sinks).await?;
+        }
+
+        // Arbitrage executor contract
+        Command::Executor { action } => {
+            let wallet = wallet.expect("executor commands load a wallet");
+            let address = wallet.address();
+            let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet));
+
+            match action {
+                ExecutorCommand::Deploy { bytecode } => {
+                    let deployment = deploy_executor(client.clone(), load_bytecode(&bytecode)?).await?;
+                    println!("{}", render(&deployment, output)?);
+                }
+                ExecutorCommand::Arb { execution, uniswap, balancer, min_profit, priority_fee } => {
+                    let gas = GasModel::fetch(&*provider, addresses.get(WETH)?, &addresses.native_symbol, priority_fee).await?;
+                    let (pool_1, pool_2) = load_arbitrage_pools(
+                        provider.clone(),
+                        addresses.resolve(&uniswap)?,
+                        addresses.resolve(&balancer)?,
+                    ).await?;
+                    let mut result = evaluate_arbitrage(&pool_1, &pool_2, &gas, min_profit).await?;
+                    let executor = ArbExecutor::new(execution.executor, client.clone());
+                    let builder = match result.plan.clone() {
+                        Some(plan) => {
+                            let builder = route_for_plan(&plan, [&pool_1, &pool_2], execution.executor)?.slippage_bps(execution.slippage_bps);
+                            // The executor's own gas estimate replaces the per-venue figure the plan was scored with
+                            let native_price = result.gas.as_ref().and_then(|gas| gas.native_price);
+                            let estimated = route_gas(&executor, &builder.clone().build()?, &gas, native_price).await;
+                            result.net_profit = estimated.net_profit(plan.profit);
+                            result.gas = Some(estimated);
+                            Some(builder)
+                        }
+                        None => None,
+                    };
+                    let builder = match (builder, result.net_profit) {
+                        (Some(builder), Some(net_profit)) if net_profit > 0.0 && net_profit >= min_profit => builder,
+                        _ => anyhow::bail!("No trade clears --min-profit after gas:\n{}", render(&result, OutputFormat::Text)?),
+                    };
+                    // The contract then rejects any fill that wouldn't cover the gas and --min-profit
+                    let floor = result.gas.as_ref().and_then(|gas| gas.profit_floor(min_profit));
+                    let route = builder.min_profit(floor.ok_or_else(|| anyhow::anyhow!("Gas cost couldn't be priced"))?).build()?;
+                    println!("{}", render(&execute_route(&executor, route, execution.dry_run).await?, output)?);
+                }
+                ExecutorCommand::Search { execution, search } => {
+                    let graph = load_graph(provider.clone(), addresses, &chain).await?;
+                    let start_tokens = search.tokens.iter().map(|token| registry.resolve(token)).collect::<Result<Vec<_>>>()?;
+                    let gas = GasModel::fetch(&*provider, addresses.get(WETH)?, &addresses.native_symbol, search.priority_fee).await?;
+                    let result = graph.search(&start_tokens, search.max_hops, &gas, search.min_profit).await?;
+                    let Some(best) = result.opportunities.first() else {
+                        anyhow::bail!("No cycle clears --min-profit after gas");
+                    };
+                    let executor = ArbExecutor::new(execution.executor, client.clone());
+                    let builder = route_for_cycle(&graph, best, execution.executor)
+                        .await?
+                        .slippage_bps(execution.slippage_bps);
+                    // Re-check the best cycle with the executor's own gas estimate
+                    let native_price = best.gas.as_ref().and_then(|gas| gas.native_price);
+                    let estimated = route_gas(&executor, &builder.clone().build()?, &gas, native_price).await;
+                    match estimated.net_profit(best.profit) {
+                        Some(net_profit) if net_profit > 0.0 && net_profit >= search.min_profit => {}
+                        _ => anyhow::bail!("Best cycle no longer clears --min-profit at the estimated gas: {}", estimated.describe()),
+                    }
+                    let floor = estimated.profit_floor(search.min_profit);
+                    let route = builder.min_profit(floor.ok_or_else(|| anyhow::anyhow!("Gas cost couldn't be priced"))?).build()?;
+                    println!("{}", render(&execute_route(&executor, route, execution.dry_run).await?, output)?);
+                }
+                ExecutorCommand::Withdraw { executor, token, amount, to } => {
+                    let token = registry.metadata(provider.clone(), registry.resolve(&token)?).await?;
+                    let amount = TokenAmount::parse(&amount, token.decimals)?;
+                    let executor = ArbExecutor::new(executor, client.clone());
+                    let result = withdraw(&executor, token.address, to.unwrap_or(address), amount).await?;
+                    println!("{}", render(&result, output)?);
+                }
+            }
+            registry.save()?;
+        }
 
-    // Transfer tokens
-    if args.contains(&"account_transfer".to_string()) {
-        let recipient = RECIPIENT_ADDRESS.parse()?;
-        // Transfer ETH
-        let amount = U256::from(1000000000000000u64); // 0.001 ETH in wei
-        println!("\n");
-        transfer_eth(client.clone(), address, recipient, amount).await.map_err(|e| anyhow::anyhow!(e))?;
+        // HD accounts
+        Command::Accounts { action } => match action {
+            AccountsCommand::Derive { count } => {
+                let phrase = read_secret("ETH_RUST_MNEMONIC", "Mnemonic: ")?;
+                accounts.derive(&phrase, count)?;
+                accounts.save()?;
+                println!("{}", render(&accounts, output)?);
+            }
+            AccountsCommand::List => println!("{}", render(&accounts, output)?),
+            AccountsCommand::Label { account, label } => {
+                let account = accounts.set_label(&account, &label)?;
+                accounts.save()?;
+                println!("{}", render(&account, output)?);
+            }
+            AccountsCommand::Scan { gap, tokens } => {
+                let phrase = read_secret("ETH_RUST_MNEMONIC", "Mnemonic: ")?;
+                let symbols: Vec<&str> = tokens.iter().map(String::as_str).collect();
+                let result = accounts
+                    .discover(provider.clone(), &mut registry, addresses, &phrase, &symbols, gap)
+                    .await?;
+                accounts.save()?;
+                registry.save()?;
+                println!("{}", render(&result, output)?);
+            }
+        },
+
+        // Keystore management
+        Command::Wallet { action } => match action {
+            WalletCommand::Create { name } => println!("{}", render(&create_keystore(name.as_deref())?, output)?),
+            WalletCommand::Import { name, mnemonic } => {
+                let entry = import_keystore(name.as_deref(), mnemonic, &cli.derivation_path)?;
+                println!("{}", render(&entry, output)?);
+            }
+            WalletCommand::List => println!("{}", render(&list_keystores()?, output)?),
+            WalletCommand::Export { name } => println!("{}", render(&export_keystore(&name)?, output)?),
+        },
+
+        // Transfers
+        Command::Transfer { kind } => {
+            let wallet = wallet.expect("transfer commands load a wallet");
+            let address = wallet.address();
+            let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet));
+
+            match kind {
+                TransferCommand::Eth { to, amount, send } => {
+                    let amount = TokenAmount::parse(&amount, 18)?;
+                    let result = transfer_eth(client.clone(), address, to, amount, !send).await.map_err(|e| anyhow::anyhow!(e))?;
+                    println!("{}", render(&result, output)?);
+                }
+                TransferCommand::Token { token, to, amount, from, send } => {
+                    let token = registry.metadata(provider.clone(), registry.resolve(&token)?).await?;
+                    let amount = TokenAmount::parse(&amount, token.decimals)?;
+                    let result = match from {
+                        Some(owner) => transfer_tokens_from(client.clone(), token.address, owner, to, amount, !send).await,
+                        None => transfer_tokens(client.clone(), token.address, to, amount, !send).await,
+                    }
+                    .map_err(|e| anyhow::anyhow!(e))?;
+                    println!("{}", render(&result, output)?);
+                }
+                TransferCommand::Approve { token, spender, amount, send } => {
+                    let token = registry.metadata(provider.clone(), registry.resolve(&token)?).await?;
+                    let amount = TokenAmount::parse(&amount, token.decimals)?;
+                    let result = approve_spender(client.clone(), token.address, address, spender, amount, !send).await.map_err(|e| anyhow::anyhow!(e))?;
+                    println!("{}", render(&result, output)?);
+                }
+            }
+            registry.save()?;
+        }
     }
 
     Ok(())
 }
+
+/// Loads the chain's configured pools into a token graph, skipping any that fail to load
+async fn load_graph<M: Middleware + 'static>(provider: Arc<M>, addresses: &ChainAddresses, chain: &Chain) -> Result<TokenGraph> {
+    if chain.pools.is_empty() {
+        anyhow::bail!("No pools configured for chain '{}'; add a `pools` list to the config", chain.name);
+    }
+    let mut pools = Vec::new();
+    for pool in &chain.pools {
+        match load_pool(provider.clone(), addresses, pool).await {
+            Ok(loaded) => pools.push(loaded),
+            Err(e) => eprintln!("Skipping pool {}: {}", pool.pool, e),
+        }
+    }
+    TokenGraph::new(pools)
+}
@@ -0,0 +1,448 @@
+use std::collections::{BTreeSet, VecDeque};
+use anyhow::{bail, Result};
+use ethers::prelude::*;
+use serde::Serialize;
+use crate::amount::TokenAmount;
+use crate::{balancer, curve, uniswap};
+use crate::pool::{Pool, PoolSnapshot};
+
+// Blocks of snapshots kept to roll back reorgs; anything deeper reloads every pool
+pub const DEFAULT_REORG_DEPTH: usize = 64;
+// Blocks between checks of the mirrored state against the chain
+pub const DEFAULT_CHECK_INTERVAL: u64 = 100;
+// Relative spot price difference a consistency check puts down to float rounding
+const PRICE_TOLERANCE: f64 = 1e-9;
+
+/// Events that change the state of some pool venue
+pub fn pool_event_topics() -> Vec<H256> {
+    vec![
+        uniswap::pool::SwapFilter::signature(),
+        uniswap::pool::MintFilter::signature(),
+        uniswap::pool::BurnFilter::signature(),
+        uniswap::pool::CollectFilter::signature(),
+        uniswap::v2::pool::SyncFilter::signature(),
+        balancer::pool::LogSwapFilter::signature(),
+        balancer::pool::LogJoinFilter::signature(),
+        balancer::pool::LogExitFilter::signature(),
+        balancer::vault::SwapFilter::signature(),
+        balancer::vault::PoolBalanceChangedFilter::signature(),
+        curve::pool::TokenExchangeFilter::signature(),
+        curve::crypto::curve_crypto_pool::TokenExchangeFilter::signature(),
+        curve::crypto::curve_tricrypto_ng::TokenExchangeFilter::signature(),
+    ]
+    .into_iter()
+    .chain(curve::pool::liquidity_event_topics())
+    .collect()
+}
+
+/// Index in `pools` of the pool a log is about. Vault swaps and joins/exits come from the Vault
+/// itself, so their pool is read from the pool ID, whose first 20 bytes are the pool's address.
+pub fn log_pool(log: &Log, pools: &[Address], vault: Option<Address>) -> Option<usize> {
+    let by_pool_id = [balancer::vault::SwapFilter::signature(), balancer::vault::PoolBalanceChangedFilter::signature()];
+    let address = match log.topics.first() {
+        Some(topic) if Some(log.address) == vault && by_pool_id.contains(topic) => {
+            Address::from_slice(&log.topics.get(1)?.as_bytes()[..20])
+        }
+        _ => log.address,
+    };
+    pools.iter().position(|pool| *pool == address)
+}
+
+// A mirrored block and the pool states it replaced
+struct JournalEntry {
+    number: U64,
+    hash: H256,
+    // State of each pool the block changed, from before the block
+    snapshots: Vec<(usize, PoolSnapshot)>,
+}
+
+impl JournalEntry {
+    // Keeps a pool's state from before its first change in the block
+    fn save(&mut self, pools: &[Box<dyn Pool>], index: usize) {
+        if !self.snapshots.iter().any(|(saved, _)| *saved == index) {
+            self.snapshots.push((index, pools[index].snapshot()));
+        }
+    }
+}
+
+/// What bringing the pools up to one block took
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MirrorUpdate {
+    // Pool events in the block, plus any replayed for blocks a reorg replaced or the subscription skipped
+    pub logs: usize,
+    // Events applied to the cached state without any calls
+    pub applied: usize,
+    // Pools reloaded because an event couldn't be applied, or after a reorg deeper than the journal
+    pub refreshed: Vec<Address>,
+    // Pools that failed to reload and will be retried next block
+    pub stale: Vec<Address>,
+    pub errors: Vec<String>,
+    // Mirrored blocks undone because they're no longer on the chain
+    pub rolled_back: usize,
+    // Whether a consistency check ran, and the pools it found had drifted from the chain
+    pub checked: bool,
+    pub mismatched: Vec<Address>,
+}
+
+impl MirrorUpdate {
+    /// Whether any pool's state changed
+    pub fn changed(&self) -> bool {
+        self.applied > 0 || !self.refreshed.is_empty() || self.rolled_back > 0 || !self.mismatched.is_empty()
+    }
+}
+
+/// Keeps loaded pools in step with the chain from their events, block by block, instead of
+/// reloading them. Events a pool can't apply itself trigger a reload; every `check_interval`
+/// blocks each pool is reloaded anyway and compared, to catch changes no event shows (e.g.
+/// tokens sent straight to a pool). A journal of pre-block snapshots undoes reorged blocks.
+pub struct PoolMirror {
+    addresses: Vec<Address>,
+    // Balancer V2 swaps are logged by the Vault, so it's watched along with the pools
+    vault: Option<Address>,
+    topics: Vec<H256>,
+    journal: VecDeque<JournalEntry>,
+    stale: BTreeSet<usize>,
+    reorg_depth: usize,
+    check_interval: u64,
+    last_check: Option<U64>,
+}
+
+impl PoolMirror {
+    /// Mirrors pools that have just been loaded
+    pub fn new(pools: &[Box<dyn Pool>], vault: Option<Address>) -> Self {
+        Self {
+            addresses: pools.iter().map(|pool| pool.address()).collect(),
+            vault,
+            topics: pool_event_topics(),
+            journal: VecDeque::new(),
+            stale: BTreeSet::new(),
+            reorg_depth: DEFAULT_REORG_DEPTH,
+            check_interval: DEFAULT_CHECK_INTERVAL,
+            last_check: None,
+        }
+    }
+
+    pub fn reorg_depth(mut self, blocks: usize) -> Self {
+        self.reorg_depth = blocks.max(1);
+        self
+    }
+
+    /// Blocks between consistency checks; 0 turns them off
+    pub fn check_interval(mut self, blocks: u64) -> Self {
+        self.check_interval = blocks;
+        self
+    }
+
+    fn filter(&self) -> Filter {
+        let mut watched = self.addresses.clone();
+        watched.extend(self.vault);
+        Filter::new().address(watched).topic0(self.topics.clone())
+    }
+
+    /// Brings `pools` up to a new head: undoes mirrored blocks a reorg replaced, catches up on
+    /// blocks in between, applies the head's events and reloads the pools they couldn't update
+    pub async fn sync_block<M: Middleware + 'static>(
+        &mut self,
+        provider: &M,
+        pools: &mut [Box<dyn Pool>],
+        block: &Block<H256>,
+    ) -> Result<MirrorUpdate> {
+        let (Some(number), Some(hash)) = (block.number, block.hash) else {
+            bail!("Block {:?} is still pending", block.number);
+        };
+        let mut update = MirrorUpdate::default();
+
+        // Undo mirrored blocks until the last one is an ancestor of the head
+        while let Some(last) = self.journal.back() {
+            let canonical = if last.number + 1 == number {
+                Some(block.parent_hash)
+            } else if last.number < number {
+                provider.get_block(last.number).await?.and_then(|block| block.hash)
+            } else {
+                None
+            };
+            if canonical == Some(last.hash) {
+                break;
+            }
+            self.roll_back(pools)?;
+            update.rolled_back += 1;
+        }
+        if update.rolled_back > 0 && self.journal.is_empty() {
+            self.stale.extend(0..pools.len());
+        }
+
+        let mut entry = JournalEntry { number, hash, snapshots: Vec::new() };
+        if let Err(e) = self.apply_new_logs(provider, pools, &mut entry, &mut update).await {
+            // Nothing of this block is journaled, so the next one fetches the same logs again
+            restore(pools, entry)?;
+            return Err(e);
+        }
+
+        for index in std::mem::take(&mut self.stale) {
+            entry.save(pools, index);
+            match pools[index].refresh().await {
+                Ok(()) => update.refreshed.push(self.addresses[index]),
+                Err(e) => {
+                    update.errors.push(format!("Failed to reload {:?}: {}", self.addresses[index], e));
+                    self.stale.insert(index);
+                }
+            }
+        }
+
+        match self.last_check {
+            Some(checked) if self.check_interval > 0 && number.as_u64() >= checked.as_u64() + self.check_interval => {
+                self.check(pools, &mut entry, &mut update).await;
+                self.last_check = Some(number);
+            }
+            Some(_) => {}
+            // Pools were just loaded, so the first check is an interval away
+            None => self.last_check = Some(number),
+        }
+
+        self.journal.push_back(entry);
+        while self.journal.len() > self.reorg_depth {
+            self.journal.pop_front();
+        }
+        update.stale = self.stale.iter().map(|index| self.addresses[*index]).collect();
+        Ok(update)
+    }
+
+    // Applies the logs of any blocks skipped since the last mirrored one, then the new block's
+    async fn apply_new_logs<M: Middleware + 'static>(
+        &mut self,
+        provider: &M,
+        pools: &mut [Box<dyn Pool>],
+        entry: &mut JournalEntry,
+        update: &mut MirrorUpdate,
+    ) -> Result<()> {
+        if let Some(last) = self.journal.back()
+            && last.number + 1 < entry.number
+        {
+            let logs = provider.get_logs(&self.filter().from_block(last.number + 1).to_block(entry.number - 1)).await?;
+            update.logs += logs.len();
+            update.applied += self.apply_logs(pools, &logs, entry);
+        }
+        let logs = provider.get_logs(&self.filter().at_block_hash(entry.hash)).await?;
+        update.logs += logs.len();
+        update.applied += self.apply_logs(pools, &logs, entry);
+        Ok(())
+    }
+
+    // Applies logs in order, marking pools that couldn't apply one for a reload
+    fn apply_logs(&mut self, pools: &mut [Box<dyn Pool>], logs: &[Log], entry: &mut JournalEntry) -> usize {
+        let mut applied = 0;
+        for log in logs {
+            let Some(index) = log_pool(log, &self.addresses, self.vault) else {
+                continue;
+            };
+            // Its later events are covered by the reload
+            if self.stale.contains(&index) {
+                continue;
+            }
+            entry.save(pools, index);
+            match pools[index].apply_log(log) {
+                Ok(true) => applied += 1,
+                Ok(false) | Err(_) => {
+                    self.stale.insert(index);
+                }
+            }
+        }
+        applied
+    }
+
+    // Puts back the pool states from before the last mirrored block
+    fn roll_back(&mut self, pools: &mut [Box<dyn Pool>]) -> Result<()> {
+        match self.journal.pop_back() {
+            Some(entry) => restore(pools, entry),
+            None => Ok(()),
+        }
+    }
+
+    // Reloads every pool and reports those whose mirrored reserves or price differed
+    async fn check(&mut self, pools: &mut [Box<dyn Pool>], entry: &mut JournalEntry, update: &mut MirrorUpdate) {
+        update.checked = true;
+        for index in 0..pools.len() {
+            let address = self.addresses[index];
+            if self.stale.contains(&index) || update.refreshed.contains(&address) {
+                continue;
+            }
+            let mirrored = observe(pools[index].as_ref()).await;
+            entry.save(pools, index);
+            if let Err(e) = pools[index].refresh().await {
+                update.errors.push(format!("Failed to reload {:?}: {}", address, e));
+                self.stale.insert(index);
+                continue;
+            }
+            let on_chain = observe(pools[index].as_ref()).await;
+            let consistent = match (mirrored, on_chain) {
+                (Ok((reserves, price)), Ok((chain_reserves, chain_price))) => {
+                    reserves == chain_reserves && (price - chain_price).abs() <= PRICE_TOLERANCE * chain_price.abs()
+                }
+                _ => false,
+            };
+            if !consistent {
+                update.mismatched.push(address);
+            }
+        }
+    }
+}
+
+// Puts back the pool states an entry saved
+fn restore(pools: &mut [Box<dyn Pool>], entry: JournalEntry) -> Result<()> {
+    for (index, snapshot) in entry.snapshots {
+        pools[index].restore(snapshot)?;
+    }
+    Ok(())
+}
+
+// What a consistency check compares: the pool's reserves and the price of its first token in its second
+async fn observe(pool: &dyn Pool) -> Result<(Vec<TokenAmount>, f64)> {
+    let tokens = pool.tokens()?;
+    let price = pool.spot_price(tokens[0].address, tokens[1].address).await?;
+    Ok((pool.reserves()?, price))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::{encode, Token};
+    use crate::pool::testing::{token, ConstantProductPool};
+    use crate::uniswap::v2::pool::SyncFilter;
+
+    fn head(number: u64, hash: u8, parent: u8) -> Block<H256> {
+        Block {
+            number: Some(number.into()),
+            hash: Some(H256::repeat_byte(hash)),
+            parent_hash: H256::repeat_byte(parent),
+            ..Default::default()
+        }
+    }
+
+    fn sync(pool: Address, reserves: [u64; 2]) -> Log {
+        Log {
+            address: pool,
+            topics: vec![SyncFilter::signature()],
+            data: encode(&reserves.map(|reserve| Token::Uint(U256::exp10(18) * reserve))).into(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mirror_applies_events_and_rolls_back_reorgs() {
+        let (provider, mock) = Provider::mocked();
+        let (usdc, weth, dai) = (token(1, "USDC"), token(2, "WETH"), token(3, "DAI"));
+        let mut pools: Vec<Box<dyn Pool>> = vec![
+            Box::new(ConstantProductPool::new(0, (&usdc, 2_000_000.0), (&weth, 1_000.0), 30)),
+            Box::new(ConstantProductPool::new(1, (&usdc, 1_000_000.0), (&dai, 1_000_000.0), 30)),
+        ];
+        let (pool_0, pool_1) = (pools[0].address(), pools[1].address());
+        let vault = Address::repeat_byte(9);
+        let mut mirror = PoolMirror::new(&pools, Some(vault));
+        let reserves = |pools: &[Box<dyn Pool>], index: usize| pools[index].reserves().unwrap()[0].to_f64();
+
+        // Block 1 syncs pool 0
+        mock.push::<Vec<Log>, _>(vec![sync(pool_0, [1_900_000, 1_050])]).unwrap();
+        let update = mirror.sync_block(&provider, &mut pools, &head(1, 1, 0)).await.unwrap();
+        assert_eq!((update.logs, update.applied), (1, 1));
+        assert_eq!(reserves(&pools, 0), 1_900_000.0);
+
+        // Block 2 syncs pool 1; a Vault swap names pool 0 by ID and can't be applied, so it reloads
+        let mut pool_id = [0u8; 32];
+        pool_id[..20].copy_from_slice(pool_0.as_bytes());
+        let vault_swap = Log {
+            address: vault,
+            topics: vec![balancer::vault::SwapFilter::signature(), H256(pool_id)],
+            ..Default::default()
+        };
+        mock.push::<Vec<Log>, _>(vec![sync(pool_1, [900_000, 1_100_000]), vault_swap]).unwrap();
+        let update = mirror.sync_block(&provider, &mut pools, &head(2, 2, 1)).await.unwrap();
+        assert_eq!((update.applied, update.refreshed.clone()), (1, vec![pool_0]));
+        assert_eq!(reserves(&pools, 1), 900_000.0);
+
+        // A different block 2 replaces it: pool 1 goes back to before, pool 0 keeps block 1's sync
+        mock.push::<Vec<Log>, _>(Vec::new()).unwrap();
+        let update = mirror.sync_block(&provider, &mut pools, &head(2, 3, 1)).await.unwrap();
+        assert_eq!(update.rolled_back, 1);
+        assert!(update.changed());
+        assert_eq!(reserves(&pools, 1), 1_000_000.0);
+        assert_eq!(reserves(&pools, 0), 1_900_000.0);
+        assert_eq!(log_pool(&sync(Address::repeat_byte(4), [1, 1]), &[pool_0, pool_1], Some(vault)), None);
+    }
+
+    #[tokio::test]
+    async fn test_mirror_undoes_catch_up_when_a_fetch_fails() {
+        let (provider, mock) = Provider::mocked();
+        let (usdc, weth) = (token(1, "USDC"), token(2, "WETH"));
+        let mut pools: Vec<Box<dyn Pool>> = vec![Box::new(ConstantProductPool::new(0, (&usdc, 2_000_000.0), (&weth, 1_000.0), 30))];
+        let pool = pools[0].address();
+        let mut mirror = PoolMirror::new(&pools, None);
+        let reserves = |pools: &[Box<dyn Pool>]| pools[0].reserves().unwrap()[0].to_f64();
+
+        mock.push::<Vec<Log>, _>(Vec::new()).unwrap();
+        mirror.sync_block(&provider, &mut pools, &head(1, 1, 0)).await.unwrap();
+
+        // Block 3 arrives without block 2: block 1 is checked against the chain and block 2's logs
+        // applied, then the head's logs fail to load (responses pop last first, none is left for them)
+        let mirrored = head(1, 1, 0);
+        mock.push::<Vec<Log>, _>(vec![sync(pool, [1_900_000, 1_050])]).unwrap();
+        mock.push::<Block<H256>, _>(&mirrored).unwrap();
+        assert!(mirror.sync_block(&provider, &mut pools, &head(3, 3, 2)).await.is_err());
+        assert_eq!(reserves(&pools), 2_000_000.0);
+
+        // Retrying the head fetches and applies block 2 once
+        mock.push::<Vec<Log>, _>(Vec::new()).unwrap();
+        mock.push::<Vec<Log>, _>(vec![sync(pool, [1_900_000, 1_050])]).unwrap();
+        mock.push::<Block<H256>, _>(&mirrored).unwrap();
+        let update = mirror.sync_block(&provider, &mut pools, &head(3, 3, 2)).await.unwrap();
+        assert_eq!((update.logs, update.applied), (1, 1));
+        assert_eq!(reserves(&pools), 1_900_000.0);
+    }
+
+    #[tokio::test]
+    async fn test_mirror_reloads_pools_on_crypto_exchanges() {
+        let (provider, mock) = Provider::mocked();
+        let (usdc, weth) = (token(1, "USDC"), token(2, "WETH"));
+        let mut pools: Vec<Box<dyn Pool>> = vec![Box::new(ConstantProductPool::new(0, (&usdc, 2_000_000.0), (&weth, 1_000.0), 30))];
+        let pool = pools[0].address();
+        let mut mirror = PoolMirror::new(&pools, None);
+
+        // Both crypto exchange events are watched, and neither can be applied without the pool's math
+        let legacy = curve::crypto::curve_crypto_pool::TokenExchangeFilter::signature();
+        let ng = curve::crypto::curve_tricrypto_ng::TokenExchangeFilter::signature();
+        assert_ne!(legacy, ng);
+        assert!(pool_event_topics().contains(&legacy) && pool_event_topics().contains(&ng));
+        let exchange = Log { address: pool, topics: vec![ng, H256::zero()], ..Default::default() };
+        mock.push::<Vec<Log>, _>(vec![exchange]).unwrap();
+        let update = mirror.sync_block(&provider, &mut pools, &head(1, 1, 0)).await.unwrap();
+        assert_eq!((update.applied, update.refreshed), (0, vec![pool]));
+    }
+    #[tokio::test]
+    async fn test_mirror_reloads_pools_on_liquidity_events() {
+        let (provider, mock) = Provider::mocked();
+        let (usdc, weth, dai) = (token(1, "USDC"), token(2, "WETH"), token(3, "DAI"));
+        let mut pools: Vec<Box<dyn Pool>> = vec![
+            Box::new(ConstantProductPool::new(0, (&usdc, 2_000_000.0), (&weth, 1_000.0), 30)),
+            Box::new(ConstantProductPool::new(1, (&usdc, 1_000_000.0), (&dai, 1_000_000.0), 30)),
+        ];
+        let (pool_0, pool_1) = (pools[0].address(), pools[1].address());
+        let vault = Address::repeat_byte(9);
+        let mut mirror = PoolMirror::new(&pools, Some(vault));
+
+        // 3pool's AddLiquidity, as logged on mainnet, is among the watched topics
+        let add_liquidity: H256 = "0x423f6495a08fc652425cf4ed0d1f9e37e571d9b9529b1c1c23cce780b2e7df0d".parse().unwrap();
+        assert!(pool_event_topics().contains(&add_liquidity));
+
+        // A Vault join names pool 0 by ID, and a Curve deposit comes from pool 1 itself
+        let mut pool_id = [0u8; 32];
+        pool_id[..20].copy_from_slice(pool_0.as_bytes());
+        let join = Log {
+            address: vault,
+            topics: vec![balancer::vault::PoolBalanceChangedFilter::signature(), H256(pool_id), H256::zero()],
+            ..Default::default()
+        };
+        let deposit = Log { address: pool_1, topics: vec![add_liquidity, H256::zero()], ..Default::default() };
+        mock.push::<Vec<Log>, _>(vec![join, deposit]).unwrap();
+        let update = mirror.sync_block(&provider, &mut pools, &head(1, 1, 0)).await.unwrap();
+        assert_eq!((update.applied, update.refreshed), (0, vec![pool_0, pool_1]));
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// Pretty-printed JSON, suitable for `jq`
+    Json,
+    /// Aligned columns
+    Table,
+}
+
+/// A command result that can be rendered in every output format
+pub trait Report: Serialize {
+    fn text(&self) -> String;
+    fn table(&self) -> Table;
+}
+
+pub fn render<R: Report>(report: &R, format: OutputFormat) -> Result<String> {
+    Ok(match format {
+        OutputFormat::Text => report.text(),
+        OutputFormat::Json => serde_json::to_string_pretty(report)?,
+        OutputFormat::Table => report.table().to_string(),
+    })
+}
+
+/// Single-line JSON, for streaming one result per line
+pub fn render_line<R: Report>(report: &R, format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Json => Ok(serde_json::to_string(report)?),
+        _ => render(report, format),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new<S: ToString>(headers: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            headers: headers.into_iter().map(|h| h.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Two-column field/value table
+    pub fn key_value<K: ToString, V: ToString>(pairs: impl IntoIterator<Item = (K, V)>) -> Self {
+        let mut table = Self::new(["field", "value"]);
+        for (key, value) in pairs {
+            table.row([key.to_string(), value.to_string()]);
+        }
+        table
+    }
+
+    /// Comma-separated values with a header row, quoting cells that need it
+    pub fn to_csv(&self) -> String {
+        let escape = |cell: &String| {
+            if cell.contains([',', '"', '\n']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.clone()
+            }
+        };
+        std::iter::once(&self.headers)
+            .chain(&self.rows)
+            .map(|row| row.iter().map(escape).collect::<Vec<_>>().join(",") + "\n")
+            .collect()
+    }
+
+    pub fn row<S: ToString>(&mut self, cells: impl IntoIterator<Item = S>) -> &mut Self {
+        self.rows.push(cells.into_iter().map(|c| c.to_string()).collect());
+        self
+    }
+}
+
+impl std::fmt::Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let columns = self.rows.iter().map(Vec::len).chain([self.headers.len()]).max().unwrap_or(0);
+        let mut widths = vec![0; columns];
+        for row in std::iter::once(&self.headers).chain(&self.rows) {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell.chars().count());
+            }
+        }
+
+        let write_row = |f: &mut std::fmt::Formatter<'_>, row: &[String]| -> std::fmt::Result {
+            let cells: Vec<String> = (0..columns)
+                .map(|i| format!("{:<width$}", row.get(i).map(String::as_str).unwrap_or(""), width = widths[i]))
+                .collect();
+            writeln!(f, "{}", cells.join("  ").trim_end())
+        };
+
+        write_row(f, &self.headers)?;
+        let separator: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        write_row(f, &separator)?;
+        for row in &self.rows {
+            write_row(f, row)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_alignment() {
+        let mut table = Table::new(["token", "balance"]);
+        table.row(["WETH", "1.5"]).row(["USDC", "1000"]);
+        assert_eq!(
+            table.to_string(),
+            "token  balance\n-----  -------\nWETH   1.5\nUSDC   1000\n"
+        );
+    }
+}
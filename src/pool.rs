@@ -0,0 +1,321 @@
+use ethers::prelude::*;
+use ethers::abi::{AbiEncode, RawLog};
+use std::any::Any;
+use std::sync::Arc;
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use crate::amount::TokenAmount;
+use crate::balancer::pool::PoolBalancer;
+use crate::balancer::vault::PoolBalancerV2;
+use crate::contracts::{ChainAddresses, BALANCER_VAULT, MULTICALL3};
+use crate::curve::crypto::PoolCurveCrypto;
+use crate::curve::pool::PoolCurve;
+use crate::uniswap::pool::PoolUniswap;
+use crate::uniswap::v2::pool::PoolUniswapV2;
+
+// ERC-20 view functions every pool type needs for token metadata and balances, plus the
+// approvals and transfers the executor makes ahead of swaps
+abigen!(
+    IERC20,
+    r#"[
+        function symbol() external view returns (string)
+        function decimals() external view returns (uint8)
+        function balanceOf(address owner) external view returns (uint256)
+        function approve(address spender, uint256 amount) external returns (bool)
+        function transfer(address to, uint256 amount) external returns (bool)
+    ]"#
+);
+
+/// A token held by a pool, with what's needed to scale its amounts
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PoolToken {
+    pub address: Address,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+impl PoolToken {
+    pub async fn fetch<M: Middleware + 'static>(provider: Arc<M>, address: Address) -> Result<Self> {
+        let token = IERC20::new(address, provider);
+        Ok(Self {
+            address,
+            symbol: token.symbol().call().await?,
+            decimals: token.decimals().call().await?,
+        })
+    }
+}
+
+/// Fails unless there is contract code at `address`
+pub async fn verify_contract<M: Middleware>(provider: &M, address: Address) -> Result<()> {
+    let code = provider
+        .get_code(address, None)
+        .await
+        .map_err(|e| anyhow!("Failed to get contract code: {}", e))?;
+    if code.is_empty() {
+        bail!("No contract code found at address {:?}", address);
+    }
+    Ok(())
+}
+
+/// Whether a swap from `token_in` to `token_out` goes from token0 to token1 in a two-token pool
+pub fn zero_for_one(tokens: (Address, Address), token_in: Address, token_out: Address) -> Result<bool> {
+    match (token_in, token_out) {
+        (a, b) if a == tokens.0 && b == tokens.1 => Ok(true),
+        (a, b) if a == tokens.1 && b == tokens.0 => Ok(false),
+        _ => bail!("Pool does not trade {:?} for {:?}", token_in, token_out),
+    }
+}
+
+/// One call the executor contract makes while swapping through a pool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RouteCall {
+    pub target: Address,
+    pub data: Bytes,
+}
+
+impl RouteCall {
+    pub fn new(target: Address, call: impl AbiEncode) -> Self {
+        Self { target, data: call.encode().into() }
+    }
+
+    pub fn approve(token: Address, spender: Address, amount: U256) -> Self {
+        Self::new(token, ApproveCall { spender, amount })
+    }
+
+    pub fn transfer(token: Address, to: Address, amount: U256) -> Self {
+        Self::new(token, TransferCall { to, amount })
+    }
+}
+
+/// What the arbitrage code needs from any venue. State is cached by `refresh`, so the
+/// other methods are cheap to call repeatedly; they fail until the first refresh.
+#[async_trait]
+pub trait Pool: Send + Sync {
+    fn address(&self) -> Address;
+
+    /// Short venue name for reports, e.g. "uniswap-v3"
+    fn venue(&self) -> &'static str;
+
+    /// Tokens in pool order
+    fn tokens(&self) -> Result<Vec<PoolToken>>;
+
+    /// Swap fee as a fraction of the input, e.g. 0.003
+    fn fee(&self) -> Result<f64>;
+
+    /// Token balances held by the pool, in pool order
+    fn reserves(&self) -> Result<Vec<TokenAmount>>;
+
+    /// Marginal price of `base` in units of `quote`, before fees
+    async fn spot_price(&self, base: Address, quote: Address) -> Result<f64>;
+
+    /// Amount of `token_out` received for selling `amount_in` of `token_in`, after fees
+    async fn quote_exact_in(&self, token_in: Address, token_out: Address, amount_in: TokenAmount) -> Result<TokenAmount>;
+
+    /// Reloads the on-chain state the other methods read from
+    async fn refresh(&mut self) -> Result<()>;
+
+    /// Updates the cached state from one of the pool's own events. `Ok(false)` means the event
+    /// can't be applied incrementally and the pool needs a `refresh` instead.
+    fn apply_log(&mut self, _log: &Log) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// A copy of the cached state, to roll back events from blocks that get reorged out
+    fn snapshot(&self) -> PoolSnapshot;
+
+    /// Puts back a state taken by `snapshot`
+    fn restore(&mut self, snapshot: PoolSnapshot) -> Result<()>;
+
+    /// Calls that swap `amount_in` of `token_in` held by `executor` for `token_out`, paid to the
+    /// executor. `amount_out` is the quoted output, for venues that are told what to pay out.
+    fn swap_calls(
+        &self,
+        _executor: Address,
+        _token_in: Address,
+        _token_out: Address,
+        _amount_in: U256,
+        _amount_out: U256,
+    ) -> Result<Vec<RouteCall>> {
+        bail!("{} pools can't be swapped through the executor", self.venue())
+    }
+
+    fn token_index(&self, token: Address) -> Result<usize> {
+        self.tokens()?
+            .iter()
+            .position(|pool_token| pool_token.address == token)
+            .ok_or_else(|| anyhow!("Token {:?} is not in pool {:?}", token, self.address()))
+    }
+}
+
+/// A pool's cached state, only readable by the pool type that took it
+pub struct PoolSnapshot(Box<dyn Any + Send + Sync>);
+
+impl PoolSnapshot {
+    pub fn new<T: Any + Send + Sync>(state: T) -> Self {
+        Self(Box::new(state))
+    }
+
+    pub fn take<T: Any>(self) -> Result<T> {
+        self.0.downcast().map(|state| *state).map_err(|_| anyhow!("Snapshot is from another pool type"))
+    }
+}
+
+/// Input for reading a marginal price off a fee-free swap: a millionth of `balance`, small
+/// enough not to move the price
+pub(crate) fn marginal_amount(balance: U256) -> U256 {
+    (balance / 1_000_000).max(U256::one())
+}
+
+/// Decodes one of a contract's events from a log
+pub(crate) fn decode_event<E: EthLogDecode>(log: &Log) -> Option<E> {
+    E::decode_log(&RawLog { topics: log.topics.clone(), data: log.data.to_vec() }).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Venue {
+    Uniswap,
+    UniswapV2,
+    Balancer,
+    BalancerV2,
+    Curve,
+    CurveCrypto,
+}
+
+/// A pool in the chain config's `pools` list: its venue plus an address, address-book name
+/// or, for Balancer V2, pool ID
+#[derive(Debug, Clone, Deserialize)]
+pub struct PoolConfig {
+    pub venue: Venue,
+    pub pool: String,
+}
+
+/// Builds the pool a config entry names and loads its state
+pub async fn load_pool<M: Middleware + 'static>(
+    provider: Arc<M>,
+    addresses: &ChainAddresses,
+    config: &PoolConfig,
+) -> Result<Box<dyn Pool>> {
+    let mut pool: Box<dyn Pool> = match config.venue {
+        Venue::Uniswap => Box::new(
+            PoolUniswap::new(provider, addresses.resolve(&config.pool)?).multicall(addresses.get(MULTICALL3).ok()),
+        ),
+        Venue::UniswapV2 => Box::new(PoolUniswapV2::new(provider, addresses.resolve(&config.pool)?)),
+        Venue::Balancer => Box::new(PoolBalancer::new(provider, addresses.resolve(&config.pool)?).await?),
+        Venue::BalancerV2 => Box::new(
+            PoolBalancerV2::from_id_or_address(provider, addresses.get(BALANCER_VAULT)?, addresses, &config.pool).await?,
+        ),
+        Venue::Curve => Box::new(PoolCurve::new(provider, addresses.resolve(&config.pool)?)),
+        Venue::CurveCrypto => Box::new(PoolCurveCrypto::new(provider, addresses.resolve(&config.pool)?)),
+    };
+    pool.refresh().await?;
+    Ok(pool)
+}
+
+// Shared error for pools whose state hasn't been loaded yet
+pub(crate) fn not_loaded(address: Address) -> anyhow::Error {
+    anyhow!("Pool {:?} not loaded, call refresh first", address)
+}
+
+// Pools with known behaviour for the arbitrage tests
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use crate::uniswap::v2::pool::{SwapCall, SyncFilter};
+
+    /// A Uniswap-V2-style constant-product pool with a fee in basis points
+    pub struct ConstantProductPool {
+        pub address: Address,
+        pub tokens: Vec<PoolToken>,
+        pub reserves: Vec<TokenAmount>,
+        pub fee_bps: u32,
+    }
+
+    impl ConstantProductPool {
+        /// Two 18-decimal tokens with reserves in whole tokens
+        pub fn new(id: u64, (token_a, reserve_a): (&PoolToken, f64), (token_b, reserve_b): (&PoolToken, f64), fee_bps: u32) -> Self {
+            let whole = |reserve: f64| TokenAmount::parse(&format!("{:.18}", reserve), 18).unwrap();
+            Self {
+                address: Address::from_low_u64_be(100 + id),
+                tokens: vec![token_a.clone(), token_b.clone()],
+                reserves: vec![whole(reserve_a), whole(reserve_b)],
+                fee_bps,
+            }
+        }
+    }
+
+    pub fn token(id: u64, symbol: &str) -> PoolToken {
+        PoolToken { address: Address::from_low_u64_be(id), symbol: symbol.to_string(), decimals: 18 }
+    }
+
+    #[async_trait]
+    impl Pool for ConstantProductPool {
+        fn address(&self) -> Address {
+            self.address
+        }
+
+        fn venue(&self) -> &'static str {
+            "mock"
+        }
+
+        fn tokens(&self) -> Result<Vec<PoolToken>> {
+            Ok(self.tokens.clone())
+        }
+
+        fn fee(&self) -> Result<f64> {
+            Ok(self.fee_bps as f64 / 10_000.0)
+        }
+
+        fn reserves(&self) -> Result<Vec<TokenAmount>> {
+            Ok(self.reserves.clone())
+        }
+
+        async fn spot_price(&self, base: Address, quote: Address) -> Result<f64> {
+            let (i, j) = (self.token_index(base)?, self.token_index(quote)?);
+            Ok(self.reserves[j].to_f64() / self.reserves[i].to_f64())
+        }
+
+        async fn quote_exact_in(&self, token_in: Address, token_out: Address, amount_in: TokenAmount) -> Result<TokenAmount> {
+            let (i, j) = (self.token_index(token_in)?, self.token_index(token_out)?);
+            let (reserve_in, reserve_out) = (self.reserves[i].raw(), self.reserves[j].raw());
+            let amount_in_with_fee = amount_in.raw() * (10_000 - self.fee_bps);
+            let amount_out = amount_in_with_fee * reserve_out / (reserve_in * 10_000 + amount_in_with_fee);
+            Ok(TokenAmount::new(amount_out, self.tokens[j].decimals))
+        }
+
+        async fn refresh(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        // Takes the pool's reserves from a Uniswap V2 `Sync`
+        fn apply_log(&mut self, log: &Log) -> Result<bool> {
+            let Some(sync) = decode_event::<SyncFilter>(log) else {
+                return Ok(false);
+            };
+            self.reserves = vec![TokenAmount::new(U256::from(sync.reserve_0), 18), TokenAmount::new(U256::from(sync.reserve_1), 18)];
+            Ok(true)
+        }
+
+        fn snapshot(&self) -> PoolSnapshot {
+            PoolSnapshot::new(self.reserves.clone())
+        }
+
+        fn restore(&mut self, snapshot: PoolSnapshot) -> Result<()> {
+            self.reserves = snapshot.take()?;
+            Ok(())
+        }
+
+        // Swaps like a Uniswap V2 pair: input sent first, output named up front
+        fn swap_calls(&self, executor: Address, token_in: Address, token_out: Address, amount_in: U256, amount_out: U256) -> Result<Vec<RouteCall>> {
+            let (amount_0_out, amount_1_out) = match zero_for_one((self.tokens[0].address, self.tokens[1].address), token_in, token_out)? {
+                true => (U256::zero(), amount_out),
+                false => (amount_out, U256::zero()),
+            };
+            Ok(vec![
+                RouteCall::transfer(token_in, self.address, amount_in),
+                RouteCall::new(self.address, SwapCall { amount_0_out, amount_1_out, to: executor, data: Bytes::new() }),
+            ])
+        }
+    }
+}
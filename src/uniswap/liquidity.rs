@@ -0,0 +1,342 @@
+use ethers::types::{Address, U256};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use anyhow::Result;
+use crate::amount::TokenAmount;
+use crate::output::{Report, Table};
+use crate::uniswap::math::{
+    add_liquidity_delta, get_amount_0_delta, get_amount_1_delta, get_sqrt_ratio_at_tick, mul_div, MAX_TICK, MIN_TICK,
+};
+use crate::uniswap::pool::PoolInfo;
+use crate::uniswap::simulator::PoolState;
+
+// Default price moves, in percent, reported as depth
+pub const DEFAULT_DEPTH_PERCENTAGES: [f64; 4] = [1.0, 2.0, 5.0, 10.0];
+
+/// A price range with constant active liquidity, and the tokens it holds
+#[derive(Debug, Clone, Serialize)]
+pub struct LiquidityRange {
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    // Decimal-adjusted prices of token0 in token1 at the range bounds
+    pub price_lower: f64,
+    pub price_upper: f64,
+    pub amount_0: TokenAmount,
+    pub amount_1: TokenAmount,
+    // Tokens available between spot and the far edge of this range: token0 above spot, token1 below
+    pub cumulative_0: TokenAmount,
+    pub cumulative_1: TokenAmount,
+}
+
+/// Tokens available before the price moves by `change_pct` in either direction
+#[derive(Debug, Clone, Serialize)]
+pub struct DepthLevel {
+    pub change_pct: f64,
+    // token0 bought while pushing the price up by `change_pct`
+    pub amount_0: TokenAmount,
+    // token1 bought while pushing the price down by `change_pct`
+    pub amount_1: TokenAmount,
+    // false if the move reaches past the loaded ticks, so the amounts are a lower bound
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LiquidityDistribution {
+    pub pool: Address,
+    pub token_0_symbol: String,
+    pub token_1_symbol: String,
+    pub tick: i32,
+    pub price: f64,
+    pub liquidity: u128,
+    pub ranges: Vec<LiquidityRange>,
+    pub depth: Vec<DepthLevel>,
+}
+
+impl LiquidityDistribution {
+    /// Reconstructs active liquidity per tick range from a loaded state by applying `liquidityNet` at each crossing
+    pub fn from_state(info: &PoolInfo, state: &PoolState, depth_percentages: &[f64]) -> Result<Self> {
+        let spacing = state.tick_spacing;
+        let lower_bound = (state.word_range.0 as i32 * 256 * spacing).max(MIN_TICK);
+        let upper_bound = ((state.word_range.1 as i32 * 256 + 255) * spacing).min(MAX_TICK);
+        let decimals = (info.token_0_decimals, info.token_1_decimals);
+
+        // Range bounds: every initialized tick plus the edges of the loaded words
+        let mut bounds: BTreeSet<i32> = state.ticks.range(lower_bound..=upper_bound).map(|(tick, _)| *tick).collect();
+        bounds.insert(lower_bound);
+        bounds.insert(upper_bound);
+        let current_lower = *bounds.range(..=state.tick).next_back().unwrap_or(&lower_bound);
+        let liquidity_net = |tick: i32| state.ticks.get(&tick).map(|info| info.liquidity_net).unwrap_or(0);
+
+        // Walk up from the current range, then down, adjusting liquidity at each initialized tick
+        let mut above = Vec::new();
+        let mut liquidity = state.liquidity;
+        let mut lower = current_lower;
+        for &upper in bounds.range(current_lower + 1..) {
+            above.push((lower, upper, liquidity));
+            liquidity = add_liquidity_delta(liquidity, liquidity_net(upper))?;
+            lower = upper;
+        }
+
+        let mut below = Vec::new();
+        let mut liquidity = state.liquidity;
+        let mut upper = current_lower;
+        for &lower in bounds.range(..current_lower).rev() {
+            liquidity = add_liquidity_delta(liquidity, -liquidity_net(upper))?;
+            below.push((lower, upper, liquidity));
+            upper = lower;
+        }
+
+        let mut ranges = Vec::new();
+        let mut cumulative_0 = U256::zero();
+        for (lower, upper, liquidity) in above {
+            let (amount_0, amount_1) = range_amounts(state.sqrt_price_x96, lower, upper, liquidity)?;
+            cumulative_0 += amount_0;
+            ranges.push(make_range(lower, upper, liquidity, (amount_0, amount_1), (cumulative_0, U256::zero()), decimals)?);
+        }
+        // The current range holds token1 below spot too
+        let mut cumulative_1 = ranges.first().map(|range| range.amount_1.raw()).unwrap_or_default();
+        if let Some(current) = ranges.first_mut() {
+            current.cumulative_1 = TokenAmount::new(cumulative_1, decimals.1);
+        }
+        for (lower, upper, liquidity) in below {
+            let (amount_0, amount_1) = range_amounts(state.sqrt_price_x96, lower, upper, liquidity)?;
+            cumulative_1 += amount_1;
+            ranges.push(make_range(lower, upper, liquidity, (amount_0, amount_1), (U256::zero(), cumulative_1), decimals)?);
+        }
+        ranges.sort_by_key(|range| range.tick_lower);
+
+        let depth = depth_percentages
+            .iter()
+            .map(|change_pct| depth_level(state, &ranges, *change_pct, decimals, (lower_bound, upper_bound)))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            pool: info.address,
+            token_0_symbol: info.token_0_symbol.clone(),
+            token_1_symbol: info.token_1_symbol.clone(),
+            tick: state.tick,
+            price: info.price_0_in_1.to_f64(),
+            liquidity: state.liquidity,
+            ranges,
+            depth,
+        })
+    }
+
+    /// Ranges as CSV, one row per tick range, for plotting a depth chart
+    pub fn to_csv(&self) -> String {
+        self.table().to_csv()
+    }
+}
+
+impl Report for LiquidityDistribution {
+    fn text(&self) -> String {
+        let mut lines = vec![
+            "📊 Uniswap V3 Liquidity Distribution:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Pool: {:?}", self.pool),
+            format!("Tick: {} (1 {} = {} {})", self.tick, self.token_0_symbol, self.price, self.token_1_symbol),
+            format!("Active liquidity: {}", self.liquidity),
+            format!("Ranges loaded: {}", self.ranges.len()),
+            String::new(),
+            "🌊 Depth:".to_string(),
+            "-------------------------------------".to_string(),
+        ];
+        for level in &self.depth {
+            lines.push(format!(
+                "±{}%: {} {} above, {} {} below{}",
+                level.change_pct,
+                level.amount_0,
+                self.token_0_symbol,
+                level.amount_1,
+                self.token_1_symbol,
+                if level.complete { "" } else { " (partial, load more words)" }
+            ));
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new([
+            "tick_lower",
+            "tick_upper",
+            "price_lower",
+            "price_upper",
+            "liquidity",
+            "amount_0",
+            "amount_1",
+            "cumulative_0",
+            "cumulative_1",
+        ]);
+        for range in &self.ranges {
+            table.row([
+                range.tick_lower.to_string(),
+                range.tick_upper.to_string(),
+                range.price_lower.to_string(),
+                range.price_upper.to_string(),
+                range.liquidity.to_string(),
+                range.amount_0.to_string(),
+                range.amount_1.to_string(),
+                range.cumulative_0.to_string(),
+                range.cumulative_1.to_string(),
+            ]);
+        }
+        table
+    }
+}
+
+fn tick_price(tick: i32, (decimals_0, decimals_1): (u8, u8)) -> f64 {
+    1.0001f64.powi(tick) * 10f64.powi(decimals_0 as i32 - decimals_1 as i32)
+}
+
+fn make_range(
+    tick_lower: i32,
+    tick_upper: i32,
+    liquidity: u128,
+    (amount_0, amount_1): (U256, U256),
+    (cumulative_0, cumulative_1): (U256, U256),
+    decimals: (u8, u8),
+) -> Result<LiquidityRange> {
+    Ok(LiquidityRange {
+        tick_lower,
+        tick_upper,
+        liquidity,
+        price_lower: tick_price(tick_lower, decimals),
+        price_upper: tick_price(tick_upper, decimals),
+        amount_0: TokenAmount::new(amount_0, decimals.0),
+        amount_1: TokenAmount::new(amount_1, decimals.1),
+        cumulative_0: TokenAmount::new(cumulative_0, decimals.0),
+        cumulative_1: TokenAmount::new(cumulative_1, decimals.1),
+    })
+}
+
+// Token amounts held by `liquidity` in [lower, upper]: token0 above the current price, token1 below
+fn range_amounts(sqrt_price_x96: U256, tick_lower: i32, tick_upper: i32, liquidity: u128) -> Result<(U256, U256)> {
+    let sqrt_lower = get_sqrt_ratio_at_tick(tick_lower)?;
+    let sqrt_upper = get_sqrt_ratio_at_tick(tick_upper)?;
+    amounts_between(sqrt_price_x96, sqrt_lower, sqrt_upper, liquidity)
+}
+
+fn amounts_between(sqrt_price_x96: U256, sqrt_lower: U256, sqrt_upper: U256, liquidity: u128) -> Result<(U256, U256)> {
+    if sqrt_lower >= sqrt_upper {
+        return Ok((U256::zero(), U256::zero()));
+    }
+    if sqrt_price_x96 <= sqrt_lower {
+        Ok((get_amount_0_delta(sqrt_lower, sqrt_upper, liquidity, false)?, U256::zero()))
+    } else if sqrt_price_x96 >= sqrt_upper {
+        Ok((U256::zero(), get_amount_1_delta(sqrt_lower, sqrt_upper, liquidity, false)?))
+    } else {
+        Ok((
+            get_amount_0_delta(sqrt_price_x96, sqrt_upper, liquidity, false)?,
+            get_amount_1_delta(sqrt_lower, sqrt_price_x96, liquidity, false)?,
+        ))
+    }
+}
+
+// Scales a sqrt price by sqrt(factor), i.e. moves the price by `factor`
+fn scale_sqrt_price(sqrt_price_x96: U256, factor: f64) -> Result<U256> {
+    let scaled = U256::from((factor.sqrt() * 1e18) as u128);
+    mul_div(sqrt_price_x96, scaled, U256::exp10(18))
+}
+
+fn depth_level(
+    state: &PoolState,
+    ranges: &[LiquidityRange],
+    change_pct: f64,
+    decimals: (u8, u8),
+    (lower_bound, upper_bound): (i32, i32),
+) -> Result<DepthLevel> {
+    let change = change_pct / 100.0;
+    let sqrt_up = scale_sqrt_price(state.sqrt_price_x96, 1.0 + change)?;
+    let sqrt_down = scale_sqrt_price(state.sqrt_price_x96, (1.0 - change).max(0.0))?;
+
+    let mut amount_0 = U256::zero();
+    let mut amount_1 = U256::zero();
+    for range in ranges {
+        let sqrt_lower = get_sqrt_ratio_at_tick(range.tick_lower)?;
+        let sqrt_upper = get_sqrt_ratio_at_tick(range.tick_upper)?;
+        // token0 between spot and the upper target, token1 between the lower target and spot
+        amount_0 += amounts_between(U256::zero(), sqrt_lower.max(state.sqrt_price_x96), sqrt_upper.min(sqrt_up), range.liquidity)?.0;
+        amount_1 += amounts_between(U256::MAX, sqrt_lower.max(sqrt_down), sqrt_upper.min(state.sqrt_price_x96), range.liquidity)?.1;
+    }
+
+    Ok(DepthLevel {
+        change_pct,
+        amount_0: TokenAmount::new(amount_0, decimals.0),
+        amount_1: TokenAmount::new(amount_1, decimals.1),
+        complete: sqrt_up <= get_sqrt_ratio_at_tick(upper_bound)? && sqrt_down >= get_sqrt_ratio_at_tick(lower_bound)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uniswap::pool::Slot0;
+    use crate::uniswap::simulator::TickInfo;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_distribution_reconstructs_liquidity_per_range() {
+        // Positions [-600, 600] with 1e18 and [-60, 60] with 2e18, at price 1
+        let mut ticks = BTreeMap::new();
+        for (tick, net) in [(-600, 10i128.pow(18)), (-60, 2 * 10i128.pow(18)), (60, -2 * 10i128.pow(18)), (600, -(10i128.pow(18)))] {
+            ticks.insert(tick, TickInfo { liquidity_gross: net.unsigned_abs(), liquidity_net: net });
+        }
+        let state = PoolState {
+            sqrt_price_x96: U256::one() << 96,
+            tick: 0,
+            liquidity: 3 * 10u128.pow(18),
+            fee: 3000,
+            tick_spacing: 60,
+            ticks,
+            word_range: (-1, 0),
+        };
+        let slot0 = Slot0 {
+            sqrt_price_x96: state.sqrt_price_x96,
+            tick: 0,
+            observation_index: 0,
+            observation_cardinality: 1,
+            observation_cardinality_next: 1,
+            fee_protocol: 0,
+            unlocked: true,
+        };
+        let info = PoolInfo {
+            address: Address::zero(),
+            fee: 3000,
+            liquidity: state.liquidity,
+            factory: Address::zero(),
+            token_0_addr: Address::zero(),
+            token_1_addr: Address::zero(),
+            token_0_symbol: "A".to_string(),
+            token_1_symbol: "B".to_string(),
+            token_0_balance: TokenAmount::zero(18),
+            token_1_balance: TokenAmount::zero(18),
+            token_0_decimals: 18,
+            token_1_decimals: 18,
+            slot0,
+            price_0_in_1: TokenAmount::parse("1", 18).unwrap(),
+            price_1_in_0: TokenAmount::parse("1", 18).unwrap(),
+        };
+
+        let distribution = LiquidityDistribution::from_state(&info, &state, &[0.5, 99.0]).unwrap();
+        let liquidity: Vec<(i32, i32, u128)> =
+            distribution.ranges.iter().map(|r| (r.tick_lower, r.tick_upper, r.liquidity)).collect();
+        assert_eq!(
+            liquidity,
+            vec![
+                (-256 * 60, -600, 0),
+                (-600, -60, 10u128.pow(18)),
+                (-60, 60, 3 * 10u128.pow(18)),
+                (60, 600, 10u128.pow(18)),
+                (600, 255 * 60, 0),
+            ]
+        );
+
+        // Within ±0.5% only the narrow position is reached; -99% runs past the loaded words
+        assert!(distribution.depth[0].complete);
+        assert!(distribution.depth[0].amount_0 < distribution.ranges[2].cumulative_0);
+        assert!(!distribution.depth[1].complete);
+        assert_eq!(distribution.depth[1].amount_1, distribution.ranges[0].cumulative_1);
+        assert!(distribution.to_csv().starts_with("tick_lower,tick_upper,"));
+    }
+}
@@ -0,0 +1,407 @@
+// Port of the Uniswap V3 core math libraries (FullMath, TickMath, SqrtPriceMath, SwapMath)
+use ethers::types::{U256, U512};
+use anyhow::{anyhow, bail, Result};
+
+pub const MIN_TICK: i32 = -887272;
+pub const MAX_TICK: i32 = 887272;
+
+// getSqrtRatioAtTick(MIN_TICK) and getSqrtRatioAtTick(MAX_TICK)
+pub fn min_sqrt_ratio() -> U256 {
+    U256::from(4295128739u64)
+}
+
+pub fn max_sqrt_ratio() -> U256 {
+    U256::from_dec_str("1461446703485210103287273052203988822378723970342").unwrap()
+}
+
+// Fee denominator: fees are expressed in hundredths of a bip
+pub const FEE_DENOMINATOR: u32 = 1_000_000;
+
+const RESOLUTION: usize = 96;
+
+fn q96() -> U256 {
+    U256::one() << RESOLUTION
+}
+
+fn max_u160() -> U256 {
+    (U256::one() << 160) - 1
+}
+
+// ---- FullMath ----
+
+/// floor(a * b / denominator) with a 512-bit intermediate
+pub fn mul_div(a: U256, b: U256, denominator: U256) -> Result<U256> {
+    if denominator.is_zero() {
+        bail!("mulDiv by zero");
+    }
+    let result = U512::from(a) * U512::from(b) / U512::from(denominator);
+    U256::try_from(result).map_err(|_| anyhow!("mulDiv overflow"))
+}
+
+/// ceil(a * b / denominator) with a 512-bit intermediate
+pub fn mul_div_rounding_up(a: U256, b: U256, denominator: U256) -> Result<U256> {
+    let result = mul_div(a, b, denominator)?;
+    if (U512::from(a) * U512::from(b)) % U512::from(denominator) > U512::zero() {
+        if result == U256::MAX {
+            bail!("mulDivRoundingUp overflow");
+        }
+        return Ok(result + 1);
+    }
+    Ok(result)
+}
+
+// UnsafeMath.divRoundingUp
+fn div_rounding_up(x: U256, y: U256) -> U256 {
+    let quotient = x / y;
+    if x % y > U256::zero() { quotient + 1 } else { quotient }
+}
+
+// ---- TickMath ----
+
+// Multipliers for each bit of |tick|: 2^128 / sqrt(1.0001)^(2^i)
+const TICK_RATIOS: [u128; 19] = [
+    0xfff97272373d413259a46990580e213a,
+    0xfff2e50f5f656932ef12357cf3c7fdcc,
+    0xffe5caca7e10e4e61c3624eaa0941cd0,
+    0xffcb9843d60f6159c9db58835c926644,
+    0xff973b41fa98c081472e6896dfb254c0,
+    0xff2ea16466c96a3843ec78b326b52861,
+    0xfe5dee046a99a2a811c461f1969c3053,
+    0xfcbe86c7900a88aedcffc83b479aa3a4,
+    0xf987a7253ac413176f2b074cf7815e54,
+    0xf3392b0822b70005940c7a398e4b70f3,
+    0xe7159475a2c29b7443b29c7fa6e889d9,
+    0xd097f3bdfd2022b8845ad8f792aa5825,
+    0xa9f746462d870fdf8a65dc1f90e061e5,
+    0x70d869a156d2a1b890bb3df62baf32f7,
+    0x31be135f97d08fd981231505542fcfa6,
+    0x9aa508b5b7a84e1c677de54f3e99bc9,
+    0x5d6af8dedb81196699c329225ee604,
+    0x2216e584f5fa1ea926041bedfe98,
+    0x48a170391f7dc42444e8fa2,
+];
+
+/// sqrt(1.0001^tick) * 2^96
+pub fn get_sqrt_ratio_at_tick(tick: i32) -> Result<U256> {
+    let abs_tick = tick.unsigned_abs();
+    if abs_tick > MAX_TICK as u32 {
+        bail!("Tick {} out of range", tick);
+    }
+
+    let mut ratio = if abs_tick & 0x1 != 0 {
+        U256::from(0xfffcb933bd6fad37aa2d162d1a594001u128)
+    } else {
+        U256::one() << 128
+    };
+    for (bit, multiplier) in TICK_RATIOS.iter().enumerate() {
+        if abs_tick & (0x2 << bit) != 0 {
+            ratio = (ratio * U256::from(*multiplier)) >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+
+    // Back to Q64.96, rounding up so getTickAtSqrtRatio of the result is consistent
+    let rounding = if (ratio & U256::from(u32::MAX)).is_zero() { 0 } else { 1 };
+    Ok((ratio >> 32) + rounding)
+}
+
+/// The greatest tick whose sqrt ratio is <= `sqrt_price_x96`
+pub fn get_tick_at_sqrt_ratio(sqrt_price_x96: U256) -> Result<i32> {
+    if sqrt_price_x96 < min_sqrt_ratio() || sqrt_price_x96 >= max_sqrt_ratio() {
+        bail!("sqrtPriceX96 {} out of range", sqrt_price_x96);
+    }
+    // Binary search over getSqrtRatioAtTick, which is monotonic; same result as the on-chain log2 approximation
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if get_sqrt_ratio_at_tick(mid)? <= sqrt_price_x96 {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    Ok(low)
+}
+
+// ---- SqrtPriceMath ----
+
+/// Next sqrt price after adding or removing `amount` of token0, rounding up
+pub fn get_next_sqrt_price_from_amount_0_rounding_up(
+    sqrt_price_x96: U256,
+    liquidity: u128,
+    amount: U256,
+    add: bool,
+) -> Result<U256> {
+    if amount.is_zero() {
+        return Ok(sqrt_price_x96);
+    }
+    let numerator_1 = U256::from(liquidity) << RESOLUTION;
+    let product = amount.checked_mul(sqrt_price_x96);
+
+    if add {
+        if let Some(product) = product
+            && let Some(denominator) = numerator_1.checked_add(product)
+        {
+            return mul_div_rounding_up(numerator_1, sqrt_price_x96, denominator);
+        }
+        let denominator = (numerator_1 / sqrt_price_x96)
+            .checked_add(amount)
+            .ok_or_else(|| anyhow!("Amount overflow"))?;
+        Ok(div_rounding_up(numerator_1, denominator))
+    } else {
+        let product = match product {
+            Some(product) if numerator_1 > product => product,
+            _ => bail!("Not enough liquidity to remove {} of token0", amount),
+        };
+        let result = mul_div_rounding_up(numerator_1, sqrt_price_x96, numerator_1 - product)?;
+        if result > max_u160() {
+            bail!("sqrtPriceX96 overflows uint160");
+        }
+        Ok(result)
+    }
+}
+
+/// Next sqrt price after adding or removing `amount` of token1, rounding down
+pub fn get_next_sqrt_price_from_amount_1_rounding_down(
+    sqrt_price_x96: U256,
+    liquidity: u128,
+    amount: U256,
+    add: bool,
+) -> Result<U256> {
+    let liquidity = U256::from(liquidity);
+    if add {
+        let quotient = mul_div(amount, q96(), liquidity)?;
+        let result = sqrt_price_x96.checked_add(quotient).ok_or_else(|| anyhow!("sqrtPriceX96 overflow"))?;
+        if result > max_u160() {
+            bail!("sqrtPriceX96 overflows uint160");
+        }
+        Ok(result)
+    } else {
+        let quotient = mul_div_rounding_up(amount, q96(), liquidity)?;
+        if sqrt_price_x96 <= quotient {
+            bail!("Not enough liquidity to remove {} of token1", amount);
+        }
+        Ok(sqrt_price_x96 - quotient)
+    }
+}
+
+pub fn get_next_sqrt_price_from_input(sqrt_price_x96: U256, liquidity: u128, amount_in: U256, zero_for_one: bool) -> Result<U256> {
+    if sqrt_price_x96.is_zero() || liquidity == 0 {
+        bail!("Price and liquidity must be non-zero");
+    }
+    if zero_for_one {
+        get_next_sqrt_price_from_amount_0_rounding_up(sqrt_price_x96, liquidity, amount_in, true)
+    } else {
+        get_next_sqrt_price_from_amount_1_rounding_down(sqrt_price_x96, liquidity, amount_in, true)
+    }
+}
+
+pub fn get_next_sqrt_price_from_output(sqrt_price_x96: U256, liquidity: u128, amount_out: U256, zero_for_one: bool) -> Result<U256> {
+    if sqrt_price_x96.is_zero() || liquidity == 0 {
+        bail!("Price and liquidity must be non-zero");
+    }
+    if zero_for_one {
+        get_next_sqrt_price_from_amount_1_rounding_down(sqrt_price_x96, liquidity, amount_out, false)
+    } else {
+        get_next_sqrt_price_from_amount_0_rounding_up(sqrt_price_x96, liquidity, amount_out, false)
+    }
+}
+
+/// token0 needed to move between two prices: L * (sqrtB - sqrtA) / (sqrtA * sqrtB)
+pub fn get_amount_0_delta(sqrt_ratio_a: U256, sqrt_ratio_b: U256, liquidity: u128, round_up: bool) -> Result<U256> {
+    let (sqrt_ratio_a, sqrt_ratio_b) = if sqrt_ratio_a > sqrt_ratio_b {
+        (sqrt_ratio_b, sqrt_ratio_a)
+    } else {
+        (sqrt_ratio_a, sqrt_ratio_b)
+    };
+    if sqrt_ratio_a.is_zero() {
+        bail!("sqrtPriceX96 must be non-zero");
+    }
+    let numerator_1 = U256::from(liquidity) << RESOLUTION;
+    let numerator_2 = sqrt_ratio_b - sqrt_ratio_a;
+
+    if round_up {
+        Ok(div_rounding_up(mul_div_rounding_up(numerator_1, numerator_2, sqrt_ratio_b)?, sqrt_ratio_a))
+    } else {
+        Ok(mul_div(numerator_1, numerator_2, sqrt_ratio_b)? / sqrt_ratio_a)
+    }
+}
+
+/// token1 needed to move between two prices: L * (sqrtB - sqrtA)
+pub fn get_amount_1_delta(sqrt_ratio_a: U256, sqrt_ratio_b: U256, liquidity: u128, round_up: bool) -> Result<U256> {
+    let difference = if sqrt_ratio_a > sqrt_ratio_b {
+        sqrt_ratio_a - sqrt_ratio_b
+    } else {
+        sqrt_ratio_b - sqrt_ratio_a
+    };
+    if round_up {
+        mul_div_rounding_up(U256::from(liquidity), difference, q96())
+    } else {
+        mul_div(U256::from(liquidity), difference, q96())
+    }
+}
+
+// ---- SwapMath ----
+
+/// Result of swapping within a single tick range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapStep {
+    pub sqrt_price_next_x96: U256,
+    pub amount_in: U256,
+    pub amount_out: U256,
+    pub fee_amount: U256,
+}
+
+/// Swaps `amount_remaining` (input if `exact_in`, else output) towards the target price, capped by it
+pub fn compute_swap_step(
+    sqrt_ratio_current_x96: U256,
+    sqrt_ratio_target_x96: U256,
+    liquidity: u128,
+    amount_remaining: U256,
+    exact_in: bool,
+    fee_pips: u32,
+) -> Result<SwapStep> {
+    let zero_for_one = sqrt_ratio_current_x96 >= sqrt_ratio_target_x96;
+    let fee = U256::from(fee_pips);
+    let fee_complement = U256::from(FEE_DENOMINATOR - fee_pips);
+    let mut amount_in = U256::zero();
+    let mut amount_out = U256::zero();
+
+    let sqrt_price_next_x96 = if exact_in {
+        let amount_remaining_less_fee = mul_div(amount_remaining, fee_complement, U256::from(FEE_DENOMINATOR))?;
+        amount_in = if zero_for_one {
+            get_amount_0_delta(sqrt_ratio_target_x96, sqrt_ratio_current_x96, liquidity, true)?
+        } else {
+            get_amount_1_delta(sqrt_ratio_current_x96, sqrt_ratio_target_x96, liquidity, true)?
+        };
+        if amount_remaining_less_fee >= amount_in {
+            sqrt_ratio_target_x96
+        } else {
+            get_next_sqrt_price_from_input(sqrt_ratio_current_x96, liquidity, amount_remaining_less_fee, zero_for_one)?
+        }
+    } else {
+        amount_out = if zero_for_one {
+            get_amount_1_delta(sqrt_ratio_target_x96, sqrt_ratio_current_x96, liquidity, false)?
+        } else {
+            get_amount_0_delta(sqrt_ratio_current_x96, sqrt_ratio_target_x96, liquidity, false)?
+        };
+        if amount_remaining >= amount_out {
+            sqrt_ratio_target_x96
+        } else {
+            get_next_sqrt_price_from_output(sqrt_ratio_current_x96, liquidity, amount_remaining, zero_for_one)?
+        }
+    };
+
+    let max = sqrt_ratio_target_x96 == sqrt_price_next_x96;
+    // Amounts computed above are reused only if the step reached the target
+    let keep_in = max && exact_in;
+    let keep_out = max && !exact_in;
+
+    if zero_for_one {
+        if !keep_in {
+            amount_in = get_amount_0_delta(sqrt_price_next_x96, sqrt_ratio_current_x96, liquidity, true)?;
+        }
+        if !keep_out {
+            amount_out = get_amount_1_delta(sqrt_price_next_x96, sqrt_ratio_current_x96, liquidity, false)?;
+        }
+    } else {
+        if !keep_in {
+            amount_in = get_amount_1_delta(sqrt_ratio_current_x96, sqrt_price_next_x96, liquidity, true)?;
+        }
+        if !keep_out {
+            amount_out = get_amount_0_delta(sqrt_ratio_current_x96, sqrt_price_next_x96, liquidity, false)?;
+        }
+    }
+
+    // Never pay out more than requested
+    if !exact_in && amount_out > amount_remaining {
+        amount_out = amount_remaining;
+    }
+
+    let fee_amount = if exact_in && sqrt_price_next_x96 != sqrt_ratio_target_x96 {
+        // The whole remainder was consumed, so whatever isn't input is fee
+        amount_remaining - amount_in
+    } else {
+        mul_div_rounding_up(amount_in, fee, fee_complement)?
+    };
+
+    Ok(SwapStep {
+        sqrt_price_next_x96,
+        amount_in,
+        amount_out,
+        fee_amount,
+    })
+}
+
+// ---- LiquidityMath ----
+
+/// Applies a signed liquidity delta, failing on underflow or overflow like `LiquidityMath.addDelta`
+pub fn add_liquidity_delta(liquidity: u128, delta: i128) -> Result<u128> {
+    if delta < 0 {
+        liquidity.checked_sub(delta.unsigned_abs()).ok_or_else(|| anyhow!("Liquidity underflow"))
+    } else {
+        liquidity.checked_add(delta as u128).ok_or_else(|| anyhow!("Liquidity overflow"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_math_bounds_and_inverse() {
+        assert_eq!(get_sqrt_ratio_at_tick(MIN_TICK).unwrap(), min_sqrt_ratio());
+        assert_eq!(get_sqrt_ratio_at_tick(MAX_TICK).unwrap(), max_sqrt_ratio());
+        assert_eq!(get_sqrt_ratio_at_tick(0).unwrap(), U256::one() << 96);
+        assert!(get_sqrt_ratio_at_tick(MAX_TICK + 1).is_err());
+
+        for tick in [MIN_TICK, -200_000, -1, 0, 1, 50_000, 887_271] {
+            let sqrt_price = get_sqrt_ratio_at_tick(tick).unwrap();
+            assert_eq!(get_tick_at_sqrt_ratio(sqrt_price).unwrap(), tick);
+            assert_eq!(get_tick_at_sqrt_ratio(sqrt_price + 1).unwrap(), tick);
+        }
+        assert_eq!(get_tick_at_sqrt_ratio(min_sqrt_ratio() + 1).unwrap(), MIN_TICK);
+        assert_eq!(get_tick_at_sqrt_ratio(max_sqrt_ratio() - 1).unwrap(), MAX_TICK - 1);
+    }
+
+    #[test]
+    fn test_swap_step_cases_from_uniswap_tests() {
+        // Cases from v3-core's SwapMath.spec.ts
+        let price_1 = U256::one() << 96;
+        let price_101_100 = U256::from_dec_str("79623317895830914510639640423").unwrap();
+        let liquidity = 2 * 10u128.pow(18);
+        let amount = U256::exp10(18);
+
+        // Exact in, capped at the price target in one for zero
+        let step = compute_swap_step(price_1, price_101_100, liquidity, amount, true, 600).unwrap();
+        assert_eq!(step.amount_in, U256::from_dec_str("9975124224178055").unwrap());
+        assert_eq!(step.fee_amount, U256::from_dec_str("5988667735148").unwrap());
+        assert_eq!(step.amount_out, U256::from_dec_str("9925619580021728").unwrap());
+        assert_eq!(step.sqrt_price_next_x96, price_101_100);
+
+        // Exact out, fully received in one for zero
+        let target = U256::from_dec_str("250541448375047931186413801569").unwrap();
+        let step = compute_swap_step(price_1, target, liquidity, amount, false, 600).unwrap();
+        assert_eq!(step.amount_in, U256::from_dec_str("2000000000000000000").unwrap());
+        assert_eq!(step.fee_amount, U256::from_dec_str("1200720432259356").unwrap());
+        assert_eq!(step.amount_out, amount);
+        assert_eq!(step.sqrt_price_next_x96, U256::from_dec_str("158456325028528675187087900672").unwrap());
+
+        // Entire input amount taken as fee
+        let step = compute_swap_step(
+            U256::from(2413u64),
+            U256::from_dec_str("79887613182836312").unwrap(),
+            1985041575832132834610021537970,
+            U256::from(10u64),
+            true,
+            1872,
+        )
+        .unwrap();
+        assert_eq!(step.amount_in, U256::zero());
+        assert_eq!(step.fee_amount, U256::from(10u64));
+        assert_eq!(step.amount_out, U256::zero());
+        assert_eq!(step.sqrt_price_next_x96, U256::from(2413u64));
+    }
+}
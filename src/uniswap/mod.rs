@@ -1 +1,5 @@
-pub mod pool;
\ No newline at end of file
+pub mod pool;
+pub mod math;
+pub mod simulator;
+pub mod liquidity;
+pub mod v2;
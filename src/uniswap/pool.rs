@@ -1,6 +1,15 @@
 use ethers::prelude::*;
+use ethers::abi::AbiEncode;
+use ethers::types::U512;
 use std::sync::Arc;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use crate::amount::TokenAmount;
+use crate::output::{Report, Table};
+use crate::pool::{decode_event, not_loaded, verify_contract, zero_for_one, Pool, PoolSnapshot, PoolToken, RouteCall, IERC20};
+use crate::uniswap::liquidity::LiquidityDistribution;
+use crate::uniswap::math::{max_sqrt_ratio, min_sqrt_ratio};
+use crate::uniswap::simulator::{load_pool_state, PoolState, SwapQuote};
 
 // UniswapV3Pool ABI fragment based on official Uniswap V3 interfaces
 abigen!(
@@ -11,19 +20,63 @@ abigen!(
         function fee() external view returns (uint24)
         function liquidity() external view returns (uint128)
         function factory() external view returns (address)
+        function tickSpacing() external view returns (int24)
+        function tickBitmap(int16 wordPosition) external view returns (uint256)
+        function ticks(int24 tick) external view returns (uint128 liquidityGross, int128 liquidityNet, uint256 feeGrowthOutside0X128, uint256 feeGrowthOutside1X128, int56 tickCumulativeOutside, uint160 secondsPerLiquidityOutsideX128, uint32 secondsOutside, bool initialized)
+        function slot0() external view returns (uint160 sqrtPriceX96, int24 tick, uint16 observationIndex, uint16 observationCardinality, uint16 observationCardinalityNext, uint8 feeProtocol, bool unlocked)
+        function swap(address recipient, bool zeroForOne, int256 amountSpecified, uint160 sqrtPriceLimitX96, bytes data) external returns (int256 amount0, int256 amount1)
+        event Swap(address indexed sender, address indexed recipient, int256 amount0, int256 amount1, uint160 sqrtPriceX96, uint128 liquidity, int24 tick)
+        event Mint(address sender, address indexed owner, int24 indexed tickLower, int24 indexed tickUpper, uint128 amount, uint256 amount0, uint256 amount1)
+        event Burn(address indexed owner, int24 indexed tickLower, int24 indexed tickUpper, uint128 amount, uint256 amount0, uint256 amount1)
+        event Collect(address indexed owner, address recipient, int24 indexed tickLower, int24 indexed tickUpper, uint128 amount0, uint128 amount1)
     ]"#
 );
 
-abigen!(
-    IERC20,
-    r#"[
-        function symbol() external view returns (string)
-        function decimals() external view returns (uint8)
-        function balanceOf(address) external view returns (uint256)
-    ]"#
-);
+// Decimal places kept when converting sqrtPriceX96 into a spot price
+pub const PRICE_DECIMALS: u8 = 18;
+
+/// The pool's `slot0`: current price, tick and oracle observation state
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Slot0 {
+    pub sqrt_price_x96: U256,
+    pub tick: i32,
+    pub observation_index: u16,
+    pub observation_cardinality: u16,
+    pub observation_cardinality_next: u16,
+    pub fee_protocol: u8,
+    pub unlocked: bool,
+}
 
+impl Slot0 {
+    /// Price of token0 in token1, decimal-adjusted and truncated to `PRICE_DECIMALS`
+    pub fn price_0_in_1(&self, decimals_0: u8, decimals_1: u8) -> Result<TokenAmount> {
+        // price = sqrtPriceX96^2 / 2^192 * 10^(decimals_0 - decimals_1)
+        let sqrt_price = U512::from(self.sqrt_price_x96);
+        let numerator = sqrt_price * sqrt_price * U512::exp10(decimals_0 as usize + PRICE_DECIMALS as usize);
+        let denominator = (U512::one() << 192) * U512::exp10(decimals_1 as usize);
+        price_ratio(numerator, denominator)
+    }
+
+    /// Price of token1 in token0, decimal-adjusted and truncated to `PRICE_DECIMALS`
+    pub fn price_1_in_0(&self, decimals_0: u8, decimals_1: u8) -> Result<TokenAmount> {
+        let sqrt_price = U512::from(self.sqrt_price_x96);
+        let numerator = (U512::one() << 192) * U512::exp10(decimals_1 as usize + PRICE_DECIMALS as usize);
+        let denominator = sqrt_price * sqrt_price * U512::exp10(decimals_0 as usize);
+        price_ratio(numerator, denominator)
+    }
+}
+
+fn price_ratio(numerator: U512, denominator: U512) -> Result<TokenAmount> {
+    if denominator.is_zero() {
+        return Err(anyhow!("Pool is not initialized (sqrtPriceX96 is zero)"));
+    }
+    let raw = U256::try_from(numerator / denominator).map_err(|_| anyhow!("Spot price overflows U256"))?;
+    Ok(TokenAmount::new(raw, PRICE_DECIMALS))
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct PoolInfo {
+    pub address: Address,
     pub fee: u32,
     pub liquidity: u128,
     pub factory: Address,
@@ -31,52 +84,163 @@ pub struct PoolInfo {
     pub token_1_addr: Address,
     pub token_0_symbol: String,
     pub token_1_symbol: String,
-    pub token_0_balance: f64,
-    pub token_1_balance: f64,
+    pub token_0_balance: TokenAmount,
+    pub token_1_balance: TokenAmount,
+    pub token_0_decimals: u8,
+    pub token_1_decimals: u8,
+    pub slot0: Slot0,
+    // Spot prices from sqrtPriceX96: token1 per token0, and token0 per token1
+    pub price_0_in_1: TokenAmount,
+    pub price_1_in_0: TokenAmount,
+}
+
+impl PoolInfo {
+    /// Virtual reserves `L / sqrtP` and `L * sqrtP` in whole tokens, valid while the price stays in the current tick range
+    pub fn virtual_reserves(&self) -> (f64, f64) {
+        let sqrt_price = self.slot0.sqrt_price_x96.to_string().parse::<f64>().unwrap_or(0.0) / 2f64.powi(96);
+        let liquidity = self.liquidity as f64;
+        let reserve_0 = liquidity / sqrt_price / 10f64.powi(self.token_0_decimals as i32);
+        let reserve_1 = liquidity * sqrt_price / 10f64.powi(self.token_1_decimals as i32);
+        (reserve_0, reserve_1)
+    }
 }
 
-pub struct PoolUniswap {
-    contract: UniswapV3Pool<Provider<Http>>,
-    provider: Arc<Provider<Http>>,
+impl Report for PoolInfo {
+    fn text(&self) -> String {
+        [
+            "🪣  Uniswap V3 Pool Info:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Pool: {:?}", self.address),
+            format!("Fee: {}", self.fee),
+            format!("Liquidity: {}", self.liquidity),
+            format!("Factory: {:?}", self.factory),
+            format!("Token0: {:?}", self.token_0_addr),
+            format!("Token1: {:?}", self.token_1_addr),
+            format!("sqrtPriceX96: {}", self.slot0.sqrt_price_x96),
+            format!("Tick: {}", self.slot0.tick),
+            format!(
+                "Observations: index {}, cardinality {} (next {})",
+                self.slot0.observation_index, self.slot0.observation_cardinality, self.slot0.observation_cardinality_next
+            ),
+            String::new(),
+            "💱 Spot Price:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("1 {} = {} {}", self.token_0_symbol, self.price_0_in_1, self.token_1_symbol),
+            format!("1 {} = {} {}", self.token_1_symbol, self.price_1_in_0, self.token_0_symbol),
+            String::new(),
+            "🧠 Uniswap V3 Pool Token Balances:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("{}: {:.6}", self.token_0_symbol, self.token_0_balance),
+            format!("{}: {:.6}", self.token_1_symbol, self.token_1_balance),
+        ]
+        .join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(["token", "address", "balance", "price"]);
+        table
+            .row([
+                self.token_0_symbol.clone(),
+                format!("{:?}", self.token_0_addr),
+                self.token_0_balance.to_string(),
+                format!("{} {}", self.price_0_in_1, self.token_1_symbol),
+            ])
+            .row([
+                self.token_1_symbol.clone(),
+                format!("{:?}", self.token_1_addr),
+                self.token_1_balance.to_string(),
+                format!("{} {}", self.price_1_in_0, self.token_0_symbol),
+            ]);
+        table
+    }
+}
+
+/// A simulated swap expressed in the pool's tokens
+#[derive(Debug, Clone, Serialize)]
+pub struct UniswapQuote {
+    pub pool: Address,
+    pub token_in: Address,
+    pub token_out: Address,
+    pub symbol_in: String,
+    pub symbol_out: String,
+    pub amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+    pub fee_amount: TokenAmount,
+    pub exact_out: bool,
+    pub swap: SwapQuote,
+}
+
+impl Report for UniswapQuote {
+    fn text(&self) -> String {
+        [
+            "🧮 Uniswap V3 Quote (simulated offline):".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Pool: {:?}", self.pool),
+            format!("Sell: {} {}", self.amount_in, self.symbol_in),
+            format!("Buy: {} {}", self.amount_out, self.symbol_out),
+            format!("LP fee: {} {}", self.fee_amount, self.symbol_in),
+            format!("Price after: {} (tick {})", self.swap.sqrt_price_after, self.swap.tick_after),
+            format!("Ticks crossed: {}", self.swap.ticks_crossed),
+        ]
+        .join("\n")
+    }
+
+    fn table(&self) -> Table {
+        Table::key_value([
+            ("pool", format!("{:?}", self.pool)),
+            ("token_in", self.symbol_in.clone()),
+            ("token_out", self.symbol_out.clone()),
+            ("amount_in", self.amount_in.to_string()),
+            ("amount_out", self.amount_out.to_string()),
+            ("fee_amount", self.fee_amount.to_string()),
+            ("tick_after", self.swap.tick_after.to_string()),
+            ("ticks_crossed", self.swap.ticks_crossed.to_string()),
+        ])
+    }
+}
+
+// Tick bitmap words loaded on each side of the price by `Pool::refresh`
+pub const DEFAULT_WORD_RADIUS: i16 = 8;
+
+pub struct PoolUniswap<M> {
+    contract: UniswapV3Pool<M>,
+    provider: Arc<M>,
     address: Address,
     info: Option<PoolInfo>,
+    multicall: Option<Address>,
+    word_radius: Option<i16>,
+    state: Option<PoolState>,
 }
 
-impl PoolUniswap {
-    pub fn new(provider: Arc<Provider<Http>>, pool_address: Address) -> Self {
+impl<M: Middleware + 'static> PoolUniswap<M> {
+    pub fn new(provider: Arc<M>, pool_address: Address) -> Self {
         let contract = UniswapV3Pool::new(pool_address, provider.clone());
         Self { 
             contract,
             provider,
             address: pool_address,
             info: None,
+            multicall: None,
+            word_radius: Some(DEFAULT_WORD_RADIUS),
+            state: None,
         }
     }
 
-    pub async fn verify_contract(&self) -> Result<()> {
-        println!("\nVerifying contract at address: {:?}", self.address);
-        
-        // Check if contract exists by getting its code
-        match self.provider.get_code(self.address, None).await {
-            Ok(code) => {
-                if code.is_empty() {
-                    return Err(anyhow::anyhow!("No contract code found at address {:?}", self.address));
-                }
-                println!("Contract code found. Code size: {} bytes", code.len());
-            }
-            Err(e) => {
-                return Err(anyhow::anyhow!("Failed to get contract code: {}", e));
-            }
-        }
+    /// Multicall3 contract used by `Pool::refresh` to load ticks
+    pub fn multicall(mut self, multicall_address: Option<Address>) -> Self {
+        self.multicall = multicall_address;
+        self
+    }
 
-        Ok(())
+    /// Tick bitmap words `Pool::refresh` loads on each side of the price; `None` loads the full range
+    pub fn word_radius(mut self, word_radius: Option<i16>) -> Self {
+        self.word_radius = word_radius;
+        self
     }
 
-    pub async fn get_pool_info(&mut self) -> Result<()> {
-        println!("Fetching Uniswap V3 Pool Info from: {}", self.address);
-        
+    pub async fn get_pool_info(&mut self) -> Result<&PoolInfo> {
         // First verify the contract exists
-        self.verify_contract().await?;
+        verify_contract(&*self.provider, self.address).await?;
         
         // Get pool basic info
         let token_0_addr = self.contract.token_0().call().await?;
@@ -84,6 +248,7 @@ impl PoolUniswap {
         let factory = self.contract.factory().call().await?;
         let fee = self.contract.fee().call().await?;
         let liquidity = self.contract.liquidity().call().await?;
+        let slot0 = self.get_slot0().await?;
 
         // Create token contracts
         let token_0 = IERC20::new(token_0_addr, self.provider.clone());
@@ -99,25 +264,17 @@ impl PoolUniswap {
         let balance_0 = token_0.balance_of(self.address).call().await?;
         let balance_1 = token_1.balance_of(self.address).call().await?;
 
-        // Convert U256 to f64 properly
-        let human_balance_0 = balance_0.as_u128() as f64 / 10f64.powi(decimals_0 as i32);
-        let human_balance_1 = balance_1.as_u128() as f64 / 10f64.powi(decimals_1 as i32);
-
-        // Display pool information
-        println!("\n🪣  Uniswap V3 Pool Info:");
-        println!("-------------------------------------");
-        println!("Fee: {}", fee);
-        println!("Liquidity: {}", liquidity);
-        println!("Factory: {}", factory);
-        println!("Token0: {}", token_0_addr);
-        println!("Token1: {}", token_1_addr);
-        println!("\n🧠 Uniswap V3 Pool Token Balances:");
-        println!("-------------------------------------");
-        println!("{}: {:.6}", symbol_0, human_balance_0);
-        println!("{}: {:.6}", symbol_1, human_balance_1);
+        // Keep balances exact, accounting for decimals
+        let human_balance_0 = TokenAmount::new(balance_0, decimals_0);
+        let human_balance_1 = TokenAmount::new(balance_1, decimals_1);
+
+        // Spot prices come from sqrtPriceX96, not balances, which don't reflect concentrated liquidity
+        let price_0_in_1 = slot0.price_0_in_1(decimals_0, decimals_1)?;
+        let price_1_in_0 = slot0.price_1_in_0(decimals_0, decimals_1)?;
 
         //return all the values
         let pool_info = PoolInfo {
+            address: self.address,
             fee,
             liquidity,
             factory,
@@ -127,15 +284,245 @@ impl PoolUniswap {
             token_1_symbol: symbol_1,
             token_0_balance: human_balance_0,
             token_1_balance: human_balance_1,
+            token_0_decimals: decimals_0,
+            token_1_decimals: decimals_1,
+            slot0,
+            price_0_in_1,
+            price_1_in_0,
         };      
 
-        self.info = Some(pool_info);
+        Ok(self.info.insert(pool_info))
+    }
 
-        Ok(())
+    pub async fn get_slot0(&self) -> Result<Slot0> {
+        let (sqrt_price_x96, tick, observation_index, observation_cardinality, observation_cardinality_next, fee_protocol, unlocked) =
+            self.contract.slot_0().call().await?;
+        Ok(Slot0 {
+            sqrt_price_x96,
+            tick,
+            observation_index,
+            observation_cardinality,
+            observation_cardinality_next,
+            fee_protocol,
+            unlocked,
+        })
+    }
+
+    /// Snapshot of price, liquidity and initialized ticks for offline swap simulation
+    pub async fn load_state(&self, multicall_address: Option<Address>, word_radius: Option<i16>) -> Result<PoolState> {
+        load_pool_state(self.provider.clone(), self.address, multicall_address, word_radius).await
+    }
+
+    /// Active liquidity per tick range within `word_radius` bitmap words of spot, and depth at each percentage
+    pub async fn liquidity_distribution(
+        &mut self,
+        multicall_address: Option<Address>,
+        word_radius: i16,
+        depth_percentages: &[f64],
+    ) -> Result<LiquidityDistribution> {
+        let info = self.get_pool_info().await?.clone();
+        let state = self.load_state(multicall_address, Some(word_radius)).await?;
+        LiquidityDistribution::from_state(&info, &state, depth_percentages)
+    }
+
+    /// Quotes a swap of `amount` (input, or output with `exact_out`) against a loaded state; needs `get_pool_info` first
+    pub fn quote(&self, state: &PoolState, token_in: Address, amount: TokenAmount, exact_out: bool) -> Result<UniswapQuote> {
+        let info = self.info.as_ref().ok_or_else(|| anyhow!("Pool info not loaded, call get_pool_info first"))?;
+        let zero_for_one = if token_in == info.token_0_addr {
+            true
+        } else if token_in == info.token_1_addr {
+            false
+        } else {
+            return Err(anyhow!("Token {:?} is not in pool {:?}", token_in, self.address));
+        };
+        let (token_out, symbol_in, symbol_out, decimals_in, decimals_out) = if zero_for_one {
+            (info.token_1_addr, &info.token_0_symbol, &info.token_1_symbol, info.token_0_decimals, info.token_1_decimals)
+        } else {
+            (info.token_0_addr, &info.token_1_symbol, &info.token_0_symbol, info.token_1_decimals, info.token_0_decimals)
+        };
+
+        let swap = if exact_out {
+            state.quote_exact_out(zero_for_one, amount.raw(), None)?
+        } else {
+            state.quote_exact_in(zero_for_one, amount.raw(), None)?
+        };
+        Ok(UniswapQuote {
+            pool: self.address,
+            token_in,
+            token_out,
+            symbol_in: symbol_in.clone(),
+            symbol_out: symbol_out.clone(),
+            amount_in: TokenAmount::new(swap.amount_in, decimals_in),
+            amount_out: TokenAmount::new(swap.amount_out, decimals_out),
+            fee_amount: TokenAmount::new(swap.fee_amount, decimals_in),
+            exact_out,
+            swap,
+        })
     }
 
     pub fn get_info(&self) -> Option<&PoolInfo> {
         self.info.as_ref()
     }
+
+    /// The state loaded by the last `Pool::refresh`
+    pub fn get_state(&self) -> Option<&PoolState> {
+        self.state.as_ref()
+    }
 }
 
+#[async_trait::async_trait]
+impl<M: Middleware + 'static> Pool for PoolUniswap<M> {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn venue(&self) -> &'static str {
+        "uniswap-v3"
+    }
+
+    fn tokens(&self) -> Result<Vec<PoolToken>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(vec![
+            PoolToken { address: info.token_0_addr, symbol: info.token_0_symbol.clone(), decimals: info.token_0_decimals },
+            PoolToken { address: info.token_1_addr, symbol: info.token_1_symbol.clone(), decimals: info.token_1_decimals },
+        ])
+    }
+
+    fn fee(&self) -> Result<f64> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(info.fee as f64 / 1e6)
+    }
+
+    fn reserves(&self) -> Result<Vec<TokenAmount>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(vec![info.token_0_balance, info.token_1_balance])
+    }
+
+    async fn spot_price(&self, base: Address, quote: Address) -> Result<f64> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(if zero_for_one((info.token_0_addr, info.token_1_addr), base, quote)? {
+            info.price_0_in_1.to_f64()
+        } else {
+            info.price_1_in_0.to_f64()
+        })
+    }
+
+    async fn quote_exact_in(&self, token_in: Address, token_out: Address, amount_in: TokenAmount) -> Result<TokenAmount> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        let state = self.state.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        zero_for_one((info.token_0_addr, info.token_1_addr), token_in, token_out)?;
+        Ok(self.quote(state, token_in, amount_in, false)?.amount_out)
+    }
+
+    async fn refresh(&mut self) -> Result<()> {
+        self.get_pool_info().await?;
+        self.state = Some(self.load_state(self.multicall, self.word_radius).await?);
+        Ok(())
+    }
+
+    // Swaps log the new price, tick and liquidity outright; positions move ticks and balances by the logged amounts.
+    // Burned tokens stay in the pool until they're collected.
+    fn apply_log(&mut self, log: &Log) -> Result<bool> {
+        let (Some(info), Some(state)) = (self.info.as_mut(), self.state.as_mut()) else {
+            return Err(not_loaded(self.address));
+        };
+        let (amount_0, amount_1, into_pool) = match decode_event::<UniswapV3PoolEvents>(log) {
+            Some(UniswapV3PoolEvents::SwapFilter(swap)) => {
+                state.sqrt_price_x96 = swap.sqrt_price_x96;
+                state.tick = swap.tick;
+                state.liquidity = swap.liquidity;
+                info.slot0.sqrt_price_x96 = swap.sqrt_price_x96;
+                info.slot0.tick = swap.tick;
+                info.price_0_in_1 = info.slot0.price_0_in_1(info.token_0_decimals, info.token_1_decimals)?;
+                info.price_1_in_0 = info.slot0.price_1_in_0(info.token_0_decimals, info.token_1_decimals)?;
+                // One side is positive (paid in), the other negative (paid out)
+                info.token_0_balance = shift_balance(info.token_0_balance, swap.amount_0.unsigned_abs(), !swap.amount_0.is_negative())?;
+                info.token_1_balance = shift_balance(info.token_1_balance, swap.amount_1.unsigned_abs(), !swap.amount_1.is_negative())?;
+                (U256::zero(), U256::zero(), true)
+            }
+            Some(UniswapV3PoolEvents::MintFilter(mint)) => {
+                state.modify_position(mint.tick_lower, mint.tick_upper, i128::try_from(mint.amount)?)?;
+                (mint.amount_0, mint.amount_1, true)
+            }
+            Some(UniswapV3PoolEvents::BurnFilter(burn)) => {
+                state.modify_position(burn.tick_lower, burn.tick_upper, -i128::try_from(burn.amount)?)?;
+                (U256::zero(), U256::zero(), false)
+            }
+            Some(UniswapV3PoolEvents::CollectFilter(collect)) => (U256::from(collect.amount_0), U256::from(collect.amount_1), false),
+            None => return Ok(false),
+        };
+        info.liquidity = state.liquidity;
+        info.token_0_balance = shift_balance(info.token_0_balance, amount_0, into_pool)?;
+        info.token_1_balance = shift_balance(info.token_1_balance, amount_1, into_pool)?;
+        Ok(true)
+    }
+
+    fn snapshot(&self) -> PoolSnapshot {
+        PoolSnapshot::new((self.info.clone(), self.state.clone()))
+    }
+
+    fn restore(&mut self, snapshot: PoolSnapshot) -> Result<()> {
+        (self.info, self.state) = snapshot.take()?;
+        Ok(())
+    }
+
+    // The pool pays out first and collects the input through the executor's swap callback, which
+    // reads the input token from `data`; there's no price limit, the executor checks the output
+    fn swap_calls(&self, executor: Address, token_in: Address, token_out: Address, amount_in: U256, _amount_out: U256) -> Result<Vec<RouteCall>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        let zero_for_one = zero_for_one((info.token_0_addr, info.token_1_addr), token_in, token_out)?;
+        let swap = SwapCall {
+            recipient: executor,
+            zero_for_one,
+            amount_specified: I256::from_raw(amount_in),
+            sqrt_price_limit_x96: match zero_for_one {
+                true => min_sqrt_ratio() + 1,
+                false => max_sqrt_ratio() - 1,
+            },
+            data: token_in.encode().into(),
+        };
+        Ok(vec![RouteCall::new(self.address, swap)])
+    }
+}
+
+// A pool balance after an event moved `amount` into or out of the pool
+fn shift_balance(balance: TokenAmount, amount: U256, into_pool: bool) -> Result<TokenAmount> {
+    let raw = match into_pool {
+        true => balance.raw().checked_add(amount),
+        false => balance.raw().checked_sub(amount),
+    };
+    raw.map(|raw| TokenAmount::new(raw, balance.decimals()))
+        .ok_or_else(|| anyhow!("Pool balance out of range after event; pool state is stale"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot0(sqrt_price_x96: U256) -> Slot0 {
+        Slot0 {
+            sqrt_price_x96,
+            tick: 0,
+            observation_index: 0,
+            observation_cardinality: 1,
+            observation_cardinality_next: 1,
+            fee_protocol: 0,
+            unlocked: true,
+        }
+    }
+
+    #[test]
+    fn test_spot_prices_from_sqrt_price() {
+        // sqrtPriceX96 = 2^96 is a raw price of 1
+        let one = slot0(U256::one() << 96);
+        assert_eq!(one.price_0_in_1(18, 18).unwrap().to_string(), "1");
+
+        // USDC (6) / WETH (18) at 2500 USDC per WETH: raw price = 1e12 / 2500 = 4e8, sqrt = 20000
+        let usdc_weth = slot0(U256::from(20_000u64) << 96);
+        assert_eq!(usdc_weth.price_0_in_1(6, 18).unwrap().to_string(), "0.0004");
+        assert_eq!(usdc_weth.price_1_in_0(6, 18).unwrap().to_string(), "2500");
+
+        assert!(slot0(U256::zero()).price_1_in_0(6, 18).is_err());
+    }
+}
@@ -0,0 +1,400 @@
+use ethers::abi::Token;
+use ethers::contract::{Multicall, MULTICALL_ADDRESS};
+use ethers::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use anyhow::{anyhow, bail, Result};
+use crate::account::multicall_balances::DEFAULT_BATCH_SIZE;
+use crate::output::{Report, Table};
+use crate::uniswap::math::{
+    add_liquidity_delta, compute_swap_step, get_sqrt_ratio_at_tick, get_tick_at_sqrt_ratio, max_sqrt_ratio,
+    min_sqrt_ratio, MAX_TICK, MIN_TICK,
+};
+use crate::uniswap::pool::UniswapV3Pool;
+
+/// Liquidity change applied when the price crosses an initialized tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TickInfo {
+    pub liquidity_gross: u128,
+    pub liquidity_net: i128,
+}
+
+/// Local copy of the state a V3 swap reads: price, active liquidity and initialized ticks
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolState {
+    pub sqrt_price_x96: U256,
+    pub tick: i32,
+    pub liquidity: u128,
+    pub fee: u32,
+    pub tick_spacing: i32,
+    // Initialized ticks; a tick is initialized exactly when it has an entry
+    pub ticks: BTreeMap<i32, TickInfo>,
+    // Inclusive range of tick bitmap words that were loaded
+    pub word_range: (i16, i16),
+}
+
+/// Outcome of a simulated swap; amounts are raw token units
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapQuote {
+    pub zero_for_one: bool,
+    pub amount_in: U256,
+    pub amount_out: U256,
+    // Part of `amount_in` paid as LP fee
+    pub fee_amount: U256,
+    pub sqrt_price_after: U256,
+    pub tick_after: i32,
+    pub liquidity_after: u128,
+    pub ticks_crossed: u32,
+}
+
+impl Report for SwapQuote {
+    fn text(&self) -> String {
+        let (token_in, token_out) = if self.zero_for_one { ("token0", "token1") } else { ("token1", "token0") };
+        [
+            "🧮 Simulated Uniswap V3 Swap:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Amount in: {} {} (fee {})", self.amount_in, token_in, self.fee_amount),
+            format!("Amount out: {} {}", self.amount_out, token_out),
+            format!("Price after: {} (tick {})", self.sqrt_price_after, self.tick_after),
+            format!("Ticks crossed: {}", self.ticks_crossed),
+        ]
+        .join("\n")
+    }
+
+    fn table(&self) -> Table {
+        Table::key_value([
+            ("zero_for_one", self.zero_for_one.to_string()),
+            ("amount_in", self.amount_in.to_string()),
+            ("amount_out", self.amount_out.to_string()),
+            ("fee_amount", self.fee_amount.to_string()),
+            ("sqrt_price_after", self.sqrt_price_after.to_string()),
+            ("tick_after", self.tick_after.to_string()),
+            ("ticks_crossed", self.ticks_crossed.to_string()),
+        ])
+    }
+}
+
+impl PoolState {
+    /// Quotes selling exactly `amount_in`; stops early, like the pool, if the price limit is reached
+    pub fn quote_exact_in(&self, zero_for_one: bool, amount_in: U256, sqrt_price_limit_x96: Option<U256>) -> Result<SwapQuote> {
+        self.clone().swap(zero_for_one, amount_in, true, sqrt_price_limit_x96)
+    }
+
+    /// Quotes buying exactly `amount_out`, failing if the pool can't provide it within the price limit
+    pub fn quote_exact_out(&self, zero_for_one: bool, amount_out: U256, sqrt_price_limit_x96: Option<U256>) -> Result<SwapQuote> {
+        let quote = self.clone().swap(zero_for_one, amount_out, false, sqrt_price_limit_x96)?;
+        if quote.amount_out != amount_out {
+            bail!("Pool can only provide {} of the {} requested", quote.amount_out, amount_out);
+        }
+        Ok(quote)
+    }
+
+    /// Runs `UniswapV3Pool.swap` against this state and updates it
+    pub fn swap(
+        &mut self,
+        zero_for_one: bool,
+        amount_specified: U256,
+        exact_in: bool,
+        sqrt_price_limit_x96: Option<U256>,
+    ) -> Result<SwapQuote> {
+        if amount_specified.is_zero() {
+            bail!("Swap amount must be non-zero");
+        }
+        let sqrt_price_limit_x96 = sqrt_price_limit_x96.unwrap_or_else(|| {
+            if zero_for_one { min_sqrt_ratio() + 1 } else { max_sqrt_ratio() - 1 }
+        });
+        let limit_ok = if zero_for_one {
+            sqrt_price_limit_x96 < self.sqrt_price_x96 && sqrt_price_limit_x96 > min_sqrt_ratio()
+        } else {
+            sqrt_price_limit_x96 > self.sqrt_price_x96 && sqrt_price_limit_x96 < max_sqrt_ratio()
+        };
+        if !limit_ok {
+            bail!("Invalid price limit {}", sqrt_price_limit_x96);
+        }
+
+        let mut remaining = amount_specified;
+        let mut calculated = U256::zero();
+        let mut fee_total = U256::zero();
+        let mut ticks_crossed = 0;
+
+        while !remaining.is_zero() && self.sqrt_price_x96 != sqrt_price_limit_x96 {
+            let sqrt_price_start = self.sqrt_price_x96;
+            let (tick_next, initialized) = self.next_initialized_tick_within_one_word(self.tick, zero_for_one)?;
+            let tick_next = tick_next.clamp(MIN_TICK, MAX_TICK);
+            let sqrt_price_next = get_sqrt_ratio_at_tick(tick_next)?;
+
+            let target = if (zero_for_one && sqrt_price_next < sqrt_price_limit_x96)
+                || (!zero_for_one && sqrt_price_next > sqrt_price_limit_x96)
+            {
+                sqrt_price_limit_x96
+            } else {
+                sqrt_price_next
+            };
+
+            let step = compute_swap_step(self.sqrt_price_x96, target, self.liquidity, remaining, exact_in, self.fee)?;
+            self.sqrt_price_x96 = step.sqrt_price_next_x96;
+            fee_total += step.fee_amount;
+
+            if exact_in {
+                remaining -= step.amount_in + step.fee_amount;
+                calculated += step.amount_out;
+            } else {
+                remaining -= step.amount_out;
+                calculated += step.amount_in + step.fee_amount;
+            }
+
+            if self.sqrt_price_x96 == sqrt_price_next {
+                // Crossed into the next range: apply the tick's liquidity change
+                if initialized {
+                    let liquidity_net = self.ticks.get(&tick_next).map(|tick| tick.liquidity_net).unwrap_or(0);
+                    let liquidity_net = if zero_for_one { -liquidity_net } else { liquidity_net };
+                    self.liquidity = add_liquidity_delta(self.liquidity, liquidity_net)?;
+                    ticks_crossed += 1;
+                }
+                self.tick = if zero_for_one { tick_next - 1 } else { tick_next };
+            } else if self.sqrt_price_x96 != sqrt_price_start {
+                self.tick = get_tick_at_sqrt_ratio(self.sqrt_price_x96)?;
+            }
+        }
+
+        let specified_used = amount_specified - remaining;
+        let (amount_in, amount_out) = if exact_in { (specified_used, calculated) } else { (calculated, specified_used) };
+        Ok(SwapQuote {
+            zero_for_one,
+            amount_in,
+            amount_out,
+            fee_amount: fee_total,
+            sqrt_price_after: self.sqrt_price_x96,
+            tick_after: self.tick,
+            liquidity_after: self.liquidity,
+            ticks_crossed,
+        })
+    }
+
+    /// `UniswapV3Pool._modifyPosition` on the ticks and active liquidity: a `Mint` adds
+    /// `liquidity_delta` over `[tick_lower, tick_upper)` and a `Burn` takes it away
+    pub fn modify_position(&mut self, tick_lower: i32, tick_upper: i32, liquidity_delta: i128) -> Result<()> {
+        if tick_lower >= tick_upper {
+            bail!("Invalid position range {}..{}", tick_lower, tick_upper);
+        }
+        for (tick, net) in [(tick_lower, liquidity_delta), (tick_upper, -liquidity_delta)] {
+            let info = self.ticks.entry(tick).or_insert(TickInfo { liquidity_gross: 0, liquidity_net: 0 });
+            info.liquidity_gross = add_liquidity_delta(info.liquidity_gross, liquidity_delta)?;
+            info.liquidity_net = info.liquidity_net.checked_add(net).ok_or_else(|| anyhow!("Liquidity net overflow"))?;
+            // A tick with no positions referencing it is cleared from the bitmap
+            if info.liquidity_gross == 0 {
+                self.ticks.remove(&tick);
+            }
+        }
+        if (tick_lower..tick_upper).contains(&self.tick) {
+            self.liquidity = add_liquidity_delta(self.liquidity, liquidity_delta)?;
+        }
+        Ok(())
+    }
+
+    /// `TickBitmap.nextInitializedTickWithinOneWord` over the loaded ticks
+    fn next_initialized_tick_within_one_word(&self, tick: i32, lte: bool) -> Result<(i32, bool)> {
+        let spacing = self.tick_spacing;
+        let mut compressed = tick / spacing;
+        if tick < 0 && tick % spacing != 0 {
+            compressed -= 1;
+        }
+
+        // The searched word must have been loaded, otherwise initialized ticks could be missed
+        let word = if lte { compressed >> 8 } else { (compressed + 1) >> 8 };
+        if word < self.word_range.0 as i32 || word > self.word_range.1 as i32 {
+            bail!(
+                "Swap reaches tick bitmap word {} outside the loaded range {}..={}; load more words",
+                word,
+                self.word_range.0,
+                self.word_range.1
+            );
+        }
+
+        if lte {
+            let word_start = (compressed >> 8) << 8;
+            let found = self.ticks.range(word_start * spacing..=compressed * spacing).next_back();
+            Ok(match found {
+                Some((tick, _)) => (*tick, true),
+                None => (word_start * spacing, false),
+            })
+        } else {
+            let next = compressed + 1;
+            let word_end = ((next >> 8) << 8) + 255;
+            let found = self.ticks.range(next * spacing..=word_end * spacing).next();
+            Ok(match found {
+                Some((tick, _)) => (*tick, true),
+                None => (word_end * spacing, false),
+            })
+        }
+    }
+}
+
+// Bitmap words spanning every usable tick for a spacing
+fn full_word_range(tick_spacing: i32) -> (i16, i16) {
+    (((MIN_TICK / tick_spacing) >> 8) as i16, ((MAX_TICK / tick_spacing) >> 8) as i16)
+}
+
+/// Loads price, liquidity and initialized ticks through Multicall3
+///
+/// `word_radius` limits loading to that many bitmap words (256 tick spacings each) on each side of the
+/// current tick; `None` loads the full tick range.
+pub async fn load_pool_state<M: Middleware + 'static>(
+    provider: Arc<M>,
+    pool: Address,
+    multicall_address: Option<Address>,
+    word_radius: Option<i16>,
+) -> Result<PoolState> {
+    let contract = UniswapV3Pool::new(pool, provider.clone());
+    let (sqrt_price_x96, tick, ..) = contract.slot_0().call().await?;
+    let liquidity = contract.liquidity().call().await?;
+    let fee = contract.fee().call().await?;
+    let tick_spacing = contract.tick_spacing().call().await?;
+    let multicall_address = multicall_address.unwrap_or(MULTICALL_ADDRESS);
+
+    let (min_word, max_word) = full_word_range(tick_spacing);
+    let word_range = match word_radius {
+        Some(radius) => {
+            let current = (tick / tick_spacing - i32::from(tick < 0 && tick % tick_spacing != 0)) >> 8;
+            (
+                (current - radius as i32).max(min_word as i32) as i16,
+                (current + radius as i32).min(max_word as i32) as i16,
+            )
+        }
+        None => (min_word, max_word),
+    };
+
+    // Bitmap words first, then the liquidity of every set bit
+    let words: Vec<i16> = (word_range.0..=word_range.1).collect();
+    let mut initialized = Vec::new();
+    for batch in words.chunks(DEFAULT_BATCH_SIZE) {
+        let mut multicall = Multicall::new(provider.clone(), Some(multicall_address)).await?;
+        for word in batch {
+            multicall.add_call(contract.tick_bitmap(*word), false);
+        }
+        for (word, result) in batch.iter().zip(multicall.call_raw().await?) {
+            let bitmap = match result {
+                Ok(Token::Uint(bitmap)) => bitmap,
+                _ => bail!("tickBitmap({}) failed", word),
+            };
+            for bit in (0..256).filter(|bit| bitmap.bit(*bit)) {
+                initialized.push(((*word as i32) * 256 + bit as i32) * tick_spacing);
+            }
+        }
+    }
+
+    let mut ticks = BTreeMap::new();
+    for batch in initialized.chunks(DEFAULT_BATCH_SIZE) {
+        let mut multicall = Multicall::new(provider.clone(), Some(multicall_address)).await?;
+        for tick in batch {
+            multicall.add_call(contract.ticks(*tick), false);
+        }
+        for (tick, result) in batch.iter().zip(multicall.call_raw().await?) {
+            let fields = match result {
+                Ok(Token::Tuple(fields)) => fields,
+                _ => bail!("ticks({}) failed", tick),
+            };
+            let liquidity_gross = fields.first().cloned().and_then(Token::into_uint).ok_or_else(|| anyhow!("Bad ticks({})", tick))?;
+            let liquidity_net = fields.get(1).cloned().and_then(Token::into_int).ok_or_else(|| anyhow!("Bad ticks({})", tick))?;
+            ticks.insert(
+                *tick,
+                TickInfo {
+                    liquidity_gross: liquidity_gross.as_u128(),
+                    liquidity_net: I256::from_raw(liquidity_net).as_i128(),
+                },
+            );
+        }
+    }
+
+    Ok(PoolState {
+        sqrt_price_x96,
+        tick,
+        liquidity,
+        fee,
+        tick_spacing,
+        ticks,
+        word_range,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two overlapping positions around price 1: [-600, 600] with 1e18 and [-60, 60] with 2e18
+    fn state() -> PoolState {
+        let mut ticks = BTreeMap::new();
+        for (tick, net) in [(-600, 10i128.pow(18)), (-60, 2 * 10i128.pow(18)), (60, -2 * 10i128.pow(18)), (600, -(10i128.pow(18)))] {
+            ticks.insert(tick, TickInfo { liquidity_gross: net.unsigned_abs(), liquidity_net: net });
+        }
+        PoolState {
+            sqrt_price_x96: U256::one() << 96,
+            tick: 0,
+            liquidity: 3 * 10u128.pow(18),
+            fee: 3000,
+            tick_spacing: 60,
+            ticks,
+            word_range: full_word_range(60),
+        }
+    }
+
+    #[test]
+    fn test_swap_crosses_ticks_and_round_trips() {
+        let state = state();
+
+        // Small swap stays in range
+        let small = state.quote_exact_in(true, U256::exp10(15), None).unwrap();
+        assert_eq!(small.ticks_crossed, 0);
+        assert_eq!(small.liquidity_after, 3 * 10u128.pow(18));
+
+        // Large swap leaves the narrow position, then the wide one
+        let large = state.quote_exact_in(true, U256::exp10(18), None).unwrap();
+        assert_eq!(large.ticks_crossed, 2);
+        assert_eq!(large.liquidity_after, 0);
+        assert!(large.amount_in < U256::exp10(18));
+
+        // Exact out for the exact-in result needs no more than the original input
+        let mid = state.quote_exact_in(false, U256::from(2u64) * U256::exp10(16), None).unwrap();
+        assert_eq!(mid.ticks_crossed, 1);
+        let back = state.quote_exact_out(false, mid.amount_out, None).unwrap();
+        assert_eq!(back.amount_out, mid.amount_out);
+        assert!(back.amount_in <= mid.amount_in);
+        assert!(mid.amount_in - back.amount_in <= U256::one());
+
+        assert!(state.quote_exact_out(true, U256::exp10(19), None).is_err());
+    }
+
+    #[test]
+    fn test_next_initialized_tick_respects_word_boundaries() {
+        let state = state();
+        // Tick 0 starts word 0, so searching down from it stops at the word boundary
+        assert_eq!(state.next_initialized_tick_within_one_word(0, true).unwrap(), (0, false));
+        assert_eq!(state.next_initialized_tick_within_one_word(-1, true).unwrap(), (-60, true));
+        assert_eq!(state.next_initialized_tick_within_one_word(0, false).unwrap(), (60, true));
+        assert_eq!(state.next_initialized_tick_within_one_word(-601, true).unwrap(), (-256 * 60, false));
+        assert_eq!(state.next_initialized_tick_within_one_word(600, false).unwrap(), (255 * 60, false));
+
+        let mut narrow = state.clone();
+        narrow.word_range = (0, 0);
+        assert!(narrow.next_initialized_tick_within_one_word(-61, true).is_err());
+    }
+
+    #[test]
+    fn test_modify_position_updates_ticks_and_liquidity() {
+        let original = state();
+        let mut state = original.clone();
+        // A new position around the price adds active liquidity; one above it doesn't
+        state.modify_position(-120, 120, 10i128.pow(18)).unwrap();
+        state.modify_position(600, 1200, 10i128.pow(18)).unwrap();
+        assert_eq!(state.liquidity, 4 * 10u128.pow(18));
+        assert_eq!(state.ticks[&600], TickInfo { liquidity_gross: 2 * 10u128.pow(18), liquidity_net: 0 });
+
+        // Burning them restores the original ticks
+        state.modify_position(-120, 120, -(10i128.pow(18))).unwrap();
+        state.modify_position(600, 1200, -(10i128.pow(18))).unwrap();
+        assert_eq!(state.liquidity, 3 * 10u128.pow(18));
+        assert_eq!(state.ticks, original.ticks);
+        assert!(state.modify_position(60, -60, 1).is_err());
+    }
+}
@@ -0,0 +1,430 @@
+use ethers::prelude::*;
+use std::sync::Arc;
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+use crate::amount::TokenAmount;
+use crate::output::{Report, Table};
+use crate::pool::{decode_event, not_loaded, verify_contract, zero_for_one, Pool, PoolSnapshot, PoolToken, RouteCall};
+
+// Uniswap V2 pair and factory ABI fragments; Sushiswap and most forks share them
+abigen!(
+    UniswapV2Pair,
+    r#"[
+        function token0() external view returns (address)
+        function token1() external view returns (address)
+        function factory() external view returns (address)
+        function getReserves() external view returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast)
+        function swap(uint256 amount0Out, uint256 amount1Out, address to, bytes data) external
+        event Sync(uint112 reserve0, uint112 reserve1)
+    ]"#
+);
+
+abigen!(
+    UniswapV2Factory,
+    r#"[
+        function getPair(address tokenA, address tokenB) external view returns (address pair)
+    ]"#
+);
+
+// Uniswap V2 and Sushiswap charge 0.3%; forks with other fees can override it
+pub const DEFAULT_FEE_BPS: u32 = 30;
+const BPS: u32 = 10_000;
+
+/// `UniswapV2Library.getAmountOut`, generalised to any fee in basis points
+pub fn get_amount_out(amount_in: U256, reserve_in: U256, reserve_out: U256, fee_bps: u32) -> Result<U256> {
+    if amount_in.is_zero() {
+        bail!("Insufficient input amount");
+    }
+    if reserve_in.is_zero() || reserve_out.is_zero() {
+        bail!("Insufficient liquidity");
+    }
+    let amount_in_with_fee = amount_in * U256::from(BPS - fee_bps);
+    let numerator = amount_in_with_fee * reserve_out;
+    let denominator = reserve_in * U256::from(BPS) + amount_in_with_fee;
+    Ok(numerator / denominator)
+}
+
+/// `UniswapV2Library.getAmountIn`: the input needed to receive exactly `amount_out`, rounded up
+pub fn get_amount_in(amount_out: U256, reserve_in: U256, reserve_out: U256, fee_bps: u32) -> Result<U256> {
+    if amount_out.is_zero() {
+        bail!("Insufficient output amount");
+    }
+    if reserve_in.is_zero() || amount_out >= reserve_out {
+        bail!("Insufficient liquidity");
+    }
+    let numerator = reserve_in * amount_out * U256::from(BPS);
+    let denominator = (reserve_out - amount_out) * U256::from(BPS - fee_bps);
+    Ok(numerator / denominator + 1)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolInfo {
+    pub address: Address,
+    pub factory: Address,
+    pub fee_bps: u32,
+    pub token_0_addr: Address,
+    pub token_1_addr: Address,
+    pub token_0_symbol: String,
+    pub token_1_symbol: String,
+    pub token_0_decimals: u8,
+    pub token_1_decimals: u8,
+    pub reserve_0: TokenAmount,
+    pub reserve_1: TokenAmount,
+    pub block_timestamp_last: u32,
+    // Spot prices from the reserves: token1 per token0, and token0 per token1
+    pub price_0_in_1: TokenAmount,
+    pub price_1_in_0: TokenAmount,
+}
+
+impl Report for PoolInfo {
+    fn text(&self) -> String {
+        [
+            "🪣  Uniswap V2 Pair Info:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Pair: {:?}", self.address),
+            format!("Factory: {:?}", self.factory),
+            format!("Fee: {} bps", self.fee_bps),
+            format!("Token0: {:?}", self.token_0_addr),
+            format!("Token1: {:?}", self.token_1_addr),
+            format!("Last update: {}", self.block_timestamp_last),
+            String::new(),
+            "🧠 Reserves:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("{}: {:.6}", self.token_0_symbol, self.reserve_0),
+            format!("{}: {:.6}", self.token_1_symbol, self.reserve_1),
+            format!("1 {} = {} {}", self.token_0_symbol, self.price_0_in_1, self.token_1_symbol),
+            format!("1 {} = {} {}", self.token_1_symbol, self.price_1_in_0, self.token_0_symbol),
+        ]
+        .join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(["token", "address", "reserve", "price"]);
+        table
+            .row([
+                self.token_0_symbol.clone(),
+                format!("{:?}", self.token_0_addr),
+                self.reserve_0.to_string(),
+                format!("{} {}", self.price_0_in_1, self.token_1_symbol),
+            ])
+            .row([
+                self.token_1_symbol.clone(),
+                format!("{:?}", self.token_1_addr),
+                self.reserve_1.to_string(),
+                format!("{} {}", self.price_1_in_0, self.token_0_symbol),
+            ]);
+        table
+    }
+}
+
+/// A quote computed from cached reserves with the pair's exact integer math
+#[derive(Debug, Clone, Serialize)]
+pub struct V2Quote {
+    pub pool: Address,
+    pub token_in: Address,
+    pub token_out: Address,
+    pub symbol_in: String,
+    pub symbol_out: String,
+    pub amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+    pub exact_out: bool,
+}
+
+impl Report for V2Quote {
+    fn text(&self) -> String {
+        [
+            "🧮 Uniswap V2 Quote:".to_string(),
+            "-------------------------------------".to_string(),
+            format!("Pair: {:?}", self.pool),
+            format!("Sell: {} {}", self.amount_in, self.symbol_in),
+            format!("Buy: {} {}", self.amount_out, self.symbol_out),
+        ]
+        .join("\n")
+    }
+
+    fn table(&self) -> Table {
+        Table::key_value([
+            ("pool", format!("{:?}", self.pool)),
+            ("token_in", self.symbol_in.clone()),
+            ("token_out", self.symbol_out.clone()),
+            ("amount_in", self.amount_in.to_string()),
+            ("amount_out", self.amount_out.to_string()),
+        ])
+    }
+}
+
+pub struct PoolUniswapV2<M> {
+    contract: UniswapV2Pair<M>,
+    provider: Arc<M>,
+    address: Address,
+    fee_bps: u32,
+    info: Option<PoolInfo>,
+}
+
+impl<M: Middleware + 'static> PoolUniswapV2<M> {
+    pub fn new(provider: Arc<M>, pool_address: Address) -> Self {
+        let contract = UniswapV2Pair::new(pool_address, provider.clone());
+        Self {
+            contract,
+            provider,
+            address: pool_address,
+            fee_bps: DEFAULT_FEE_BPS,
+            info: None,
+        }
+    }
+
+    /// Finds the pair for two tokens through the factory's `getPair`
+    pub async fn from_factory(provider: Arc<M>, factory: Address, token_a: Address, token_b: Address) -> Result<Self> {
+        let factory_contract = UniswapV2Factory::new(factory, provider.clone());
+        let pair = factory_contract.get_pair(token_a, token_b).call().await?;
+        if pair.is_zero() {
+            bail!("Factory {:?} has no pair for {:?} and {:?}", factory, token_a, token_b);
+        }
+        Ok(Self::new(provider, pair))
+    }
+
+    pub fn fee_bps(mut self, fee_bps: u32) -> Self {
+        self.fee_bps = fee_bps.min(BPS - 1);
+        self
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    pub async fn get_reserves(&self) -> Result<(U256, U256, u32)> {
+        let (reserve_0, reserve_1, block_timestamp_last) = self.contract.get_reserves().call().await?;
+        Ok((U256::from(reserve_0), U256::from(reserve_1), block_timestamp_last))
+    }
+
+    pub async fn get_pool_info(&mut self) -> Result<&PoolInfo> {
+        verify_contract(&*self.provider, self.address).await?;
+
+        let token_0_addr = self.contract.token_0().call().await?;
+        let token_1_addr = self.contract.token_1().call().await?;
+        let factory = self.contract.factory().call().await?;
+        let (reserve_0, reserve_1, block_timestamp_last) = self.get_reserves().await?;
+
+        let token_0 = PoolToken::fetch(self.provider.clone(), token_0_addr).await?;
+        let token_1 = PoolToken::fetch(self.provider.clone(), token_1_addr).await?;
+        let (decimals_0, decimals_1) = (token_0.decimals, token_1.decimals);
+
+        let pool_info = PoolInfo {
+            address: self.address,
+            factory,
+            fee_bps: self.fee_bps,
+            token_0_addr,
+            token_1_addr,
+            token_0_symbol: token_0.symbol,
+            token_1_symbol: token_1.symbol,
+            token_0_decimals: decimals_0,
+            token_1_decimals: decimals_1,
+            reserve_0: TokenAmount::new(reserve_0, decimals_0),
+            reserve_1: TokenAmount::new(reserve_1, decimals_1),
+            block_timestamp_last,
+            price_0_in_1: reserve_price(reserve_1, decimals_1, reserve_0, decimals_0),
+            price_1_in_0: reserve_price(reserve_0, decimals_0, reserve_1, decimals_1),
+        };
+
+        Ok(self.info.insert(pool_info))
+    }
+
+    pub fn get_info(&self) -> Option<&PoolInfo> {
+        self.info.as_ref()
+    }
+
+    /// Quotes against the reserves from the last `get_pool_info`
+    pub fn quote(&self, token_in: Address, amount: TokenAmount, exact_out: bool) -> Result<V2Quote> {
+        let info = self.info.as_ref().ok_or_else(|| anyhow!("Pool info not loaded, call get_pool_info first"))?;
+        let (reserve_in, reserve_out, token_out, symbol_in, symbol_out) = if token_in == info.token_0_addr {
+            (info.reserve_0, info.reserve_1, info.token_1_addr, &info.token_0_symbol, &info.token_1_symbol)
+        } else if token_in == info.token_1_addr {
+            (info.reserve_1, info.reserve_0, info.token_0_addr, &info.token_1_symbol, &info.token_0_symbol)
+        } else {
+            bail!("Token {:?} is not in pair {:?}", token_in, self.address);
+        };
+
+        let (amount_in, amount_out) = if exact_out {
+            let amount_in = get_amount_in(amount.raw(), reserve_in.raw(), reserve_out.raw(), info.fee_bps)?;
+            (amount_in, amount.raw())
+        } else {
+            (amount.raw(), get_amount_out(amount.raw(), reserve_in.raw(), reserve_out.raw(), info.fee_bps)?)
+        };
+
+        Ok(V2Quote {
+            pool: self.address,
+            token_in,
+            token_out,
+            symbol_in: symbol_in.clone(),
+            symbol_out: symbol_out.clone(),
+            amount_in: TokenAmount::new(amount_in, reserve_in.decimals()),
+            amount_out: TokenAmount::new(amount_out, reserve_out.decimals()),
+            exact_out,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<M: Middleware + 'static> Pool for PoolUniswapV2<M> {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn venue(&self) -> &'static str {
+        "uniswap-v2"
+    }
+
+    fn tokens(&self) -> Result<Vec<PoolToken>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(vec![
+            PoolToken { address: info.token_0_addr, symbol: info.token_0_symbol.clone(), decimals: info.token_0_decimals },
+            PoolToken { address: info.token_1_addr, symbol: info.token_1_symbol.clone(), decimals: info.token_1_decimals },
+        ])
+    }
+
+    fn fee(&self) -> Result<f64> {
+        Ok(self.fee_bps as f64 / BPS as f64)
+    }
+
+    fn reserves(&self) -> Result<Vec<TokenAmount>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(vec![info.reserve_0, info.reserve_1])
+    }
+
+    async fn spot_price(&self, base: Address, quote: Address) -> Result<f64> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        Ok(if zero_for_one((info.token_0_addr, info.token_1_addr), base, quote)? {
+            info.price_0_in_1.to_f64()
+        } else {
+            info.price_1_in_0.to_f64()
+        })
+    }
+
+    async fn quote_exact_in(&self, token_in: Address, token_out: Address, amount_in: TokenAmount) -> Result<TokenAmount> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        zero_for_one((info.token_0_addr, info.token_1_addr), token_in, token_out)?;
+        Ok(self.quote(token_in, amount_in, false)?.amount_out)
+    }
+
+    async fn refresh(&mut self) -> Result<()> {
+        self.get_pool_info().await?;
+        Ok(())
+    }
+
+    // Every reserve change ends in a `Sync` with the new reserves, so it's the only event needed
+    fn apply_log(&mut self, log: &Log) -> Result<bool> {
+        let info = self.info.as_mut().ok_or_else(|| not_loaded(self.address))?;
+        let Some(sync) = decode_event::<SyncFilter>(log) else {
+            return Ok(false);
+        };
+        let (reserve_0, reserve_1) = (U256::from(sync.reserve_0), U256::from(sync.reserve_1));
+        info.reserve_0 = TokenAmount::new(reserve_0, info.token_0_decimals);
+        info.reserve_1 = TokenAmount::new(reserve_1, info.token_1_decimals);
+        info.price_0_in_1 = reserve_price(reserve_1, info.token_1_decimals, reserve_0, info.token_0_decimals);
+        info.price_1_in_0 = reserve_price(reserve_0, info.token_0_decimals, reserve_1, info.token_1_decimals);
+        Ok(true)
+    }
+
+    fn snapshot(&self) -> PoolSnapshot {
+        PoolSnapshot::new(self.info.clone())
+    }
+
+    fn restore(&mut self, snapshot: PoolSnapshot) -> Result<()> {
+        self.info = snapshot.take()?;
+        Ok(())
+    }
+
+    // The input is sent to the pair up front and the pair is told what to pay out, which must not
+    // be more than the reserves allow for that input
+    fn swap_calls(&self, executor: Address, token_in: Address, token_out: Address, amount_in: U256, amount_out: U256) -> Result<Vec<RouteCall>> {
+        let info = self.info.as_ref().ok_or_else(|| not_loaded(self.address))?;
+        let (amount_0_out, amount_1_out) = match zero_for_one((info.token_0_addr, info.token_1_addr), token_in, token_out)? {
+            true => (U256::zero(), amount_out),
+            false => (amount_out, U256::zero()),
+        };
+        Ok(vec![
+            RouteCall::transfer(token_in, self.address, amount_in),
+            RouteCall::new(self.address, SwapCall { amount_0_out, amount_1_out, to: executor, data: Bytes::new() }),
+        ])
+    }
+}
+
+// Price of the base token in the quote token, truncated to 18 decimals
+fn reserve_price(quote: U256, quote_decimals: u8, base: U256, base_decimals: u8) -> TokenAmount {
+    if base.is_zero() {
+        return TokenAmount::zero(18);
+    }
+    let numerator = U512::from(quote) * U512::exp10(base_decimals as usize + 18);
+    let denominator = U512::from(base) * U512::exp10(quote_decimals as usize);
+    TokenAmount::new(U256::try_from(numerator / denominator).unwrap_or(U256::MAX), 18)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amount_out_and_in_match_uniswap_library() {
+        // 1 WETH into a 1000 WETH / 2,000,000 USDC pair
+        let reserve_weth = U256::exp10(21);
+        let reserve_usdc = U256::from(2_000_000u64) * U256::exp10(6);
+        let out = get_amount_out(U256::exp10(18), reserve_weth, reserve_usdc, DEFAULT_FEE_BPS).unwrap();
+        // 1e18 * 997 * 2e12 / (1e21 * 1000 + 1e18 * 997)
+        assert_eq!(out, U256::from(1_992_013_962u64));
+
+        // Buying that output back costs at most the original input
+        let amount_in = get_amount_in(out, reserve_weth, reserve_usdc, DEFAULT_FEE_BPS).unwrap();
+        assert!(amount_in <= U256::exp10(18));
+        assert!(get_amount_out(amount_in, reserve_weth, reserve_usdc, DEFAULT_FEE_BPS).unwrap() >= out);
+
+        assert!(get_amount_in(reserve_usdc, reserve_weth, reserve_usdc, DEFAULT_FEE_BPS).is_err());
+        assert_eq!(
+            reserve_price(reserve_usdc, 6, reserve_weth, 18).to_string(),
+            "2000"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pool_trait_uses_cached_reserves() {
+        let (provider, _mock) = Provider::mocked();
+        let (weth, usdc) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let mut pair = PoolUniswapV2::new(Arc::new(provider), Address::repeat_byte(3));
+        assert!(Pool::tokens(&pair).is_err());
+
+        let (reserve_weth, reserve_usdc) = (U256::exp10(21), U256::from(2_000_000u64) * U256::exp10(6));
+        pair.info = Some(PoolInfo {
+            address: pair.address,
+            factory: Address::zero(),
+            fee_bps: DEFAULT_FEE_BPS,
+            token_0_addr: weth,
+            token_1_addr: usdc,
+            token_0_symbol: "WETH".to_string(),
+            token_1_symbol: "USDC".to_string(),
+            token_0_decimals: 18,
+            token_1_decimals: 6,
+            reserve_0: TokenAmount::new(reserve_weth, 18),
+            reserve_1: TokenAmount::new(reserve_usdc, 6),
+            block_timestamp_last: 0,
+            price_0_in_1: reserve_price(reserve_usdc, 6, reserve_weth, 18),
+            price_1_in_0: reserve_price(reserve_weth, 18, reserve_usdc, 6),
+        });
+
+        let pool: &dyn Pool = &pair;
+        assert_eq!(pool.token_index(usdc).unwrap(), 1);
+        assert_eq!(pool.fee().unwrap(), 0.003);
+        assert_eq!(pool.spot_price(weth, usdc).await.unwrap(), 2000.0);
+        let out = pool.quote_exact_in(weth, usdc, TokenAmount::new(U256::exp10(18), 18)).await.unwrap();
+        assert_eq!(out.raw(), U256::from(1_992_013_962u64));
+        assert!(pool.quote_exact_in(weth, weth, TokenAmount::new(U256::exp10(18), 18)).await.is_err());
+
+        // A `Sync` after a swap replaces the reserves; other events need a refresh
+        let sync = |reserves: [U256; 2]| Log {
+            topics: vec![SyncFilter::signature()],
+            data: ethers::abi::encode(&reserves.map(ethers::abi::Token::Uint)).into(),
+            ..Default::default()
+        };
+        assert!(pair.apply_log(&sync([U256::exp10(21) * 2, reserve_usdc / 2])).unwrap());
+        assert_eq!(Pool::spot_price(&pair, weth, usdc).await.unwrap(), 500.0);
+        assert!(!pair.apply_log(&Log::default()).unwrap());
+    }
+}